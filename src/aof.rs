@@ -0,0 +1,520 @@
+//! AOF 持久化：以 RESP 格式追加每条写命令，启动时重放。
+//! 与 Redis 7 一样由清单记录的基础文件和增量文件组成，重写时生成新的基础文件
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        LazyLock,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{
+    config,
+    rdb::{self, Snapshot},
+    resp::{RespError, RespParser, RespType},
+};
+
+use manifest::Manifest;
+
+mod manifest;
+mod propagate;
+mod rewrite;
+
+pub const DEFAULT_APPENDFILENAME: &str = "appendonly.aof";
+pub const DEFAULT_APPENDDIRNAME: &str = "appendonlydir";
+/// 与 Redis 的默认值一致：比上次重写后增长一倍且超过 64mb 时自动重写
+pub const DEFAULT_AUTO_REWRITE_PERCENTAGE: &str = "100";
+pub const DEFAULT_AUTO_REWRITE_MIN_SIZE: &str = "64mb";
+/// everysec 策略下后台 fsync 的间隔
+const FSYNC_PERIOD: Duration = Duration::from_secs(1);
+/// 与 Redis 默认的 hz 一致，每秒检查 10 次是否需要自动重写
+const REWRITE_CRON_PERIOD: Duration = Duration::from_millis(100);
+const REWRITE_IN_PROGRESS: &str = "ERR Background append only file rewriting already in progress";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Fsync {
+    /// 每条命令写入后立即 fsync
+    Always,
+    /// 每秒 fsync 一次
+    Everysec,
+    /// 由操作系统决定何时刷盘
+    No,
+}
+
+impl Fsync {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "always" => Some(Fsync::Always),
+            "everysec" => Some(Fsync::Everysec),
+            "no" => Some(Fsync::No),
+            _ => None,
+        }
+    }
+}
+
+pub struct AofFile {
+    file: File,
+    fsync: Fsync,
+    /// 最后一条命令所在的数据库，切换时先写入 SELECT
+    selected_db: Option<usize>,
+    /// 上次 fsync 之后是否有新的写入
+    unsynced: bool,
+}
+
+impl AofFile {
+    /// 打开增量文件用于追加
+    async fn open(path: &Path, fsync: Fsync) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path).await?;
+        Ok(Self {
+            file,
+            fsync,
+            selected_db: None,
+            unsynced: false,
+        })
+    }
+
+    /// 追加一条写命令，reply 是命令的执行结果，用于改写为确定的形式。返回写入的字节数
+    async fn feed(&mut self, db: usize, command: &str, args: &[Bytes], reply: &RespType) -> io::Result<u64> {
+        let commands = propagate::rewrite(command, args, reply).await;
+        if commands.is_empty() {
+            return Ok(0);
+        }
+        let mut buf = Vec::new();
+        if self.selected_db != Some(db) {
+            buf.extend(serialize(vec![Bytes::from("SELECT"), Bytes::from(db.to_string())]));
+            self.selected_db = Some(db);
+        }
+        for command in commands {
+            buf.extend(serialize(command));
+        }
+        self.file.write_all(&buf).await?;
+        self.file.flush().await?;
+        if self.fsync == Fsync::Always {
+            self.file.sync_data().await?;
+        } else {
+            self.unsynced = true;
+        }
+        Ok(buf.len() as u64)
+    }
+}
+
+pub struct AofState {
+    manifest: Manifest,
+    /// 当前的增量文件，未开启 AOF 时为 None
+    file: Option<AofFile>,
+    /// 当前增量文件的大小
+    incr_size: u64,
+}
+
+impl AofState {
+    pub async fn feed(&mut self, db: usize, command: &str, args: &[Bytes], reply: &RespType) -> io::Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            let written = file.feed(db, command, args, reply).await?;
+            self.incr_size += written;
+            CURRENT_SIZE.fetch_add(written, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+fn serialize(command: Vec<Bytes>) -> Vec<u8> {
+    let elements = command.into_iter().map(|arg| RespType::BulkString(Some(arg))).collect();
+    RespType::Array(Some(elements)).serialize()
+}
+
+static AOF: LazyLock<Mutex<AofState>> = LazyLock::new(|| {
+    Mutex::new(AofState {
+        manifest: Manifest::default(),
+        file: None,
+        incr_size: 0,
+    })
+});
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static REWRITE_RUNNING: AtomicBool = AtomicBool::new(false);
+static LAST_REWRITE_OK: AtomicBool = AtomicBool::new(true);
+/// 所有 AOF 文件的总大小
+static CURRENT_SIZE: AtomicU64 = AtomicU64::new(0);
+/// 启动或最近一次重写完成时的大小，用于计算增长的比例
+static BASE_SIZE: AtomicU64 = AtomicU64::new(0);
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn rewrite_in_progress() -> bool {
+    REWRITE_RUNNING.load(Ordering::Relaxed)
+}
+
+/// 最近一次重写是否成功
+pub fn last_rewrite_ok() -> bool {
+    LAST_REWRITE_OK.load(Ordering::Relaxed)
+}
+
+pub fn current_size() -> u64 {
+    CURRENT_SIZE.load(Ordering::Relaxed)
+}
+
+pub fn base_size() -> u64 {
+    BASE_SIZE.load(Ordering::Relaxed)
+}
+
+/// 执行写命令前获取，持有期间其他写命令不能执行，保证 AOF 中的顺序与执行顺序一致。
+/// 未开启 AOF 时返回 None
+pub async fn lock() -> Option<MutexGuard<'static, AofState>> {
+    if !enabled() {
+        return None;
+    }
+    Some(AOF.lock().await)
+}
+
+async fn filename() -> String {
+    config::get("appendfilename").await.unwrap_or_else(|| DEFAULT_APPENDFILENAME.to_string())
+}
+
+async fn server_dir() -> PathBuf {
+    PathBuf::from(config::get("dir").await.unwrap_or_else(|| ".".to_string()))
+}
+
+/// 存放清单、基础文件和增量文件的目录
+async fn aof_dir() -> PathBuf {
+    let dirname = config::get("appenddirname").await;
+    server_dir().await.join(dirname.as_deref().unwrap_or(DEFAULT_APPENDDIRNAME))
+}
+
+async fn manifest_path() -> PathBuf {
+    aof_dir().await.join(format!("{}.manifest", filename().await))
+}
+
+/// 先写入临时文件再重命名，保证清单总是完整的
+async fn persist_manifest(manifest: &Manifest) -> io::Result<()> {
+    let path = manifest_path().await;
+    let tmp = aof_dir().await.join(format!("temp-{}.manifest", filename().await));
+    let result = async {
+        let mut file = File::create(&tmp).await?;
+        file.write_all(manifest.to_string().as_bytes()).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    result
+}
+
+/// 从清单中移除历史文件后再删除这些文件
+async fn delete_history_files(manifest: &mut Manifest) -> io::Result<()> {
+    if manifest.history.is_empty() {
+        return Ok(());
+    }
+    let history = std::mem::take(&mut manifest.history);
+    persist_manifest(manifest).await?;
+    let dir = aof_dir().await;
+    for info in history {
+        println!("Removing the history file {} in the background", info.name);
+        let _ = tokio::fs::remove_file(dir.join(&info.name)).await;
+    }
+    Ok(())
+}
+
+async fn file_size(path: &Path) -> u64 {
+    tokio::fs::metadata(path).await.map_or(0, |metadata| metadata.len())
+}
+
+/// 读取清单，返回需要按顺序加载的基础文件和增量文件。
+/// 旧版本的单个 AOF 文件会先移动到 AOF 目录中作为基础文件
+pub async fn load_manifest() -> Result<Vec<PathBuf>, String> {
+    let dir = aof_dir().await;
+    let path = manifest_path().await;
+    let mut manifest = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => Manifest::parse(&content)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Manifest::default(),
+        Err(e) => return Err(format!("Fail to open AOF manifest {}: {}", path.display(), e)),
+    };
+    let filename = filename().await;
+    let old = server_dir().await.join(&filename);
+    if manifest.base.is_none() && manifest.incrs.is_empty() && tokio::fs::try_exists(&old).await.unwrap_or(false) {
+        println!("Creating AOF dir and upgrading {} to the multi part AOF format", filename);
+        manifest = upgrade(&old, &filename).await.map_err(|e| format!("Failed to upgrade the AOF: {}", e))?;
+    }
+    let mut paths = Vec::new();
+    for info in manifest.base.iter().chain(&manifest.incrs) {
+        let path = dir.join(&info.name);
+        if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            return Err(format!("The AOF file {} doesn't exist", info.name));
+        }
+        paths.push(path);
+    }
+    AOF.lock().await.manifest = manifest;
+    Ok(paths)
+}
+
+/// 把旧的 AOF 文件原样作为第一个基础文件
+async fn upgrade(old: &Path, filename: &str) -> io::Result<Manifest> {
+    let dir = aof_dir().await;
+    tokio::fs::create_dir_all(&dir).await?;
+    let mut manifest = Manifest::default();
+    manifest.new_base(filename, false);
+    if let Some(base) = manifest.base.as_mut() {
+        base.name = filename.to_string();
+    }
+    persist_manifest(&manifest).await?;
+    tokio::fs::rename(old, dir.join(filename)).await?;
+    Ok(manifest)
+}
+
+/// 加载完成后打开最后一个增量文件用于追加，之后的写命令都会被记录。
+/// 没有任何 AOF 文件时先根据当前数据生成基础文件
+pub async fn open(fsync: Fsync) -> Result<(), String> {
+    let dir = aof_dir().await;
+    tokio::fs::create_dir_all(&dir).await.map_err(|e| format!("Can't create the AOF directory: {}", e))?;
+    let filename = filename().await;
+    let mut state = AOF.lock().await;
+    if state.manifest.base.is_none() && state.manifest.incrs.is_empty() {
+        let (tmp, size) = write_temp_base(rdb::snapshot().await, use_rdb_preamble().await).await?;
+        install_base(&mut state, &tmp, size).await?;
+    }
+    let mut manifest = state.manifest.clone();
+    let name = match manifest.incrs.last() {
+        Some(incr) => incr.name.clone(),
+        None => manifest.new_incr(&filename),
+    };
+    let path = dir.join(&name);
+    let file = AofFile::open(&path, fsync).await.map_err(|e| format!("Can't open the append-only file {}: {}", name, e))?;
+    persist_manifest(&manifest).await.map_err(|e| format!("Can't persist the AOF manifest: {}", e))?;
+    if let Err(e) = delete_history_files(&mut manifest).await {
+        println!("Can't remove the AOF history files: {}", e);
+    }
+    state.manifest = manifest;
+    state.file = Some(file);
+    state.incr_size = file_size(&path).await;
+    let mut size = 0;
+    for info in state.manifest.base.iter().chain(&state.manifest.incrs) {
+        size += file_size(&dir.join(&info.name)).await;
+    }
+    CURRENT_SIZE.store(size, Ordering::Relaxed);
+    BASE_SIZE.store(size, Ordering::Relaxed);
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+async fn use_rdb_preamble() -> bool {
+    config::get("aof-use-rdb-preamble").await.is_none_or(|value| value == "yes")
+}
+
+/// 在后台线程中编码快照，写入临时文件并刷到磁盘，返回临时文件的路径和大小
+async fn write_temp_base(snapshot: Snapshot, use_rdb: bool) -> Result<(PathBuf, u64), String> {
+    let tmp = server_dir().await.join(format!("temp-rewriteaof-bg-{}.aof", std::process::id()));
+    let data = tokio::task::spawn_blocking(move || {
+        if use_rdb {
+            rdb::encode(&snapshot, true)
+        } else {
+            rewrite::rewrite(&snapshot)
+        }
+    })
+    .await
+    .map_err(|e| format!("Background AOF rewrite error: {}", e))?;
+    let result = async {
+        let mut file = File::create(&tmp).await?;
+        file.write_all(&data).await?;
+        file.sync_all().await
+    }
+    .await;
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(format!("Error writing the rewritten AOF: {}", e));
+    }
+    Ok((tmp, data.len() as u64))
+}
+
+/// 把写好的临时文件作为新的基础文件，之前的基础文件和已包含在其中的增量文件都变为历史文件并删除
+async fn install_base(state: &mut AofState, tmp: &Path, size: u64) -> Result<(), String> {
+    let dir = aof_dir().await;
+    let mut manifest = state.manifest.clone();
+    let name = manifest.new_base(&filename().await, use_rdb_preamble().await);
+    let path = dir.join(&name);
+    let result = async {
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::rename(tmp, &path).await
+    }
+    .await;
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(tmp).await;
+        return Err(format!("Error trying to rename the temporary AOF base file {}: {}", name, e));
+    }
+    manifest.mark_rewritten_incrs_as_history(state.file.is_some());
+    if let Err(e) = persist_manifest(&manifest).await {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(format!("Error trying to persist the AOF manifest: {}", e));
+    }
+    if let Err(e) = delete_history_files(&mut manifest).await {
+        println!("Can't remove the AOF history files: {}", e);
+    }
+    state.manifest = manifest;
+    let size = size + state.incr_size;
+    CURRENT_SIZE.store(size, Ordering::Relaxed);
+    BASE_SIZE.store(size, Ordering::Relaxed);
+    Ok(())
+}
+
+/// 开始后台重写。开启 AOF 时先切换到新的增量文件，之后的写命令都写入新文件，
+/// 快照与切换在同一次加锁中完成，因此重写期间的写入不会丢失也不会重复
+pub async fn rewrite() -> Result<(), String> {
+    if REWRITE_RUNNING
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return Err(REWRITE_IN_PROGRESS.to_string());
+    }
+    let snapshot = match start_rewrite().await {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            println!("{}", e);
+            LAST_REWRITE_OK.store(false, Ordering::Relaxed);
+            REWRITE_RUNNING.store(false, Ordering::Release);
+            return Err(
+                "ERR Can't execute an AOF background rewriting. Please check the server logs for more information."
+                    .to_string(),
+            );
+        }
+    };
+    println!("Background append only file rewriting started");
+    tokio::spawn(async move {
+        let result = match write_temp_base(snapshot, use_rdb_preamble().await).await {
+            Ok((tmp, size)) => install_base(&mut *AOF.lock().await, &tmp, size).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                println!("Background AOF rewrite finished successfully");
+                LAST_REWRITE_OK.store(true, Ordering::Relaxed);
+            }
+            Err(e) => {
+                println!("{}", e);
+                LAST_REWRITE_OK.store(false, Ordering::Relaxed);
+            }
+        }
+        REWRITE_RUNNING.store(false, Ordering::Release);
+    });
+    Ok(())
+}
+
+/// 刷盘并切换到新的增量文件，然后复制所有数据库
+async fn start_rewrite() -> Result<Snapshot, String> {
+    let mut state = AOF.lock().await;
+    if let Some(file) = state.file.as_mut() {
+        file.file.sync_data().await.map_err(|e| format!("Can't fsync the AOF file: {}", e))?;
+        file.unsynced = false;
+        let fsync = file.fsync;
+        let mut manifest = state.manifest.clone();
+        let name = manifest.new_incr(&filename().await);
+        let file = AofFile::open(&aof_dir().await.join(&name), fsync)
+            .await
+            .map_err(|e| format!("Can't open the append-only file {}: {}", name, e))?;
+        persist_manifest(&manifest).await.map_err(|e| format!("Can't persist the AOF manifest: {}", e))?;
+        state.manifest = manifest;
+        state.file = Some(file);
+        state.incr_size = 0;
+    }
+    Ok(rdb::snapshot().await)
+}
+
+/// everysec 策略下的后台任务，在 AOF 的锁之外执行 fsync，避免阻塞写命令
+pub async fn fsync_cron() {
+    let mut interval = tokio::time::interval(FSYNC_PERIOD);
+    loop {
+        interval.tick().await;
+        let file = {
+            let mut state = AOF.lock().await;
+            match state.file.as_mut() {
+                Some(aof) if aof.fsync == Fsync::Everysec && aof.unsynced => {
+                    aof.unsynced = false;
+                    aof.file.try_clone().await
+                }
+                _ => continue,
+            }
+        };
+        if let Err(e) = async { file?.sync_data().await }.await {
+            println!("Error syncing the AOF file: {}", e);
+        }
+    }
+}
+
+/// 后台任务，AOF 比上次重写后增长超过 auto-aof-rewrite-percentage 且大于
+/// auto-aof-rewrite-min-size 时自动重写，与 Redis 的 serverCron 一致
+pub async fn rewrite_cron() {
+    let mut interval = tokio::time::interval(REWRITE_CRON_PERIOD);
+    loop {
+        interval.tick().await;
+        if rewrite_in_progress() {
+            continue;
+        }
+        let percentage = config::get("auto-aof-rewrite-percentage").await.and_then(|value| value.parse::<u64>().ok());
+        let min_size = config::get("auto-aof-rewrite-min-size").await.and_then(|value| value.parse::<u64>().ok());
+        let (percentage, min_size) = match (percentage, min_size) {
+            (Some(percentage), Some(min_size)) if percentage > 0 => (percentage, min_size),
+            _ => continue,
+        };
+        let current = current_size();
+        if current <= min_size {
+            continue;
+        }
+        let base = base_size().max(1);
+        let growth = (current * 100 / base) as i64 - 100;
+        if growth >= percentage as i64 {
+            println!("Starting automatic rewriting of AOF on {}% growth", growth);
+            let _ = rewrite().await;
+        }
+    }
+}
+
+/// 读取一个 AOF 文件中的命令。文件末尾不完整时，allow_truncated 为 true 则截断到最后一条完整的命令，
+/// 否则返回错误
+pub async fn read_commands(path: &Path, allow_truncated: bool) -> Result<Vec<RespType>, String> {
+    let buf = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Fatal error: can't open the append log file {} for reading: {}", path.display(), e))?;
+    let mut commands = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let mut parser = RespParser::new(&buf[pos..]);
+        match parser.parse() {
+            Ok(command @ RespType::Array(Some(_))) => {
+                commands.push(command);
+                pos += parser.consumed();
+            }
+            Err(RespError::Incomplete) => {
+                if !allow_truncated {
+                    return Err(format!(
+                        "Unexpected end of file reading the append only file {}. You can: \
+                         1) Make a backup of your AOF file, then use ./redis-check-aof --fix <filename>. \
+                         2) Alternatively you can set the 'aof-load-truncated' configuration option to yes and restart the server.",
+                        path.display()
+                    ));
+                }
+                println!("!!! Warning: short read while loading the AOF file {}!!!", path.display());
+                println!("AOF {} loaded anyway because aof-load-truncated is enabled", path.display());
+                let file = OpenOptions::new().write(true).open(&path).await.map_err(|e| e.to_string())?;
+                file.set_len(pos as u64).await.map_err(|e| e.to_string())?;
+                break;
+            }
+            _ => {
+                return Err(format!(
+                    "Bad file format reading the append only file {}: make a backup of your AOF file, then use ./redis-check-aof --fix <filename>",
+                    path.display()
+                ))
+            }
+        }
+    }
+    Ok(commands)
+}
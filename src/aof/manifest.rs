@@ -0,0 +1,151 @@
+//! 多文件 AOF 的清单，格式与 Redis 7 一致，每行描述一个文件：
+//! `file appendonly.aof.1.base.rdb seq 1 type b`
+
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FileType {
+    /// 重写生成的基础文件
+    Base,
+    /// 重写完成后等待删除的旧文件
+    History,
+    /// 基础文件之后追加写命令的增量文件
+    Incr,
+}
+
+impl FileType {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "b" => Some(FileType::Base),
+            "h" => Some(FileType::History),
+            "i" => Some(FileType::Incr),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            FileType::Base => "b",
+            FileType::History => "h",
+            FileType::Incr => "i",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AofInfo {
+    pub name: String,
+    pub seq: u64,
+    pub file_type: FileType,
+}
+
+#[derive(Clone, Default)]
+pub struct Manifest {
+    pub base: Option<AofInfo>,
+    /// 按序号从小到大排列
+    pub incrs: Vec<AofInfo>,
+    pub history: Vec<AofInfo>,
+    /// 最近一个基础文件和增量文件的序号
+    pub base_seq: u64,
+    pub incr_seq: u64,
+}
+
+impl Manifest {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let invalid = || "Invalid AOF manifest file format".to_string();
+        let mut manifest = Manifest::default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let pairs = words.chunks_exact(2);
+            if !pairs.remainder().is_empty() {
+                return Err(invalid());
+            }
+            let (mut name, mut seq, mut file_type) = (None, None, None);
+            for pair in pairs {
+                match pair[0] {
+                    "file" => name = Some(pair[1].to_string()),
+                    "seq" => seq = pair[1].parse().ok(),
+                    "type" => file_type = FileType::parse(pair[1]),
+                    // 忽略未知的字段，与 Redis 一致
+                    _ => {}
+                }
+            }
+            let (name, seq, file_type) = match (name, seq, file_type) {
+                (Some(name), Some(seq), Some(file_type)) => (name, seq, file_type),
+                _ => return Err(invalid()),
+            };
+            let info = AofInfo { name, seq, file_type };
+            match file_type {
+                FileType::Base => {
+                    if manifest.base.is_some() {
+                        return Err("Found duplicate base file information".to_string());
+                    }
+                    manifest.base_seq = seq;
+                    manifest.base = Some(info);
+                }
+                FileType::History => manifest.history.push(info),
+                FileType::Incr => {
+                    if seq <= manifest.incr_seq {
+                        return Err("Found a non-monotonic sequence number".to_string());
+                    }
+                    manifest.incr_seq = seq;
+                    manifest.incrs.push(info);
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// 生成新的基础文件名，原来的基础文件标记为历史文件
+    pub fn new_base(&mut self, filename: &str, rdb: bool) -> String {
+        if let Some(mut base) = self.base.take() {
+            base.file_type = FileType::History;
+            self.history.push(base);
+        }
+        self.base_seq += 1;
+        let extension = if rdb { "rdb" } else { "aof" };
+        let name = format!("{}.{}.base.{}", filename, self.base_seq, extension);
+        self.base = Some(AofInfo {
+            name: name.clone(),
+            seq: self.base_seq,
+            file_type: FileType::Base,
+        });
+        name
+    }
+
+    pub fn new_incr(&mut self, filename: &str) -> String {
+        self.incr_seq += 1;
+        let name = format!("{}.{}.incr.aof", filename, self.incr_seq);
+        self.incrs.push(AofInfo {
+            name: name.clone(),
+            seq: self.incr_seq,
+            file_type: FileType::Incr,
+        });
+        name
+    }
+
+    /// 重写完成后，除了重写开始时新建的增量文件，其余增量文件的内容都已包含在新的基础文件中。
+    /// aof_on 为 false 时没有新建增量文件，全部标记为历史文件
+    pub fn mark_rewritten_incrs_as_history(&mut self, aof_on: bool) {
+        let keep = if aof_on { 1 } else { 0 };
+        let rewritten = self.incrs.len().saturating_sub(keep);
+        for mut incr in self.incrs.drain(..rewritten) {
+            incr.file_type = FileType::History;
+            self.history.push(incr);
+        }
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for info in self.base.iter().chain(&self.history).chain(&self.incrs) {
+            let file_type = info.file_type.as_str();
+            writeln!(f, "file {} seq {} type {}", info.name, info.seq, file_type)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+//! 把命令改写为重放时结果确定的形式：相对过期时间改为绝对时间，
+//! 随机或依赖当前状态生成的结果（SPOP、XADD 的自动 ID、浮点数自增、XREADGROUP 的投递、XCLAIM 的空闲时间判断）改为直接写入结果
+
+use bytes::Bytes;
+
+use crate::{
+    commands::upper,
+    resp::RespType,
+    storage::{self, now_ms, ConsumerGroup, PendingEntry, StreamId},
+};
+
+fn bulk(value: impl ToString) -> Bytes {
+    Bytes::from(value.to_string())
+}
+
+/// 相对时间参数转为毫秒时间戳，unit 为 1000 时参数单位是秒
+fn absolute_ms(arg: &[u8], unit: i64, relative: bool) -> Option<i64> {
+    let value: i64 = std::str::from_utf8(arg).ok()?.parse().ok()?;
+    let ms = value.checked_mul(unit)?;
+    if relative {
+        ms.checked_add(now_ms() as i64)
+    } else {
+        Some(ms)
+    }
+}
+
+/// 把 EX/PX/EXAT 选项改写为 PXAT，args 从选项开始
+fn rewrite_expire_options(args: &[Bytes]) -> Vec<Bytes> {
+    let mut rewritten = Vec::with_capacity(args.len());
+    let mut i = 0;
+    while i < args.len() {
+        let option = upper(&args[i]);
+        let (unit, relative) = match option.as_str() {
+            "EX" => (1000, true),
+            "PX" => (1, true),
+            "EXAT" => (1000, false),
+            _ => {
+                rewritten.push(args[i].clone());
+                i += 1;
+                continue;
+            }
+        };
+        match args.get(i + 1).and_then(|arg| absolute_ms(arg, unit, relative)) {
+            Some(ms) => rewritten.extend([bulk("PXAT"), bulk(ms)]),
+            None => rewritten.extend_from_slice(&args[i..(i + 2).min(args.len())]),
+        }
+        i += 2;
+    }
+    rewritten
+}
+
+/// 返回需要写入 AOF 的命令（第一个元素是命令名），返回空时不写入
+pub(super) async fn rewrite(command: &str, args: &[Bytes], reply: &RespType) -> Vec<Vec<Bytes>> {
+    let original = || {
+        let mut command = vec![bulk(command)];
+        command.extend_from_slice(args);
+        vec![command]
+    };
+    match command {
+        "SET" if args.len() > 2 => {
+            let mut rewritten = vec![bulk("SET"), args[0].clone(), args[1].clone()];
+            rewritten.extend(rewrite_expire_options(&args[2..]));
+            vec![rewritten]
+        }
+        "SETEX" | "PSETEX" if args.len() == 3 => {
+            let unit = if command == "SETEX" { 1000 } else { 1 };
+            match absolute_ms(&args[1], unit, true) {
+                Some(ms) => vec![vec![bulk("SET"), args[0].clone(), args[2].clone(), bulk("PXAT"), bulk(ms)]],
+                None => original(),
+            }
+        }
+        "EXPIRE" | "PEXPIRE" | "EXPIREAT" if args.len() >= 2 => {
+            if matches!(reply, RespType::Integer(0)) {
+                return vec![];
+            }
+            let (unit, relative) = match command {
+                "EXPIRE" => (1000, true),
+                "PEXPIRE" => (1, true),
+                _ => (1000, false),
+            };
+            match absolute_ms(&args[1], unit, relative) {
+                Some(ms) => {
+                    let mut rewritten = vec![bulk("PEXPIREAT"), args[0].clone(), bulk(ms)];
+                    rewritten.extend_from_slice(&args[2..]);
+                    vec![rewritten]
+                }
+                None => original(),
+            }
+        }
+        "PEXPIREAT" | "PERSIST" if matches!(reply, RespType::Integer(0)) => vec![],
+        // 不带选项的 GETEX 只是读取
+        "GETEX" => {
+            if args.len() < 2 || matches!(reply, RespType::BulkString(None)) {
+                return vec![];
+            }
+            if upper(&args[1]) == "PERSIST" {
+                return vec![vec![bulk("PERSIST"), args[0].clone()]];
+            }
+            let options = rewrite_expire_options(&args[1..]);
+            let mut rewritten = vec![bulk("PEXPIREAT"), args[0].clone()];
+            rewritten.extend(options.into_iter().skip(1));
+            vec![rewritten]
+        }
+        "INCRBYFLOAT" => match reply {
+            RespType::BulkString(Some(value)) => vec![vec![bulk("SET"), args[0].clone(), value.clone(), bulk("KEEPTTL")]],
+            _ => vec![],
+        },
+        "HINCRBYFLOAT" => match reply {
+            RespType::BulkString(Some(value)) => vec![vec![bulk("HSET"), args[0].clone(), args[1].clone(), value.clone()]],
+            _ => vec![],
+        },
+        "SPOP" => {
+            let members: Vec<Bytes> = match reply {
+                RespType::BulkString(Some(member)) => vec![member.clone()],
+                RespType::Array(Some(members)) => members
+                    .iter()
+                    .filter_map(|member| match member {
+                        RespType::BulkString(Some(member)) => Some(member.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            };
+            if members.is_empty() {
+                return vec![];
+            }
+            let mut rewritten = vec![bulk("SREM"), args[0].clone()];
+            rewritten.extend(members);
+            vec![rewritten]
+        }
+        "XADD" => match reply {
+            RespType::BulkString(Some(id)) => {
+                let mut rewritten = vec![bulk("XADD")];
+                rewritten.extend_from_slice(args);
+                if let Some(i) = xadd_id_index(args) {
+                    rewritten[i + 1] = id.clone();
+                }
+                vec![rewritten]
+            }
+            _ => vec![],
+        },
+        "XREADGROUP" => rewrite_xreadgroup(args, reply).await,
+        "XCLAIM" | "XAUTOCLAIM" => rewrite_claim(command, args, reply).await,
+        _ => original(),
+    }
+}
+
+fn reply_ids(reply: Option<&RespType>) -> Vec<StreamId> {
+    let elements = match reply {
+        Some(RespType::Array(Some(elements))) => elements,
+        _ => return vec![],
+    };
+    elements
+        .iter()
+        .filter_map(|element| match element {
+            RespType::BulkString(Some(id)) => StreamId::parse(id, 0),
+            // 不带 JUSTID 时每个元素是 [id, fields]
+            RespType::Array(Some(entry)) => match entry.first() {
+                Some(RespType::BulkString(Some(id))) => StreamId::parse(id, 0),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// 使用 FORCE 的 XCLAIM，重放时直接设置条目的所有者、投递时间和次数
+fn force_claim(key: &Bytes, group_name: &Bytes, consumer: &Bytes, id: StreamId, pending: &PendingEntry) -> Vec<Bytes> {
+    vec![
+        bulk("XCLAIM"),
+        key.clone(),
+        group_name.clone(),
+        consumer.clone(),
+        bulk(0),
+        id.to_bytes(),
+        bulk("TIME"),
+        bulk(pending.delivery_time),
+        bulk("RETRYCOUNT"),
+        bulk(pending.delivery_count),
+        bulk("FORCE"),
+        bulk("JUSTID"),
+    ]
+}
+
+/// 把组的 last_id 和已读取条目数写为 XGROUP SETID
+fn group_setid(key: &Bytes, group_name: &Bytes, group: &ConsumerGroup) -> Vec<Bytes> {
+    let entries_read = group.entries_read.map_or(-1, |entries_read| entries_read as i64);
+    vec![
+        bulk("XGROUP"),
+        bulk("SETID"),
+        key.clone(),
+        group_name.clone(),
+        group.last_id.to_bytes(),
+        bulk("ENTRIESREAD"),
+        bulk(entries_read),
+    ]
+}
+
+/// 与 Redis 一样，XREADGROUP 改写为对每个投递的条目使用 FORCE 的 XCLAIM 并带上 LASTID，
+/// 最后用 XGROUP SETID 同步组的 last_id 和已读取条目数。NOACK 不新增待确认条目，只写入 XGROUP SETID。
+/// 读取历史不会修改数据，不需要写入
+async fn rewrite_xreadgroup(args: &[Bytes], reply: &RespType) -> Vec<Vec<Bytes>> {
+    let mut names = None;
+    let mut noack = false;
+    let mut i = 0;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "GROUP" if i + 2 < args.len() => {
+                names = Some((&args[i + 1], &args[i + 2]));
+                i += 3;
+            }
+            "COUNT" | "BLOCK" => i += 2,
+            "NOACK" => {
+                noack = true;
+                i += 1;
+            }
+            _ => break,
+        }
+    }
+    let (group_name, consumer) = match names {
+        Some(names) => names,
+        None => return vec![],
+    };
+    let streams = args.get(i + 1..).unwrap_or_default();
+    let (keys, ids) = streams.split_at(streams.len() / 2);
+    // 只有使用 `>` 读取新条目的流会修改消费者组
+    let read_new: Vec<&Bytes> = keys
+        .iter()
+        .zip(ids)
+        .filter(|(_, id)| &id[..] == b">")
+        .map(|(key, _)| key)
+        .collect();
+    let streams = match reply {
+        RespType::Array(Some(streams)) => streams,
+        _ => return vec![],
+    };
+
+    let db = storage::read().await;
+    let mut commands = Vec::new();
+    for stream in streams {
+        let (key, ids) = match stream {
+            RespType::Array(Some(stream)) => match stream.first() {
+                Some(RespType::BulkString(Some(key))) if read_new.contains(&key) => {
+                    (key, reply_ids(stream.get(1)))
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let group = match db.stream(key) {
+            Ok(Some(stream)) => stream.groups.get(group_name),
+            _ => None,
+        };
+        let group = match group {
+            Some(group) => group,
+            None => continue,
+        };
+        if ids.is_empty() {
+            continue;
+        }
+        commands.push(vec![
+            bulk("XGROUP"),
+            bulk("CREATECONSUMER"),
+            key.clone(),
+            group_name.clone(),
+            consumer.clone(),
+        ]);
+        if !noack {
+            for id in ids {
+                if let Some(pending) = group.pel.get(&id) {
+                    let mut claim = force_claim(key, group_name, consumer, id, pending);
+                    claim.extend([bulk("LASTID"), group.last_id.to_bytes()]);
+                    commands.push(claim);
+                }
+            }
+        }
+        commands.push(group_setid(key, group_name, group));
+    }
+    commands
+}
+
+/// XCLAIM 和 XAUTOCLAIM 是否认领取决于执行时的空闲时间，与 Redis 一样改写为对每个认领的条目
+/// 使用 FORCE 的 XCLAIM，并带上认领后的投递时间和次数。从待确认列表中移除的已删除条目改写为 XACK，
+/// LASTID 改写为 XGROUP SETID
+async fn rewrite_claim(command: &str, args: &[Bytes], reply: &RespType) -> Vec<Vec<Bytes>> {
+    let (key, group_name, consumer) = (&args[0], &args[1], &args[2]);
+    let elements = match reply {
+        RespType::Array(Some(elements)) => elements,
+        _ => return vec![],
+    };
+    let (claimed, removed) = if command == "XCLAIM" {
+        let requested: Vec<StreamId> = args[4..].iter().map_while(|arg| StreamId::parse(arg, 0)).collect();
+        (reply_ids(Some(reply)), requested)
+    } else {
+        (reply_ids(elements.get(1)), reply_ids(elements.get(2)))
+    };
+
+    let db = storage::read().await;
+    let group = match db.stream(key) {
+        Ok(Some(stream)) => stream.groups.get(group_name),
+        _ => None,
+    };
+    let group = match group {
+        Some(group) => group,
+        None => return vec![],
+    };
+    // 即使没有认领任何条目，命令也会创建消费者
+    let mut commands = vec![vec![
+        bulk("XGROUP"),
+        bulk("CREATECONSUMER"),
+        key.clone(),
+        group_name.clone(),
+        consumer.clone(),
+    ]];
+    for id in claimed {
+        if let Some(pending) = group.pel.get(&id) {
+            commands.push(force_claim(key, group_name, consumer, id, pending));
+        }
+    }
+    // XCLAIM 的回复中没有被移除的条目，对不在待确认列表中的 ID 都执行 XACK，重放时对其余 ID 没有影响
+    let removed: Vec<Bytes> = removed
+        .into_iter()
+        .filter(|id| !group.pel.contains_key(id))
+        .map(StreamId::to_bytes)
+        .collect();
+    if !removed.is_empty() {
+        let mut ack = vec![bulk("XACK"), key.clone(), group_name.clone()];
+        ack.extend(removed);
+        commands.push(ack);
+    }
+    if command == "XCLAIM" && args[4..].iter().any(|arg| upper(arg) == "LASTID") {
+        commands.push(group_setid(key, group_name, group));
+    }
+    commands
+}
+
+/// XADD 中 ID 参数的位置，跳过 NOMKSTREAM 和裁剪选项
+fn xadd_id_index(args: &[Bytes]) -> Option<usize> {
+    let mut i = 1;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "NOMKSTREAM" => i += 1,
+            "MAXLEN" | "MINID" => {
+                i += 1;
+                if matches!(args.get(i).map(|arg| &arg[..]), Some(b"=" | b"~")) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            "LIMIT" => i += 2,
+            _ => return Some(i),
+        }
+    }
+    None
+}
@@ -0,0 +1,126 @@
+//! 把数据库快照转换为重建这些数据所需的最少命令，用于不使用 RDB 前导的基础文件
+
+use bytes::Bytes;
+
+use crate::{
+    commands::format_float,
+    rdb::Snapshot,
+    storage::{unix_ms, Stream, StreamId, Value},
+};
+
+use super::serialize;
+
+/// 集合类型每条命令最多包含的元素数，与 Redis 的 AOF_REWRITE_ITEMS_PER_CMD 一致
+const ITEMS_PER_COMMAND: usize = 64;
+
+fn bulk(value: impl ToString) -> Bytes {
+    Bytes::from(value.to_string())
+}
+
+/// 把元素分批写入 `command key ...`，每个元素可以由多个参数组成
+fn batched(buf: &mut Vec<u8>, command: &str, key: &Bytes, items: Vec<Vec<Bytes>>) {
+    for chunk in items.chunks(ITEMS_PER_COMMAND) {
+        let mut args = vec![bulk(command), key.clone()];
+        args.extend(chunk.iter().flatten().cloned());
+        buf.extend(serialize(args));
+    }
+}
+
+pub(super) fn rewrite(snapshot: &Snapshot) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (index, entries) in snapshot {
+        if entries.is_empty() {
+            continue;
+        }
+        buf.extend(serialize(vec![bulk("SELECT"), bulk(index)]));
+        for (key, item) in entries {
+            match &item.value {
+                Value::String(value) => buf.extend(serialize(vec![bulk("SET"), key.clone(), value.to_bytes()])),
+                Value::List(list) => {
+                    let items = list.iter().map(|element| vec![element.clone()]).collect();
+                    batched(&mut buf, "RPUSH", key, items)
+                }
+                Value::Set(set) => {
+                    let items = set.members().into_iter().map(|member| vec![member]).collect();
+                    batched(&mut buf, "SADD", key, items)
+                }
+                Value::ZSet(zset) => {
+                    let items = zset
+                        .iter()
+                        .map(|(member, score)| vec![bulk(format_float(score)), member.clone()])
+                        .collect();
+                    batched(&mut buf, "ZADD", key, items)
+                }
+                Value::Hash(hash) => {
+                    let items = hash.iter().map(|(field, value)| vec![field.clone(), value.clone()]).collect();
+                    batched(&mut buf, "HMSET", key, items)
+                }
+                Value::Stream(stream) => rewrite_stream(&mut buf, key, stream),
+            }
+            if let Some(expires) = item.expires {
+                buf.extend(serialize(vec![bulk("PEXPIREAT"), key.clone(), bulk(unix_ms(expires))]));
+            }
+        }
+    }
+    buf
+}
+
+fn id(id: StreamId) -> Bytes {
+    id.to_bytes()
+}
+
+/// 流的条目用带 ID 的 XADD 写入，再用 XSETID 恢复元数据，最后重建消费者组
+fn rewrite_stream(buf: &mut Vec<u8>, key: &Bytes, stream: &Stream) {
+    if stream.len() == 0 {
+        // 空的流无法直接创建，添加一个 0-1 的条目后立即裁剪掉，真实的最后 ID 由下面的 XSETID 恢复
+        let args = vec![bulk("XADD"), key.clone(), bulk("MAXLEN"), bulk(0), bulk("0-1"), bulk("x"), bulk("y")];
+        buf.extend(serialize(args));
+    }
+    for (entry_id, fields) in stream.iter() {
+        let mut args = vec![bulk("XADD"), key.clone(), id(*entry_id)];
+        args.extend(fields.iter().cloned());
+        buf.extend(serialize(args));
+    }
+    buf.extend(serialize(vec![
+        bulk("XSETID"),
+        key.clone(),
+        id(stream.last_id),
+        bulk("ENTRIESADDED"),
+        bulk(stream.entries_added),
+        bulk("MAXDELETEDID"),
+        id(stream.max_deleted_id),
+    ]));
+    for (name, group) in &stream.groups {
+        let entries_read = group.entries_read.map_or(-1, |entries_read| entries_read as i64);
+        buf.extend(serialize(vec![
+            bulk("XGROUP"),
+            bulk("CREATE"),
+            key.clone(),
+            name.clone(),
+            id(group.last_id),
+            bulk("ENTRIESREAD"),
+            bulk(entries_read),
+        ]));
+        for consumer in group.consumers.keys() {
+            let args = vec![bulk("XGROUP"), bulk("CREATECONSUMER"), key.clone(), name.clone(), consumer.clone()];
+            buf.extend(serialize(args));
+        }
+        // 用 XCLAIM 重建待确认列表，保留投递时间和次数
+        for (entry_id, pending) in &group.pel {
+            buf.extend(serialize(vec![
+                bulk("XCLAIM"),
+                key.clone(),
+                name.clone(),
+                pending.consumer.clone(),
+                bulk(0),
+                id(*entry_id),
+                bulk("TIME"),
+                bulk(pending.delivery_time),
+                bulk("RETRYCOUNT"),
+                bulk(pending.delivery_count),
+                bulk("JUSTID"),
+                bulk("FORCE"),
+            ]));
+        }
+    }
+}
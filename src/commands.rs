@@ -1,13 +1,260 @@
+mod bitops;
 mod config;
 mod echo;
+mod expire;
+mod geo;
 mod get;
+mod hash;
+mod hyperloglog;
+mod list;
+mod object;
 mod save;
+mod scan;
 mod set;
+mod sets;
+mod stream;
+mod stream_group;
+mod string;
+mod zset;
 mod info;
+mod keyspace;
 
+pub use bitops::*;
 pub use config::*;
 pub use echo::*;
+pub use expire::*;
+pub use geo::*;
 pub use get::*;
+pub use hash::*;
+pub use hyperloglog::*;
+pub use list::*;
+pub use object::*;
 pub use save::*;
+pub use scan::*;
 pub use set::*;
+pub use sets::*;
+pub use stream::*;
+pub use stream_group::*;
+pub use string::*;
+pub use zset::*;
 pub use info::*;
+pub use keyspace::*;
+
+use bytes::Bytes;
+use num_bigint::{BigInt, BigUint, Sign};
+
+/// 会修改数据的命令，成功执行后计入未保存的修改数
+pub fn is_write(command: &str) -> bool {
+    matches!(
+        command,
+        "SET" | "APPEND" | "SETRANGE" | "GETSET" | "GETDEL" | "GETEX" | "SETNX" | "SETEX" | "PSETEX"
+            | "MSET" | "MSETNX" | "INCR" | "DECR" | "INCRBY" | "DECRBY" | "INCRBYFLOAT"
+            | "SETBIT" | "BITOP" | "BITFIELD" | "PFADD" | "PFMERGE" | "GEOADD" | "GEOSEARCHSTORE"
+            | "DEL" | "UNLINK" | "RENAME" | "RENAMENX" | "COPY" | "MOVE" | "SWAPDB" | "FLUSHDB" | "FLUSHALL"
+            | "EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT" | "PERSIST"
+            | "LPUSH" | "RPUSH" | "LPUSHX" | "RPUSHX" | "LPOP" | "RPOP" | "LSET" | "LREM" | "LTRIM"
+            | "LINSERT" | "LMOVE" | "RPOPLPUSH"
+            | "HSET" | "HMSET" | "HSETNX" | "HDEL" | "HINCRBY" | "HINCRBYFLOAT"
+            | "SADD" | "SREM" | "SMOVE" | "SPOP" | "SINTERSTORE" | "SUNIONSTORE" | "SDIFFSTORE"
+            | "ZADD" | "ZINCRBY" | "ZREM" | "ZRANGESTORE" | "ZREMRANGEBYRANK" | "ZREMRANGEBYSCORE"
+            | "ZREMRANGEBYLEX" | "ZPOPMIN" | "ZPOPMAX" | "ZUNIONSTORE" | "ZINTERSTORE" | "ZDIFFSTORE"
+            | "XADD" | "XDEL" | "XTRIM" | "XSETID" | "XGROUP" | "XREADGROUP" | "XACK" | "XCLAIM" | "XAUTOCLAIM"
+    )
+}
+
+/// 参数转为大写字符串，用于匹配子命令和选项
+pub fn upper(arg: &[u8]) -> String {
+    String::from_utf8_lossy(arg).to_uppercase()
+}
+
+/// 参数个数错误
+pub fn wrong_args(command: &str) -> String {
+    format!("ERR wrong number of arguments for '{}' command", command)
+}
+
+/// 将参数解析为 i64
+pub fn parse_int(arg: &[u8]) -> Result<i64, String> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| "ERR value is not an integer or out of range".to_string())
+}
+
+/// 解析 SRANDMEMBER 和 HRANDFIELD 的 count，与 Redis 一样限制在 ±(i64::MAX / 2) 之内，
+/// 避免取绝对值和计算返回数量时溢出
+pub fn parse_random_count(arg: &[u8]) -> Result<i64, String> {
+    let count = parse_int(arg)?;
+    if !(-(i64::MAX / 2)..=i64::MAX / 2).contains(&count) {
+        return Err("ERR value is out of range".to_string());
+    }
+    Ok(count)
+}
+
+/// 将参数解析为有限的 f64
+pub fn parse_float(arg: &[u8]) -> Result<f64, String> {
+    std::str::from_utf8(arg)
+        .ok()
+        .filter(|s| !s.is_empty() && !s.starts_with(char::is_whitespace) && !s.ends_with(char::is_whitespace))
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|f| !f.is_nan())
+        .ok_or_else(|| "ERR value is not a valid float".to_string())
+}
+
+/// 按 Redis 的方式格式化浮点数：使用能精确还原的最短的有效数字，
+/// 与 %.17g 一样在指数小于 -4 或不小于 17 时使用科学计数法，并去掉多余的 0
+pub fn format_float(value: f64) -> String {
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    // {:e} 给出最短的有效数字，例如 3.0000000000000004e-1
+    let formatted = format!("{:e}", value.abs());
+    let (mantissa, exp) = formatted.split_once('e').unwrap();
+    let fraction_len = mantissa.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+    let digits = mantissa.replace('.', "");
+    format_decimal(value < 0.0, &digits, exp.parse::<i64>().unwrap() - fraction_len as i64)
+}
+
+/// 有效数字的个数，与 Redis 格式化 long double 的 %.17Lg 一致
+const FLOAT_PRECISION: usize = 17;
+/// 十进制指数的绝对值超过这个值时不再精确计算，按 f64 的结果输出
+const MAX_DECIMAL_EXP: i64 = 1000;
+
+/// 按 %g 的规则输出 ±digits × 10^exp，digits 不能有前导 0
+fn format_decimal(negative: bool, digits: &str, exp: i64) -> String {
+    let trimmed = digits.trim_end_matches('0');
+    let exp = exp + (digits.len() - trimmed.len()) as i64;
+    let digits = trimmed;
+    let sign = if negative { "-" } else { "" };
+    // 第一位有效数字的指数
+    let leading_exp = exp + digits.len() as i64 - 1;
+    if leading_exp < -4 || leading_exp >= FLOAT_PRECISION as i64 {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let exp_sign = if leading_exp < 0 { '-' } else { '+' };
+        return format!("{}{}{}{}e{}{:02}", sign, first, point, rest, exp_sign, leading_exp.abs());
+    }
+    if exp >= 0 {
+        return format!("{}{}{}", sign, digits, "0".repeat(exp as usize));
+    }
+    let point = digits.len() as i64 + exp;
+    if point > 0 {
+        let (int, fraction) = digits.split_at(point as usize);
+        format!("{}{}.{}", sign, int, fraction)
+    } else {
+        format!("{}0.{}{}", sign, "0".repeat(-point as usize), digits)
+    }
+}
+
+/// 把 parse_float 接受的十进制字符串精确地转为 mantissa × 10^exp，inf 等返回 None
+fn parse_decimal(value: &str) -> Option<(BigInt, i64)> {
+    let (mantissa, exp) = match value.find(['e', 'E']) {
+        Some(pos) => (&value[..pos], value[pos + 1..].parse::<i64>().ok()?),
+        None => (value, 0),
+    };
+    let (int, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let exp = exp.checked_sub(fraction.len() as i64)?;
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) || exp.abs() > MAX_DECIMAL_EXP {
+        return None;
+    }
+    let digits = format!("{}{}", int, fraction);
+    let digits = match digits.as_str() {
+        "" | "+" | "-" => return None,
+        _ => digits.parse::<BigInt>().ok()?,
+    };
+    Some((digits, exp))
+}
+
+/// 计算 INCRBYFLOAT 和 HINCRBYFLOAT 的结果，两个参数都需要先用 parse_float 检查过。
+/// Redis 使用 long double 计算，0.1 连续加 0.1 得到 0.3 而不是 0.30000000000000004，
+/// 这里直接按十进制精确相加，再保留 17 位有效数字
+pub fn incr_float(current: &[u8], increment: &[u8]) -> Result<Bytes, String> {
+    let sum = parse_float(current)? + parse_float(increment)?;
+    if !sum.is_finite() {
+        return Err("ERR increment would produce NaN or Infinity".to_string());
+    }
+    let exact = match (
+        parse_decimal(std::str::from_utf8(current).unwrap()),
+        parse_decimal(std::str::from_utf8(increment).unwrap()),
+    ) {
+        (Some((a, a_exp)), Some((b, b_exp))) => {
+            let exp = a_exp.min(b_exp);
+            let scale = |exp_diff: i64| BigInt::from(10).pow(exp_diff as u32);
+            Some((a * scale(a_exp - exp) + b * scale(b_exp - exp), exp))
+        }
+        _ => None,
+    };
+    let (sum, exp) = match exact {
+        Some((sum, exp)) if sum.sign() != Sign::NoSign => (sum, exp),
+        Some(_) => return Ok(Bytes::from_static(b"0")),
+        None => return Ok(Bytes::from(format_float(sum))),
+    };
+    let digits = sum.magnitude().to_string();
+    if digits.len() <= FLOAT_PRECISION {
+        return Ok(Bytes::from(format_decimal(sum.sign() == Sign::Minus, &digits, exp)));
+    }
+    // 四舍五入到 17 位有效数字，进位后可能多出一位
+    let dropped = digits.len() - FLOAT_PRECISION;
+    let mut rounded = digits[..FLOAT_PRECISION].parse::<BigUint>().unwrap();
+    if digits.as_bytes()[FLOAT_PRECISION] >= b'5' {
+        rounded += 1u32;
+    }
+    let rounded = rounded.to_string();
+    let exp = exp + dropped as i64 + (rounded.len() - FLOAT_PRECISION) as i64;
+    Ok(Bytes::from(format_decimal(sum.sign() == Sign::Minus, &rounded[..FLOAT_PRECISION], exp)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_float, incr_float, parse_random_count};
+
+    fn incr(current: &str, increment: &str) -> String {
+        String::from_utf8(incr_float(current.as_bytes(), increment.as_bytes()).unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn float_format() {
+        assert_eq!(format_float(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format_float(1.5), "1.5");
+        assert_eq!(format_float(-2.0), "-2");
+        assert_eq!(format_float(100.0), "100");
+        assert_eq!(format_float(0.0001), "0.0001");
+        assert_eq!(format_float(0.00001), "1e-05");
+        assert_eq!(format_float(1e16), "10000000000000000");
+        assert_eq!(format_float(1e17), "1e+17");
+        assert_eq!(format_float(-1.25e300), "-1.25e+300");
+        assert_eq!(format_float(f64::INFINITY), "inf");
+        assert_eq!(format_float(f64::NEG_INFINITY), "-inf");
+        assert_eq!(format_float(-0.0), "0");
+    }
+
+    #[test]
+    fn float_increment() {
+        assert_eq!(incr(&incr("0.1", "0.1"), "0.1"), "0.3");
+        assert_eq!(incr("10.50", "0.1"), "10.6");
+        assert_eq!(incr("5.0e3", "2.0e2"), "5200");
+        assert_eq!(incr("0", "3.0"), "3");
+        assert_eq!(incr("1", "-1"), "0");
+        assert_eq!(incr("-.5", "+.25"), "-0.25");
+        assert_eq!(incr("1", "1e-20"), "1");
+        assert_eq!(incr("0", "1e-20"), "1e-20");
+        assert_eq!(incr("0.12345678901234567", "0.000000000000000005"), "0.12345678901234568");
+        assert_eq!(incr("99999999999999999", "0.5"), "1e+17");
+        assert_eq!(incr("1e-5000", "1"), "1");
+        assert!(incr_float(b"1.7e308", b"1.7e308").is_err());
+        assert!(incr_float(b"inf", b"1").is_err());
+    }
+
+    #[test]
+    fn random_count_range() {
+        let max = (i64::MAX / 2).to_string();
+        assert_eq!(parse_random_count(max.as_bytes()), Ok(i64::MAX / 2));
+        assert_eq!(parse_random_count(format!("-{}", max).as_bytes()), Ok(-(i64::MAX / 2)));
+        let over = (i64::MAX / 2 + 1).to_string();
+        assert!(parse_random_count(over.as_bytes()).is_err());
+        assert!(parse_random_count(format!("-{}", over).as_bytes()).is_err());
+        assert!(parse_random_count(i64::MIN.to_string().as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use bytes::Bytes;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{
+        self, bit_count, bit_op, bit_pos, byte_range, get_bit, parse_strict_int, set_bit, BitOp, BitfieldType,
+        Item, Overflow, RangeUnit, Value, STRING_MAX_LEN,
+    },
+};
+
+use super::{parse_int, upper, wrong_args};
+
+/// 解析位偏移量，bits 不为 0 时支持 `#N` 表示第 N 个 bits 位宽的整数
+fn parse_bit_offset(arg: &[u8], bits: u32) -> Result<u64, String> {
+    let error = || "ERR bit offset is not an integer or out of range".to_string();
+    let (arg, multiple) = match arg.strip_prefix(b"#") {
+        Some(arg) if bits > 0 => (arg, bits as i64),
+        _ => (arg, 1),
+    };
+    let offset = parse_strict_int(arg)
+        .and_then(|offset| offset.checked_mul(multiple))
+        .ok_or_else(error)?;
+    if offset < 0 || (offset >> 3) as usize >= STRING_MAX_LEN {
+        return Err(error());
+    }
+    Ok(offset as u64)
+}
+
+/// 解析 BITCOUNT/BITPOS 的 BYTE|BIT 参数
+fn parse_range_unit(arg: Option<&Bytes>) -> Result<RangeUnit, String> {
+    match arg.map(|arg| upper(arg)).as_deref() {
+        None | Some("BYTE") => Ok(RangeUnit::Byte),
+        Some("BIT") => Ok(RangeUnit::Bit),
+        Some(_) => Err("ERR syntax error".to_string()),
+    }
+}
+
+pub async fn setbit(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("setbit"));
+    }
+    let offset = parse_bit_offset(&args[1], 0)?;
+    let on = match parse_int(&args[2]) {
+        Ok(on @ (0 | 1)) => on as u8,
+        _ => return Err("ERR bit is not an integer or out of range".to_string()),
+    };
+    let mut db = storage::write().await;
+    let old = db.modify_string(&args[0], (offset >> 3) as usize + 1, |value| set_bit(value, offset, on))?;
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(old as i64))
+}
+
+pub async fn getbit(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("getbit"));
+    }
+    let offset = parse_bit_offset(&args[1], 0)?;
+    let db = storage::read().await;
+    let value = db.string(&args[0])?.unwrap_or_default();
+    Ok(RespType::Integer(get_bit(&value, offset) as i64))
+}
+
+pub async fn bitcount(args: Vec<Bytes>) -> Result<RespType, String> {
+    let (start, end, unit) = match args.len() {
+        1 => (0, -1, RangeUnit::Byte),
+        3 | 4 => (parse_int(&args[1])?, parse_int(&args[2])?, parse_range_unit(args.get(3))?),
+        0 => return Err(wrong_args("bitcount")),
+        _ => return Err("ERR syntax error".to_string()),
+    };
+    let db = storage::read().await;
+    let value = db.string(&args[0])?.unwrap_or_default();
+    let (start, end, first_mask, last_mask) = byte_range(value.len(), start, end, unit);
+    Ok(RespType::Integer(bit_count(&value, start, end, first_mask, last_mask) as i64))
+}
+
+pub async fn bitpos(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("bitpos"));
+    }
+    let bit = match parse_int(&args[1])? {
+        bit @ (0 | 1) => bit as u8,
+        _ => return Err("ERR The bit argument must be 1 or 0.".to_string()),
+    };
+    let db = storage::read().await;
+    let value = match db.string(&args[0])? {
+        Some(value) => value,
+        // 不存在的键看作全 0 的字符串
+        None => return Ok(RespType::Integer(if bit == 1 { -1 } else { 0 })),
+    };
+    let (start, end, unit) = match args.len() {
+        2 => (0, -1, RangeUnit::Byte),
+        3 => (parse_int(&args[2])?, -1, RangeUnit::Byte),
+        4 | 5 => (parse_int(&args[2])?, parse_int(&args[3])?, parse_range_unit(args.get(4))?),
+        _ => return Err("ERR syntax error".to_string()),
+    };
+    let range = byte_range(value.len(), start, end, unit);
+    Ok(RespType::Integer(bit_pos(&value, bit, range, args.len() > 3)))
+}
+
+pub async fn bitop(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 3 {
+        return Err(wrong_args("bitop"));
+    }
+    let op = match upper(&args[0]).as_str() {
+        "AND" => BitOp::And,
+        "OR" => BitOp::Or,
+        "XOR" => BitOp::Xor,
+        "NOT" => BitOp::Not,
+        _ => return Err("ERR syntax error".to_string()),
+    };
+    if op == BitOp::Not && args.len() != 3 {
+        return Err("ERR BITOP NOT must be called with a single source key.".to_string());
+    }
+    let mut db = storage::write().await;
+    let sources = args[2..]
+        .iter()
+        .map(|key| Ok(db.string(key)?.unwrap_or_default()))
+        .collect::<Result<Vec<_>, String>>()?;
+    let sources: Vec<&[u8]> = sources.iter().map(|source| &source[..]).collect();
+    let result = bit_op(op, &sources);
+    let len = result.len();
+    // 结果为空时删除目标键
+    if len == 0 {
+        db.delete(&args[1]);
+    } else {
+        db.insert(args[1].clone(), Item::new(Value::String(Bytes::from(result).into())));
+    }
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(len as i64))
+}
+
+/// BITFIELD 的子命令
+enum BitfieldOp {
+    Get,
+    Set(i64),
+    IncrBy(i64),
+}
+
+struct BitfieldCommand {
+    op: BitfieldOp,
+    ty: BitfieldType,
+    offset: u64,
+    overflow: Overflow,
+}
+
+fn parse_bitfield_type(arg: &[u8]) -> Result<BitfieldType, String> {
+    let signed = match arg.first() {
+        Some(b'i') => Some(true),
+        Some(b'u') => Some(false),
+        _ => None,
+    };
+    let bits = parse_strict_int(arg.get(1..).unwrap_or_default());
+    match (signed, bits) {
+        (Some(signed), Some(bits)) if bits >= 1 && bits <= if signed { 64 } else { 63 } => Ok(BitfieldType {
+            signed,
+            bits: bits as u32,
+        }),
+        _ => Err(
+            "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+                .to_string(),
+        ),
+    }
+}
+
+/// 解析 BITFIELD 的子命令，返回子命令列表和写操作涉及的最大位偏移量
+fn parse_bitfield(args: &[Bytes]) -> Result<(Vec<BitfieldCommand>, Option<u64>), String> {
+    let mut commands = Vec::new();
+    let mut overflow = Overflow::Wrap;
+    let mut highest_write_offset = None;
+    let mut i = 0;
+    while i < args.len() {
+        let remaining = args.len() - i - 1;
+        let name = upper(&args[i]);
+        match name.as_str() {
+            "OVERFLOW" if remaining >= 1 => {
+                overflow = match upper(&args[i + 1]).as_str() {
+                    "WRAP" => Overflow::Wrap,
+                    "SAT" => Overflow::Sat,
+                    "FAIL" => Overflow::Fail,
+                    _ => return Err("ERR Invalid OVERFLOW type specified".to_string()),
+                };
+                i += 2;
+                continue;
+            }
+            "GET" if remaining >= 2 => {}
+            "SET" | "INCRBY" if remaining >= 3 => {}
+            _ => return Err("ERR syntax error".to_string()),
+        }
+        let ty = parse_bitfield_type(&args[i + 1])?;
+        let offset = parse_bit_offset(&args[i + 2], ty.bits)?;
+        let op = match name.as_str() {
+            "GET" => BitfieldOp::Get,
+            _ => {
+                let end = offset + ty.bits as u64 - 1;
+                highest_write_offset = Some(highest_write_offset.map_or(end, |highest: u64| highest.max(end)));
+                let value = parse_int(&args[i + 3])?;
+                if name == "SET" {
+                    BitfieldOp::Set(value)
+                } else {
+                    BitfieldOp::IncrBy(value)
+                }
+            }
+        };
+        i += if matches!(op, BitfieldOp::Get) { 3 } else { 4 };
+        commands.push(BitfieldCommand {
+            op,
+            ty,
+            offset,
+            overflow,
+        });
+    }
+    Ok((commands, highest_write_offset))
+}
+
+/// 执行一个子命令，溢出且使用 FAIL 方式时返回 nil
+fn execute_bitfield(command: &BitfieldCommand, value: &mut [u8]) -> RespType {
+    let current = command.ty.get(value, command.offset);
+    let (base, increment) = match command.op {
+        BitfieldOp::Get => return RespType::Integer(current),
+        BitfieldOp::Set(new_value) => (new_value, 0),
+        BitfieldOp::IncrBy(increment) => (current, increment),
+    };
+    match command.ty.add(base, increment, command.overflow) {
+        Some(new_value) => {
+            command.ty.set(value, command.offset, new_value);
+            let reply = if matches!(command.op, BitfieldOp::Set(_)) { current } else { new_value };
+            RespType::Integer(reply)
+        }
+        None => RespType::BulkString(None),
+    }
+}
+
+async fn bitfield_generic(args: Vec<Bytes>, name: &str, readonly: bool) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args(name));
+    }
+    let (commands, highest_write_offset) = parse_bitfield(&args[1..])?;
+    let highest_write_offset = match highest_write_offset {
+        Some(_) if readonly => return Err("ERR BITFIELD_RO only supports the GET subcommand".to_string()),
+        Some(offset) => offset,
+        None => {
+            // 只有 GET 时不创建键
+            let db = storage::read().await;
+            let value = db.string(&args[0])?.unwrap_or_default();
+            let replies = commands
+                .iter()
+                .map(|command| RespType::Integer(command.ty.get(&value, command.offset)))
+                .collect();
+            return Ok(RespType::Array(Some(replies)));
+        }
+    };
+    let mut db = storage::write().await;
+    let replies: Vec<RespType> = db.modify_string(&args[0], (highest_write_offset >> 3) as usize + 1, |value| {
+        commands
+            .iter()
+            .map(|command| execute_bitfield(command, value))
+            .collect()
+    })?;
+    // 每个成功执行的 SET 和 INCRBY 计为一次修改
+    let changes = commands
+        .iter()
+        .zip(&replies)
+        .filter(|(command, reply)| !matches!(command.op, BitfieldOp::Get) && !matches!(reply, RespType::BulkString(None)))
+        .count();
+    rdb::add_dirty(changes as u64);
+    Ok(RespType::Array(Some(replies)))
+}
+
+pub async fn bitfield(args: Vec<Bytes>) -> Result<RespType, String> {
+    bitfield_generic(args, "bitfield", false).await
+}
+
+pub async fn bitfield_ro(args: Vec<Bytes>) -> Result<RespType, String> {
+    bitfield_generic(args, "bitfield_ro", true).await
+}
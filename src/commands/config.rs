@@ -1,8 +1,25 @@
-use crate::{config, resp::RespType};
+use bytes::Bytes;
 
-pub async fn config_get(args: Vec<String>) -> Result<RespType, String> {
-    let parameter = &args[1];
-    let value = config::get(&parameter).await.unwrap();
-    let vec = vec![RespType::BulkString(Some(parameter.to_string())), RespType::BulkString(Some(value))];
+use crate::{config, glob::string_match, resp::RespType};
+
+use super::wrong_args;
+
+/// CONFIG GET 的参数是不区分大小写的 glob 模式，可以同时指定多个
+pub async fn config_get(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("config|get"));
+    }
+    let mut entries = config::entries().await;
+    let mut vec = vec![];
+    for pattern in &args[1..] {
+        entries.retain(|(name, value)| {
+            if !string_match(pattern, name.as_bytes(), true) {
+                return true;
+            }
+            vec.push(RespType::BulkString(Some(Bytes::from(name.clone()))));
+            vec.push(RespType::BulkString(Some(Bytes::from(value.clone()))));
+            false
+        });
+    }
     Ok(RespType::Array(Some(vec)))
 }
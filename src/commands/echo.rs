@@ -1,5 +1,12 @@
+use bytes::Bytes;
+
 use crate::resp::RespType;
 
-pub fn echo(args: Vec<String>) -> Result<RespType, String> {
-    Ok(RespType::SimpleString(args[0].clone()))
+use super::wrong_args;
+
+pub fn echo(args: Vec<Bytes>) -> Result<RespType, String> {
+    match args.first() {
+        Some(message) if args.len() == 1 => Ok(RespType::BulkString(Some(message.clone()))),
+        _ => Err(wrong_args("echo")),
+    }
 }
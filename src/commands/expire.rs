@@ -0,0 +1,139 @@
+use bytes::Bytes;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{self, from_unix_ms, now_ms, unix_ms},
+};
+
+use super::{parse_int, upper, wrong_args};
+
+/// EXPIRE 系列命令的 NX/XX/GT/LT 选项
+#[derive(Default)]
+struct ExpireFlags {
+    nx: bool,
+    xx: bool,
+    gt: bool,
+    lt: bool,
+}
+
+impl ExpireFlags {
+    fn parse(args: &[Bytes]) -> Result<Self, String> {
+        let mut flags = Self::default();
+        for arg in args {
+            match upper(arg).as_str() {
+                "NX" => flags.nx = true,
+                "XX" => flags.xx = true,
+                "GT" => flags.gt = true,
+                "LT" => flags.lt = true,
+                _ => return Err(format!("ERR Unsupported option {}", String::from_utf8_lossy(arg))),
+            }
+        }
+        if flags.nx && (flags.xx || flags.gt || flags.lt) {
+            return Err("ERR NX and XX, GT or LT options at the same time are not compatible".to_string());
+        }
+        if flags.gt && flags.lt {
+            return Err("ERR GT and LT options at the same time are not compatible".to_string());
+        }
+        Ok(flags)
+    }
+
+    /// current 为当前的过期时间（毫秒），None 表示永不过期
+    fn allows(&self, current: Option<i64>, when: i64) -> bool {
+        match current {
+            Some(current) => !(self.nx || (self.gt && when <= current) || (self.lt && when >= current)),
+            // 永不过期视为无限长的 TTL
+            None => !self.xx && !self.gt,
+        }
+    }
+}
+
+async fn expire_generic(args: Vec<Bytes>, name: &str, unit: i64, absolute: bool) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args(name));
+    }
+    let flags = ExpireFlags::parse(&args[2..])?;
+    let invalid = || format!("ERR invalid expire time in '{}' command", name);
+    let base = if absolute { 0 } else { now_ms() as i64 };
+    let when = parse_int(&args[1])?
+        .checked_mul(unit)
+        .and_then(|when| when.checked_add(base))
+        .ok_or_else(invalid)?;
+
+    let mut db = storage::write().await;
+    let current = match db.get_mut(&args[0]) {
+        Some(item) => item.expires.map(unix_ms),
+        None => return Ok(RespType::Integer(0)),
+    };
+    if !flags.allows(current, when) {
+        return Ok(RespType::Integer(0));
+    }
+    if when <= now_ms() as i64 {
+        db.delete(&args[0]);
+    } else {
+        db.set_expires(&args[0], Some(from_unix_ms(when)));
+    }
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(1))
+}
+
+pub async fn expire(args: Vec<Bytes>) -> Result<RespType, String> {
+    expire_generic(args, "expire", 1000, false).await
+}
+
+pub async fn pexpire(args: Vec<Bytes>) -> Result<RespType, String> {
+    expire_generic(args, "pexpire", 1, false).await
+}
+
+pub async fn expireat(args: Vec<Bytes>) -> Result<RespType, String> {
+    expire_generic(args, "expireat", 1000, true).await
+}
+
+pub async fn pexpireat(args: Vec<Bytes>) -> Result<RespType, String> {
+    expire_generic(args, "pexpireat", 1, true).await
+}
+
+/// TTL 系列命令，键不存在返回 -2，没有过期时间返回 -1
+async fn ttl_generic(args: Vec<Bytes>, name: &str, reply: fn(i64) -> i64) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args(name));
+    }
+    let db = storage::read().await;
+    let ttl = match db.get(&args[0]) {
+        Some(item) => item.expires.map_or(-1, |expires| reply(unix_ms(expires))),
+        None => -2,
+    };
+    Ok(RespType::Integer(ttl))
+}
+
+pub async fn ttl(args: Vec<Bytes>) -> Result<RespType, String> {
+    ttl_generic(args, "ttl", |when| ((when - now_ms() as i64).max(0) + 500) / 1000).await
+}
+
+pub async fn pttl(args: Vec<Bytes>) -> Result<RespType, String> {
+    ttl_generic(args, "pttl", |when| (when - now_ms() as i64).max(0)).await
+}
+
+pub async fn expiretime(args: Vec<Bytes>) -> Result<RespType, String> {
+    ttl_generic(args, "expiretime", |when| when / 1000).await
+}
+
+pub async fn pexpiretime(args: Vec<Bytes>) -> Result<RespType, String> {
+    ttl_generic(args, "pexpiretime", |when| when).await
+}
+
+pub async fn persist(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("persist"));
+    }
+    let mut db = storage::write().await;
+    let persisted = match db.get_mut(&args[0]) {
+        Some(item) => item.expires.is_some(),
+        None => false,
+    };
+    if persisted {
+        db.set_expires(&args[0], None);
+        rdb::add_dirty(1);
+    }
+    Ok(RespType::Integer(persisted as i64))
+}
@@ -0,0 +1,415 @@
+use bytes::Bytes;
+
+use crate::{
+    geohash::{
+        decode_score, distance, encode_wgs84, geohash_string, GeoShape, Shape, GEO_LAT_MAX, GEO_LAT_MIN,
+        GEO_LONG_MAX, GEO_LONG_MIN, GEO_STEP_MAX,
+    },
+    rdb,
+    resp::RespType,
+    storage::{self, Db, Item, ScoreRange, SortedSet, Value},
+};
+
+use super::{parse_float, parse_int, upper, wrong_args};
+
+fn syntax_error() -> String {
+    "ERR syntax error".to_string()
+}
+
+/// 与 Redis 的 addReplyHumanLongDouble 一致：保留 17 位小数并去掉末尾的 0
+fn coord_reply(value: f64) -> RespType {
+    let mut s = format!("{:.17}", value);
+    let trimmed = s.trim_end_matches('0').trim_end_matches('.').len();
+    s.truncate(trimmed);
+    if s == "-0" {
+        s = "0".to_string();
+    }
+    RespType::BulkString(Some(Bytes::from(s)))
+}
+
+fn coords_reply((longitude, latitude): (f64, f64)) -> RespType {
+    RespType::Array(Some(vec![coord_reply(longitude), coord_reply(latitude)]))
+}
+
+/// 距离保留 4 位小数
+fn distance_reply(distance: f64) -> RespType {
+    RespType::BulkString(Some(Bytes::from(format!("{:.4}", distance))))
+}
+
+/// 解析并检查经纬度
+fn parse_long_lat(longitude: &[u8], latitude: &[u8]) -> Result<(f64, f64), String> {
+    let longitude = parse_float(longitude)?;
+    let latitude = parse_float(latitude)?;
+    if !(GEO_LONG_MIN..=GEO_LONG_MAX).contains(&longitude) || !(GEO_LAT_MIN..=GEO_LAT_MAX).contains(&latitude) {
+        return Err(format!("ERR invalid longitude,latitude pair {:.6},{:.6}", longitude, latitude));
+    }
+    Ok((longitude, latitude))
+}
+
+/// 返回单位对应的米数
+fn parse_unit(arg: &[u8]) -> Result<f64, String> {
+    match String::from_utf8_lossy(arg).to_lowercase().as_str() {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err("ERR unsupported unit provided. please use M, KM, FT, MI".to_string()),
+    }
+}
+
+/// 解析非负的距离，error 是无法解析时的错误信息
+fn parse_distance(arg: &[u8], error: &str) -> Result<f64, String> {
+    parse_float(arg).map_err(|_| format!("ERR {}", error))
+}
+
+pub async fn geoadd(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 4 {
+        return Err(wrong_args("geoadd"));
+    }
+    let (mut nx, mut xx, mut ch) = (false, false, false);
+    let mut i = 1;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "NX" => nx = true,
+            "XX" => xx = true,
+            "CH" => ch = true,
+            _ => break,
+        }
+        i += 1;
+    }
+    let triples = &args[i..];
+    if triples.is_empty() || !triples.chunks_exact(3).remainder().is_empty() || (nx && xx) {
+        return Err(syntax_error());
+    }
+    let mut elements = Vec::with_capacity(triples.len() / 3);
+    for triple in triples.chunks(3) {
+        let (longitude, latitude) = parse_long_lat(&triple[0], &triple[1])?;
+        let hash = encode_wgs84(longitude, latitude, GEO_STEP_MAX).unwrap_or_default();
+        elements.push((triple[2].clone(), hash.align52() as f64));
+    }
+
+    let mut db = storage::write().await;
+    if xx && db.zset(&args[0])?.is_none() {
+        return Ok(RespType::Integer(0));
+    }
+    let zset = db.zset_or_insert(&args[0])?;
+    let (mut added, mut updated) = (0, 0);
+    for (member, score) in elements {
+        match zset.score(&member) {
+            Some(_) if nx => {}
+            Some(current) => {
+                if current != score {
+                    zset.insert(member, score);
+                    updated += 1;
+                }
+            }
+            None if xx => {}
+            None => {
+                zset.insert(member, score);
+                added += 1;
+            }
+        }
+    }
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty((added + updated) as u64);
+    Ok(RespType::Integer(if ch { added + updated } else { added }))
+}
+
+pub async fn geopos(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("geopos"));
+    }
+    let db = storage::read().await;
+    let zset = db.zset(&args[0])?;
+    let replies = args[1..]
+        .iter()
+        .map(|member| match zset.and_then(|zset| zset.score(member)) {
+            Some(score) => coords_reply(decode_score(score)),
+            None => RespType::Array(None),
+        })
+        .collect();
+    Ok(RespType::Array(Some(replies)))
+}
+
+pub async fn geodist(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 3 {
+        return Err(wrong_args("geodist"));
+    }
+    let conversion = match args.len() {
+        3 => 1.0,
+        4 => parse_unit(&args[3])?,
+        _ => return Err(syntax_error()),
+    };
+    let db = storage::read().await;
+    let zset = match db.zset(&args[0])? {
+        Some(zset) => zset,
+        None => return Ok(RespType::BulkString(None)),
+    };
+    match (zset.score(&args[1]), zset.score(&args[2])) {
+        (Some(score1), Some(score2)) => {
+            let (lon1, lat1) = decode_score(score1);
+            let (lon2, lat2) = decode_score(score2);
+            Ok(distance_reply(distance(lon1, lat1, lon2, lat2) / conversion))
+        }
+        _ => Ok(RespType::BulkString(None)),
+    }
+}
+
+pub async fn geohash(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("geohash"));
+    }
+    let db = storage::read().await;
+    let zset = db.zset(&args[0])?;
+    let replies = args[1..]
+        .iter()
+        .map(|member| {
+            let hash = zset.and_then(|zset| zset.score(member)).map(geohash_string);
+            RespType::BulkString(hash.map(Bytes::from))
+        })
+        .collect();
+    Ok(RespType::Array(Some(replies)))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Sort {
+    None,
+    Asc,
+    Desc,
+}
+
+/// 搜索的中心
+enum Origin {
+    Member(Bytes),
+    LonLat(f64, f64),
+}
+
+/// GEOSEARCH 和 GEOSEARCHSTORE 的参数
+struct SearchOptions {
+    origin: Option<Origin>,
+    shape: Option<(Shape, f64)>,
+    sort: Sort,
+    count: usize,
+    any: bool,
+    withdist: bool,
+    withhash: bool,
+    withcoord: bool,
+    storedist: bool,
+}
+
+impl SearchOptions {
+    fn parse(args: &[Bytes], name: &str, store: bool) -> Result<Self, String> {
+        let mut options = Self {
+            origin: None,
+            shape: None,
+            sort: Sort::None,
+            count: 0,
+            any: false,
+            withdist: false,
+            withhash: false,
+            withcoord: false,
+            storedist: false,
+        };
+        let mut i = 0;
+        while i < args.len() {
+            let remaining = args.len() - i - 1;
+            match upper(&args[i]).as_str() {
+                "WITHDIST" => options.withdist = true,
+                "WITHHASH" => options.withhash = true,
+                "WITHCOORD" => options.withcoord = true,
+                "ANY" => options.any = true,
+                "ASC" => options.sort = Sort::Asc,
+                "DESC" => options.sort = Sort::Desc,
+                "COUNT" if remaining >= 1 => {
+                    let count = parse_int(&args[i + 1])?;
+                    if count <= 0 {
+                        return Err("ERR COUNT must be > 0".to_string());
+                    }
+                    options.count = count as usize;
+                    i += 1;
+                }
+                "FROMMEMBER" if remaining >= 1 && !matches!(options.origin, Some(Origin::LonLat(..))) => {
+                    options.origin = Some(Origin::Member(args[i + 1].clone()));
+                    i += 1;
+                }
+                "FROMLONLAT" if remaining >= 2 && !matches!(options.origin, Some(Origin::Member(_))) => {
+                    let (longitude, latitude) = parse_long_lat(&args[i + 1], &args[i + 2])?;
+                    options.origin = Some(Origin::LonLat(longitude, latitude));
+                    i += 2;
+                }
+                "BYRADIUS" if remaining >= 2 && !matches!(options.shape, Some((Shape::Box { .. }, _))) => {
+                    let radius = parse_distance(&args[i + 1], "need numeric radius")?;
+                    if radius < 0.0 {
+                        return Err("ERR radius cannot be negative".to_string());
+                    }
+                    options.shape = Some((Shape::Radius(radius), parse_unit(&args[i + 2])?));
+                    i += 2;
+                }
+                "BYBOX" if remaining >= 3 && !matches!(options.shape, Some((Shape::Radius(_), _))) => {
+                    let width = parse_distance(&args[i + 1], "need numeric width")?;
+                    let height = parse_distance(&args[i + 2], "need numeric height")?;
+                    if width < 0.0 || height < 0.0 {
+                        return Err("ERR height or width cannot be negative".to_string());
+                    }
+                    options.shape = Some((Shape::Box { width, height }, parse_unit(&args[i + 3])?));
+                    i += 3;
+                }
+                "STOREDIST" if store => options.storedist = true,
+                _ => return Err(syntax_error()),
+            }
+            i += 1;
+        }
+        if store && (options.withdist || options.withhash || options.withcoord) {
+            return Err("ERR GEOSEARCHSTORE is not compatible with WITHDIST, WITHHASH and WITHCOORD options".to_string());
+        }
+        if options.origin.is_none() {
+            return Err(format!("ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for {}", name));
+        }
+        if options.shape.is_none() {
+            return Err(format!("ERR exactly one of BYRADIUS and BYBOX can be specified for {}", name));
+        }
+        if options.any && options.count == 0 {
+            return Err("ERR the ANY argument requires COUNT argument".to_string());
+        }
+        // 指定 COUNT 时需要排序才能返回最近的 N 个结果，使用 ANY 时除外
+        if options.count != 0 && options.sort == Sort::None && !options.any {
+            options.sort = Sort::Asc;
+        }
+        Ok(options)
+    }
+}
+
+/// 搜索到的点
+struct GeoPoint {
+    member: Bytes,
+    score: f64,
+    longitude: f64,
+    latitude: f64,
+    /// 使用用户指定的单位
+    distance: f64,
+}
+
+/// 在有序集合中搜索，按 Redis 的顺序遍历中心区域和周围的区域
+fn search(zset: &SortedSet, options: &SearchOptions) -> Result<Vec<GeoPoint>, String> {
+    let (longitude, latitude) = match options.origin.as_ref() {
+        Some(Origin::Member(member)) => decode_score(
+            zset.score(member)
+                .ok_or_else(|| "ERR could not decode requested zset member".to_string())?,
+        ),
+        Some(Origin::LonLat(longitude, latitude)) => (*longitude, *latitude),
+        None => unreachable!(),
+    };
+    let (shape, conversion) = options.shape.unwrap();
+    let shape = GeoShape {
+        longitude,
+        latitude,
+        conversion,
+        shape,
+    };
+    // 使用 ANY 时找到足够的结果就停止
+    let limit = if options.any { options.count } else { 0 };
+    let mut points = Vec::new();
+    for area in shape.search_areas() {
+        if limit > 0 && points.len() >= limit {
+            break;
+        }
+        let min = area.align52() as f64;
+        let max = area.neighbor_max().align52() as f64;
+        let range = ScoreRange {
+            min,
+            max,
+            minex: false,
+            maxex: true,
+        };
+        for (member, score) in zset.range_by_score(&range, false, 0, None) {
+            let (longitude, latitude) = decode_score(score);
+            if let Some(distance) = shape.distance_if_within(longitude, latitude) {
+                points.push(GeoPoint {
+                    member,
+                    score,
+                    longitude,
+                    latitude,
+                    distance: distance / conversion,
+                });
+                if limit > 0 && points.len() >= limit {
+                    break;
+                }
+            }
+        }
+    }
+    match options.sort {
+        Sort::None => {}
+        Sort::Asc => points.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+        Sort::Desc => points.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+    }
+    if options.count > 0 {
+        points.truncate(options.count);
+    }
+    Ok(points)
+}
+
+/// 执行搜索，源键不存在时返回 None
+fn search_key(db: &Db, key: &[u8], options: &SearchOptions) -> Result<Option<Vec<GeoPoint>>, String> {
+    match db.zset(key)? {
+        Some(zset) => Ok(Some(search(zset, options)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn geosearch(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 6 {
+        return Err(wrong_args("geosearch"));
+    }
+    let db = storage::read().await;
+    // 先检查类型再解析参数
+    db.zset(&args[0])?;
+    let options = SearchOptions::parse(&args[1..], "geosearch", false)?;
+    let points = search_key(&db, &args[0], &options)?.unwrap_or_default();
+    let replies = points
+        .into_iter()
+        .map(|point| {
+            let member = RespType::BulkString(Some(point.member));
+            if !(options.withdist || options.withhash || options.withcoord) {
+                return member;
+            }
+            let mut reply = vec![member];
+            if options.withdist {
+                reply.push(distance_reply(point.distance));
+            }
+            if options.withhash {
+                reply.push(RespType::Integer(point.score as i64));
+            }
+            if options.withcoord {
+                reply.push(coords_reply((point.longitude, point.latitude)));
+            }
+            RespType::Array(Some(reply))
+        })
+        .collect();
+    Ok(RespType::Array(Some(replies)))
+}
+
+/// 结果写入目标键，STOREDIST 时使用距离作为分数，没有结果时删除目标键
+pub async fn geosearchstore(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 7 {
+        return Err(wrong_args("geosearchstore"));
+    }
+    let mut db = storage::write().await;
+    db.zset(&args[1])?;
+    let options = SearchOptions::parse(&args[2..], "geosearchstore", true)?;
+    let points = search_key(&db, &args[1], &options)?.unwrap_or_default();
+    let len = points.len();
+    if len == 0 {
+        if db.delete(&args[0]) {
+            rdb::add_dirty(1);
+        }
+        return Ok(RespType::Integer(0));
+    }
+    let mut zset = SortedSet::default();
+    for point in points {
+        let score = if options.storedist { point.distance } else { point.score };
+        zset.insert(point.member, score);
+    }
+    db.insert(args[0].clone(), Item::new(Value::ZSet(zset)));
+    rdb::add_dirty(len as u64);
+    Ok(RespType::Integer(len as i64))
+}
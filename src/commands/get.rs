@@ -1,31 +1,27 @@
-use crate::{resp::RespType, storage};
-use regex::Regex;
+use bytes::Bytes;
 
-pub async fn get(args: Vec<String>) -> Result<RespType, String> {
-    Ok(match storage::get(&args[0]).await {
-        Some(value) => RespType::SimpleString(value),
-        None => RespType::BulkString(None),
-    })
-}
+use crate::{glob::string_match, resp::RespType, storage};
+
+use super::wrong_args;
 
-pub async fn keys(args: Vec<String>) -> Result<RespType, String> {
+pub async fn get(args: Vec<Bytes>) -> Result<RespType, String> {
     if args.len() != 1 {
-        return Ok(RespType::SimpleError("wrong number of arguments for 'keys' command".to_string()));
+        return Err(wrong_args("get"));
     }
+    Ok(RespType::BulkString(storage::get(&args[0]).await?))
+}
 
-    let regex_pattern = &args[0].replace('*', ".*"); // Replace '*' with '.*' (wildcard)
+pub async fn keys(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("keys"));
+    }
 
-    let patten = Regex::new(regex_pattern).unwrap();
-    let keys = storage::keys()
+    let pattern = &args[0];
+    let values = storage::keys()
         .await
-        .iter()
-        .filter(|key| patten.is_match(key))
-        .map(|key| key.to_string())
-        .collect::<Vec<String>>();
-    
-    let values = keys
-        .iter()
-        .map(|key| RespType::SimpleString(key.to_string()))
+        .into_iter()
+        .filter(|key| string_match(pattern, key, false))
+        .map(|key| RespType::BulkString(Some(key)))
         .collect::<Vec<RespType>>();
     let reply = RespType::Array(Some(values));
     Ok(reply)
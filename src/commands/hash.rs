@@ -0,0 +1,239 @@
+use bytes::Bytes;
+
+use crate::{random, rdb, resp::RespType, storage};
+
+use super::{incr_float, parse_float, parse_int, parse_random_count, upper, wrong_args};
+
+fn bulk(value: &Bytes) -> RespType {
+    RespType::BulkString(Some(value.clone()))
+}
+
+pub async fn hset(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 3 || args.len() % 2 != 1 {
+        return Err(wrong_args("hset"));
+    }
+    let mut db = storage::write().await;
+    let hash = db.hash_or_insert(&args[0])?;
+    let mut added = 0;
+    for pair in args[1..].chunks(2) {
+        if hash.insert(pair[0].clone(), pair[1].clone()).is_none() {
+            added += 1;
+        }
+    }
+    rdb::add_dirty((args.len() / 2) as u64);
+    Ok(RespType::Integer(added))
+}
+
+pub async fn hmset(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 3 || args.len() % 2 != 1 {
+        return Err(wrong_args("hmset"));
+    }
+    hset(args).await?;
+    Ok(RespType::SimpleString("OK".to_string()))
+}
+
+pub async fn hsetnx(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("hsetnx"));
+    }
+    let mut db = storage::write().await;
+    let hash = db.hash_or_insert(&args[0])?;
+    if hash.contains_key(&args[1]) {
+        return Ok(RespType::Integer(0));
+    }
+    hash.insert(args[1].clone(), args[2].clone());
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(1))
+}
+
+pub async fn hget(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("hget"));
+    }
+    let db = storage::read().await;
+    let value = db.hash(&args[0])?.and_then(|hash| hash.get(&args[1]).cloned());
+    Ok(RespType::BulkString(value))
+}
+
+pub async fn hmget(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("hmget"));
+    }
+    let db = storage::read().await;
+    let hash = db.hash(&args[0])?;
+    let values = args[1..]
+        .iter()
+        .map(|field| RespType::BulkString(hash.and_then(|hash| hash.get(field).cloned())))
+        .collect();
+    Ok(RespType::Array(Some(values)))
+}
+
+pub async fn hdel(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("hdel"));
+    }
+    let mut db = storage::write().await;
+    let hash = match db.hash_mut(&args[0])? {
+        Some(hash) => hash,
+        None => return Ok(RespType::Integer(0)),
+    };
+    let removed = args[1..]
+        .iter()
+        .filter(|field| hash.remove(*field).is_some())
+        .count();
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty(removed as u64);
+    Ok(RespType::Integer(removed as i64))
+}
+
+pub async fn hgetall(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("hgetall"));
+    }
+    let db = storage::read().await;
+    let values = match db.hash(&args[0])? {
+        Some(hash) => hash
+            .iter()
+            .flat_map(|(field, value)| [bulk(field), bulk(value)])
+            .collect(),
+        None => vec![],
+    };
+    Ok(RespType::Array(Some(values)))
+}
+
+pub async fn hkeys(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("hkeys"));
+    }
+    let db = storage::read().await;
+    let values = match db.hash(&args[0])? {
+        Some(hash) => hash.keys().map(bulk).collect(),
+        None => vec![],
+    };
+    Ok(RespType::Array(Some(values)))
+}
+
+pub async fn hvals(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("hvals"));
+    }
+    let db = storage::read().await;
+    let values = match db.hash(&args[0])? {
+        Some(hash) => hash.values().map(bulk).collect(),
+        None => vec![],
+    };
+    Ok(RespType::Array(Some(values)))
+}
+
+pub async fn hlen(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("hlen"));
+    }
+    let db = storage::read().await;
+    let len = db.hash(&args[0])?.map_or(0, |hash| hash.len());
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn hexists(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("hexists"));
+    }
+    let db = storage::read().await;
+    let exists = db.hash(&args[0])?.is_some_and(|hash| hash.contains_key(&args[1]));
+    Ok(RespType::Integer(exists as i64))
+}
+
+pub async fn hstrlen(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("hstrlen"));
+    }
+    let db = storage::read().await;
+    let len = db
+        .hash(&args[0])?
+        .and_then(|hash| hash.get(&args[1]))
+        .map_or(0, |value| value.len());
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn hincrby(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("hincrby"));
+    }
+    let increment = parse_int(&args[2])?;
+    let mut db = storage::write().await;
+    // 先计算出新值，出错时不创建键
+    let current = match db.hash(&args[0])?.and_then(|hash| hash.get(&args[1])) {
+        Some(value) => parse_int(value).map_err(|_| "ERR hash value is not an integer".to_string())?,
+        None => 0,
+    };
+    let value = current
+        .checked_add(increment)
+        .ok_or_else(|| "ERR increment or decrement would overflow".to_string())?;
+    db.hash_or_insert(&args[0])?.insert(args[1].clone(), Bytes::from(value.to_string()));
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(value))
+}
+
+pub async fn hincrbyfloat(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("hincrbyfloat"));
+    }
+    parse_float(&args[2])?;
+    let mut db = storage::write().await;
+    let current = match db.hash(&args[0])?.and_then(|hash| hash.get(&args[1])) {
+        Some(value) => {
+            parse_float(value).map_err(|_| "ERR hash value is not a float".to_string())?;
+            value.clone()
+        }
+        None => Bytes::from_static(b"0"),
+    };
+    let value = incr_float(&current, &args[2])?;
+    db.hash_or_insert(&args[0])?.insert(args[1].clone(), value.clone());
+    rdb::add_dirty(1);
+    Ok(RespType::BulkString(Some(value)))
+}
+
+pub async fn hrandfield(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(wrong_args("hrandfield"));
+    }
+    let count = args.get(1).map(|count| parse_random_count(count)).transpose()?;
+    let with_values = match args.get(2) {
+        Some(arg) if upper(arg) == "WITHVALUES" => true,
+        Some(_) => return Err("ERR syntax error".to_string()),
+        None => false,
+    };
+    let db = storage::read().await;
+    let hash = db.hash(&args[0])?;
+    let count = match count {
+        Some(count) => count,
+        None => {
+            let field = hash.and_then(|hash| hash.random()).map(|(field, _)| field.clone());
+            return Ok(RespType::BulkString(field));
+        }
+    };
+    let hash = match hash {
+        Some(hash) => hash,
+        None => return Ok(RespType::Array(Some(vec![]))),
+    };
+    let entries: Vec<(&Bytes, &Bytes)> = hash.iter().collect();
+    let picked = if count >= 0 {
+        random::sample(entries, count as usize)
+    } else {
+        // 负数表示允许重复
+        (0..count.unsigned_abs())
+            .map(|_| entries[random::below(entries.len())])
+            .collect()
+    };
+    let values = picked
+        .into_iter()
+        .flat_map(|(field, value)| {
+            let mut reply = vec![bulk(field)];
+            if with_values {
+                reply.push(bulk(value));
+            }
+            reply
+        })
+        .collect();
+    Ok(RespType::Array(Some(values)))
+}
@@ -0,0 +1,91 @@
+use bytes::Bytes;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{
+        self, hll_add, hll_count, hll_count_registers, hll_invalidate_cache, hll_is_dense, hll_is_valid, hll_merge, hll_new,
+        hll_store_registers, Db, StringValue, HLL_REGISTERS, INVALID_HLL,
+    },
+};
+
+use super::wrong_args;
+
+/// 读取 HyperLogLog，值不是合法的 HyperLogLog 时返回错误
+fn hll(db: &Db, key: &[u8]) -> Result<Option<Bytes>, String> {
+    match db.string_value(key)? {
+        Some(StringValue::Raw(value)) if hll_is_valid(value) => Ok(Some(value.clone())),
+        Some(_) => Err(INVALID_HLL.to_string()),
+        None => Ok(None),
+    }
+}
+
+pub async fn pfadd(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("pfadd"));
+    }
+    let mut db = storage::write().await;
+    let created = hll(&db, &args[0])?.is_none();
+    let updated = db.modify_string(&args[0], 0, |value| {
+        if created {
+            *value = hll_new();
+        }
+        let mut updated = created;
+        for element in &args[1..] {
+            updated |= hll_add(value, element)?;
+        }
+        if updated {
+            hll_invalidate_cache(value);
+        }
+        Ok::<_, String>(updated)
+    })??;
+    rdb::add_dirty(updated as u64);
+    Ok(RespType::Integer(updated as i64))
+}
+
+/// 单个键时返回缓存的基数并在缓存失效时更新缓存，多个键时返回并集的基数
+pub async fn pfcount(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("pfcount"));
+    }
+    let mut db = storage::write().await;
+    if args.len() == 1 {
+        if hll(&db, &args[0])?.is_none() {
+            return Ok(RespType::Integer(0));
+        }
+        let count = db.modify_string(&args[0], 0, |value| hll_count(value))??;
+        return Ok(RespType::Integer(count as i64));
+    }
+    let mut max = vec![0; HLL_REGISTERS];
+    for key in &args {
+        if let Some(value) = hll(&db, key)? {
+            hll_merge(&mut max, &value)?;
+        }
+    }
+    Ok(RespType::Integer(hll_count_registers(&max) as i64))
+}
+
+/// 目标键本身也参与合并，任意一个输入是密集编码时结果使用密集编码
+pub async fn pfmerge(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("pfmerge"));
+    }
+    let mut db = storage::write().await;
+    let mut max = vec![0; HLL_REGISTERS];
+    let mut dense = false;
+    for key in &args {
+        if let Some(value) = hll(&db, key)? {
+            dense |= hll_is_dense(&value);
+            hll_merge(&mut max, &value)?;
+        }
+    }
+    let created = hll(&db, &args[0])?.is_none();
+    db.modify_string(&args[0], 0, |value| {
+        if created {
+            *value = hll_new();
+        }
+        hll_store_registers(value, &max, dense)
+    })??;
+    rdb::add_dirty(1);
+    Ok(RespType::SimpleString("OK".to_string()))
+}
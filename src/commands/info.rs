@@ -1,12 +1,88 @@
-use crate::{config, resp::RespType};
+use bytes::Bytes;
 
+use crate::{aof, config, rdb, resp::RespType, storage};
 
+/// 按 Redis 的格式生成一个段落
+fn section(name: &str, fields: Vec<(&str, String)>) -> String {
+    let mut section = format!("# {}\r\n", name);
+    for (field, value) in fields {
+        section.push_str(&format!("{}:{}\r\n", field, value));
+    }
+    section
+}
 
-
-pub async fn info() -> Result<RespType, String> {
+async fn replication() -> String {
     let role = match config::get("replicaof").await {
         Some(_) => "slave",
         None => "master",
     };
-    Ok(RespType::SimpleString(format!("role: {}", role)))
-}
\ No newline at end of file
+    section("Replication", vec![("role", role.to_string())])
+}
+
+fn status(ok: bool) -> String {
+    if ok { "ok" } else { "err" }.to_string()
+}
+
+fn persistence() -> String {
+    let mut fields = vec![
+        ("loading", "0".to_string()),
+        ("rdb_changes_since_last_save", rdb::dirty().to_string()),
+        ("rdb_bgsave_in_progress", (rdb::bgsave_in_progress() as u8).to_string()),
+        ("rdb_last_save_time", rdb::lastsave().to_string()),
+        ("rdb_last_bgsave_status", status(rdb::lastbgsave_ok())),
+        ("aof_enabled", (aof::enabled() as u8).to_string()),
+        ("aof_rewrite_in_progress", (aof::rewrite_in_progress() as u8).to_string()),
+        ("aof_last_bgrewrite_status", status(aof::last_rewrite_ok())),
+    ];
+    // 与 Redis 一致，只在开启 AOF 时列出文件大小
+    if aof::enabled() {
+        fields.push(("aof_current_size", aof::current_size().to_string()));
+        fields.push(("aof_base_size", aof::base_size().to_string()));
+    }
+    section("Persistence", fields)
+}
+
+async fn stats() -> String {
+    let mut expired_keys = 0;
+    for index in 0..storage::db_count() {
+        expired_keys += storage::read_db(index).await.expired_keys;
+    }
+    section("Stats", vec![("expired_keys", expired_keys.to_string())])
+}
+
+/// 只列出非空的数据库
+async fn keyspace() -> String {
+    let mut fields = vec![];
+    for index in 0..storage::db_count() {
+        let db = storage::read_db(index).await;
+        if db.size() > 0 {
+            let value = format!("keys={},expires={},avg_ttl={}", db.size(), db.volatile_len(), db.avg_ttl);
+            fields.push((format!("db{}", index), value));
+        }
+    }
+    section("Keyspace", fields.iter().map(|(name, value)| (name.as_str(), value.clone())).collect())
+}
+
+pub async fn info(args: Vec<Bytes>) -> Result<RespType, String> {
+    let sections: Vec<String> = args.iter().map(|arg| String::from_utf8_lossy(arg).to_lowercase()).collect();
+    let all = sections.is_empty()
+        || sections
+            .iter()
+            .any(|name| matches!(name.as_str(), "all" | "everything" | "default"));
+    let wanted = |name: &str| all || sections.iter().any(|section| section == name);
+
+    let mut output = vec![];
+    if wanted("replication") {
+        output.push(replication().await);
+    }
+    if wanted("persistence") {
+        output.push(persistence());
+    }
+    if wanted("stats") {
+        output.push(stats().await);
+    }
+    if wanted("keyspace") {
+        output.push(keyspace().await);
+    }
+    Ok(RespType::BulkString(Some(Bytes::from(output.join("\r\n")))))
+}
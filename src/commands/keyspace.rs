@@ -0,0 +1,254 @@
+use bytes::Bytes;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{self, Db, Item},
+};
+
+use super::{parse_int, upper, wrong_args};
+
+fn ok() -> RespType {
+    RespType::SimpleString("OK".to_string())
+}
+
+pub async fn del(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("del"));
+    }
+    let mut db = storage::write().await;
+    let deleted = args.iter().filter(|key| db.delete(key)).count();
+    rdb::add_dirty(deleted as u64);
+    Ok(RespType::Integer(deleted as i64))
+}
+
+pub async fn unlink(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("unlink"));
+    }
+    let mut db = storage::write().await;
+    let items: Vec<_> = args.iter().filter_map(|key| db.take(key)).collect();
+    drop(db);
+    let deleted = items.len();
+    storage::free_items(items);
+    rdb::add_dirty(deleted as u64);
+    Ok(RespType::Integer(deleted as i64))
+}
+
+/// 统计存在的键数，重复的键会重复计数
+async fn count_existing(args: Vec<Bytes>, name: &str) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args(name));
+    }
+    let db = storage::read().await;
+    let count = args.iter().filter(|key| db.get(key).is_some()).count();
+    Ok(RespType::Integer(count as i64))
+}
+
+pub async fn exists(args: Vec<Bytes>) -> Result<RespType, String> {
+    count_existing(args, "exists").await
+}
+
+pub async fn touch(args: Vec<Bytes>) -> Result<RespType, String> {
+    count_existing(args, "touch").await
+}
+
+pub async fn key_type(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("type"));
+    }
+    let db = storage::read().await;
+    let name = db.get(&args[0]).map_or("none", |item| item.value.type_name());
+    Ok(RespType::SimpleString(name.to_string()))
+}
+
+async fn rename_generic(args: Vec<Bytes>, nx: bool) -> Result<RespType, String> {
+    let mut db = storage::write().await;
+    if db.get_mut(&args[0]).is_none() {
+        return Err("ERR no such key".to_string());
+    }
+    if args[0] == args[1] {
+        return Ok(if nx { RespType::Integer(0) } else { ok() });
+    }
+    if nx && db.get_mut(&args[1]).is_some() {
+        return Ok(RespType::Integer(0));
+    }
+    if let Some(item) = db.take(&args[0]) {
+        db.insert(args[1].clone(), item);
+    }
+    rdb::add_dirty(1);
+    Ok(if nx { RespType::Integer(1) } else { ok() })
+}
+
+pub async fn rename(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("rename"));
+    }
+    rename_generic(args, false).await
+}
+
+pub async fn renamenx(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("renamenx"));
+    }
+    rename_generic(args, true).await
+}
+
+/// 解析数据库编号，invalid 为无法解析时的错误信息，默认使用整数的错误信息
+fn parse_db_index(arg: &[u8], invalid: Option<&str>) -> Result<usize, String> {
+    let index = match invalid {
+        Some(invalid) => parse_int(arg).map_err(|_| format!("ERR {}", invalid))?,
+        None => parse_int(arg)?,
+    };
+    if index < 0 || index as usize >= storage::db_count() {
+        return Err("ERR DB index is out of range".to_string());
+    }
+    Ok(index as usize)
+}
+
+pub fn select(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("select"));
+    }
+    let index = parse_db_index(&args[0], Some("invalid DB index"))?;
+    storage::select_db(index);
+    Ok(ok())
+}
+
+/// 把键移动到另一个数据库，目标数据库中已有同名的键时不移动
+pub async fn move_key(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("move"));
+    }
+    let src = storage::selected_db();
+    let dst = parse_db_index(&args[1], None)?;
+    if src == dst {
+        return Err("ERR source and destination objects are the same".to_string());
+    }
+    let (mut src_db, mut dst_db) = storage::write_pair(src, dst).await;
+    if src_db.get_mut(&args[0]).is_none() || dst_db.get_mut(&args[0]).is_some() {
+        return Ok(RespType::Integer(0));
+    }
+    if let Some(item) = src_db.take(&args[0]) {
+        dst_db.insert(args[0].clone(), item);
+    }
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(1))
+}
+
+pub async fn swapdb(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("swapdb"));
+    }
+    let first = parse_db_index(&args[0], Some("invalid first DB index"))?;
+    let second = parse_db_index(&args[1], Some("invalid second DB index"))?;
+    if first != second {
+        let (mut first_db, mut second_db) = storage::write_pair(first, second).await;
+        std::mem::swap(&mut *first_db, &mut *second_db);
+    }
+    rdb::add_dirty(1);
+    Ok(ok())
+}
+
+/// 把 item 写入 dst，目标键已存在且未指定 REPLACE 时不写入
+fn copy_to(dst: &mut Db, key: &Bytes, item: Item, replace: bool) -> RespType {
+    if dst.get_mut(key).is_some() && !replace {
+        return RespType::Integer(0);
+    }
+    dst.insert(key.clone(), item);
+    rdb::add_dirty(1);
+    RespType::Integer(1)
+}
+
+pub async fn copy(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("copy"));
+    }
+    let src = storage::selected_db();
+    let mut dst = src;
+    let mut replace = false;
+    let mut i = 2;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "REPLACE" => replace = true,
+            "DB" if i + 1 < args.len() => {
+                dst = parse_db_index(&args[i + 1], None)?;
+                i += 1;
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+        i += 1;
+    }
+    if src == dst && args[0] == args[1] {
+        return Err("ERR source and destination objects are the same".to_string());
+    }
+    if src == dst {
+        let mut db = storage::write().await;
+        let item = match db.get_mut(&args[0]) {
+            Some(item) => item.clone(),
+            None => return Ok(RespType::Integer(0)),
+        };
+        return Ok(copy_to(&mut db, &args[1], item, replace));
+    }
+    let (mut src_db, mut dst_db) = storage::write_pair(src, dst).await;
+    let item = match src_db.get_mut(&args[0]) {
+        Some(item) => item.clone(),
+        None => return Ok(RespType::Integer(0)),
+    };
+    Ok(copy_to(&mut dst_db, &args[1], item, replace))
+}
+
+pub async fn randomkey(args: Vec<Bytes>) -> Result<RespType, String> {
+    if !args.is_empty() {
+        return Err(wrong_args("randomkey"));
+    }
+    let db = storage::read().await;
+    Ok(RespType::BulkString(db.random_key()))
+}
+
+pub async fn dbsize(args: Vec<Bytes>) -> Result<RespType, String> {
+    if !args.is_empty() {
+        return Err(wrong_args("dbsize"));
+    }
+    let db = storage::read().await;
+    Ok(RespType::Integer(db.size() as i64))
+}
+
+/// 解析 FLUSHDB/FLUSHALL 的 ASYNC|SYNC 参数，返回是否在后台释放
+fn parse_flush_mode(args: &[Bytes], name: &str) -> Result<bool, String> {
+    match args {
+        [] => Ok(false),
+        [mode] => match upper(mode).as_str() {
+            "ASYNC" => Ok(true),
+            "SYNC" => Ok(false),
+            _ => Err("ERR syntax error".to_string()),
+        },
+        _ => Err(wrong_args(name)),
+    }
+}
+
+/// all 为 true 时清空所有数据库，否则只清空当前数据库
+async fn flush(args: Vec<Bytes>, name: &str, all: bool) -> Result<RespType, String> {
+    let lazy = parse_flush_mode(&args, name)?;
+    let mut dbs = if all {
+        storage::write_all().await
+    } else {
+        vec![storage::write().await]
+    };
+    let items: Vec<_> = dbs.iter_mut().map(|db| db.clear()).collect();
+    drop(dbs);
+    // 与 Redis 一样，数据库本来为空时也计为一次修改，保证 FLUSHDB 和 FLUSHALL 总会写入 AOF
+    rdb::add_dirty(items.iter().map(|items| items.len() as u64).sum::<u64>() + 1);
+    if lazy {
+        storage::free_async(items);
+    }
+    Ok(ok())
+}
+
+pub async fn flushdb(args: Vec<Bytes>) -> Result<RespType, String> {
+    flush(args, "flushdb", false).await
+}
+
+pub async fn flushall(args: Vec<Bytes>) -> Result<RespType, String> {
+    flush(args, "flushall", true).await
+}
@@ -0,0 +1,299 @@
+use bytes::Bytes;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{self, normalize_index, normalize_range, List},
+};
+
+use super::{parse_int, upper, wrong_args};
+
+#[derive(Clone, Copy, PartialEq)]
+enum End {
+    Left,
+    Right,
+}
+
+impl End {
+    fn parse(arg: &[u8]) -> Result<Self, String> {
+        match upper(arg).as_str() {
+            "LEFT" => Ok(End::Left),
+            "RIGHT" => Ok(End::Right),
+            _ => Err("ERR syntax error".to_string()),
+        }
+    }
+
+    fn pop(self, list: &mut List) -> Option<Bytes> {
+        match self {
+            End::Left => list.pop_front(),
+            End::Right => list.pop_back(),
+        }
+    }
+
+    fn push(self, list: &mut List, value: Bytes) {
+        match self {
+            End::Left => list.push_front(value),
+            End::Right => list.push_back(value),
+        }
+    }
+}
+
+async fn push(args: Vec<Bytes>, name: &str, end: End, only_existing: bool) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args(name));
+    }
+    let mut db = storage::write().await;
+    let list = if only_existing {
+        match db.list_mut(&args[0])? {
+            Some(list) => list,
+            None => return Ok(RespType::Integer(0)),
+        }
+    } else {
+        db.list_or_insert(&args[0])?
+    };
+    for value in &args[1..] {
+        end.push(list, value.clone());
+    }
+    rdb::add_dirty((args.len() - 1) as u64);
+    Ok(RespType::Integer(list.len() as i64))
+}
+
+pub async fn lpush(args: Vec<Bytes>) -> Result<RespType, String> {
+    push(args, "lpush", End::Left, false).await
+}
+
+pub async fn rpush(args: Vec<Bytes>) -> Result<RespType, String> {
+    push(args, "rpush", End::Right, false).await
+}
+
+pub async fn lpushx(args: Vec<Bytes>) -> Result<RespType, String> {
+    push(args, "lpushx", End::Left, true).await
+}
+
+pub async fn rpushx(args: Vec<Bytes>) -> Result<RespType, String> {
+    push(args, "rpushx", End::Right, true).await
+}
+
+async fn pop(args: Vec<Bytes>, name: &str, end: End) -> Result<RespType, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(wrong_args(name));
+    }
+    let count = match args.get(1) {
+        Some(count) => {
+            let count = parse_int(count)?;
+            if count < 0 {
+                return Err("ERR value is out of range, must be positive".to_string());
+            }
+            Some(count as usize)
+        }
+        None => None,
+    };
+    let mut db = storage::write().await;
+    let list = match db.list_mut(&args[0])? {
+        Some(list) => list,
+        None if count.is_some() => return Ok(RespType::Array(None)),
+        None => return Ok(RespType::BulkString(None)),
+    };
+    let (reply, popped) = match count {
+        Some(count) => {
+            let mut values = Vec::new();
+            while values.len() < count {
+                match end.pop(list) {
+                    Some(value) => values.push(RespType::BulkString(Some(value))),
+                    None => break,
+                }
+            }
+            let popped = values.len();
+            (RespType::Array(Some(values)), popped)
+        }
+        None => {
+            let value = end.pop(list);
+            let popped = value.is_some() as usize;
+            (RespType::BulkString(value), popped)
+        }
+    };
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty(popped as u64);
+    Ok(reply)
+}
+
+pub async fn lpop(args: Vec<Bytes>) -> Result<RespType, String> {
+    pop(args, "lpop", End::Left).await
+}
+
+pub async fn rpop(args: Vec<Bytes>) -> Result<RespType, String> {
+    pop(args, "rpop", End::Right).await
+}
+
+pub async fn llen(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("llen"));
+    }
+    let db = storage::read().await;
+    let len = db.list(&args[0])?.map_or(0, |list| list.len());
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn lrange(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("lrange"));
+    }
+    let start = parse_int(&args[1])?;
+    let stop = parse_int(&args[2])?;
+    let db = storage::read().await;
+    let values = match db.list(&args[0])? {
+        Some(list) => match normalize_range(start, stop, list.len()) {
+            Some((start, end)) => list
+                .range(start..=end)
+                .map(|value| RespType::BulkString(Some(value.clone())))
+                .collect(),
+            None => vec![],
+        },
+        None => vec![],
+    };
+    Ok(RespType::Array(Some(values)))
+}
+
+pub async fn lindex(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("lindex"));
+    }
+    let index = parse_int(&args[1])?;
+    let db = storage::read().await;
+    let value = db
+        .list(&args[0])?
+        .and_then(|list| normalize_index(index, list.len()).map(|i| list[i].clone()));
+    Ok(RespType::BulkString(value))
+}
+
+pub async fn lset(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("lset"));
+    }
+    let index = parse_int(&args[1])?;
+    let mut db = storage::write().await;
+    let list = db
+        .list_mut(&args[0])?
+        .ok_or_else(|| "ERR no such key".to_string())?;
+    let index = normalize_index(index, list.len()).ok_or_else(|| "ERR index out of range".to_string())?;
+    list[index] = args[2].clone();
+    rdb::add_dirty(1);
+    Ok(RespType::SimpleString("OK".to_string()))
+}
+
+pub async fn lrem(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("lrem"));
+    }
+    let count = parse_int(&args[1])?;
+    let element = &args[2];
+    let mut db = storage::write().await;
+    let list = match db.list_mut(&args[0])? {
+        Some(list) => list,
+        None => return Ok(RespType::Integer(0)),
+    };
+    let limit = if count == 0 { usize::MAX } else { count.unsigned_abs() as usize };
+    let mut removed = 0;
+    if count >= 0 {
+        list.retain(|item| {
+            let remove = removed < limit && item == element;
+            removed += remove as usize;
+            !remove
+        });
+    } else {
+        // 从尾部向前把保留的元素依次移到尾部，最后一次性去掉头部
+        let mut kept = list.len();
+        for i in (0..list.len()).rev() {
+            if removed < limit && list[i] == *element {
+                removed += 1;
+            } else {
+                kept -= 1;
+                list.swap(i, kept);
+            }
+        }
+        list.drain(..kept);
+    }
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty(removed as u64);
+    Ok(RespType::Integer(removed as i64))
+}
+
+pub async fn ltrim(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("ltrim"));
+    }
+    let start = parse_int(&args[1])?;
+    let stop = parse_int(&args[2])?;
+    let mut db = storage::write().await;
+    if let Some(list) = db.list_mut(&args[0])? {
+        let len = list.len();
+        match normalize_range(start, stop, list.len()) {
+            Some((start, end)) => {
+                list.truncate(end + 1);
+                list.drain(..start);
+            }
+            None => list.clear(),
+        }
+        rdb::add_dirty((len - list.len()) as u64);
+        db.remove_if_empty(&args[0]);
+    }
+    Ok(RespType::SimpleString("OK".to_string()))
+}
+
+pub async fn linsert(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 4 {
+        return Err(wrong_args("linsert"));
+    }
+    let after = match upper(&args[1]).as_str() {
+        "BEFORE" => false,
+        "AFTER" => true,
+        _ => return Err("ERR syntax error".to_string()),
+    };
+    let mut db = storage::write().await;
+    let list = match db.list_mut(&args[0])? {
+        Some(list) => list,
+        None => return Ok(RespType::Integer(0)),
+    };
+    match list.iter().position(|value| value == &args[2]) {
+        Some(pos) => {
+            list.insert(if after { pos + 1 } else { pos }, args[3].clone());
+            rdb::add_dirty(1);
+            Ok(RespType::Integer(list.len() as i64))
+        }
+        None => Ok(RespType::Integer(-1)),
+    }
+}
+
+async fn move_element(source: &Bytes, destination: &Bytes, from: End, to: End) -> Result<RespType, String> {
+    let mut db = storage::write().await;
+    // 先检查目标键的类型，避免弹出元素后才发现无法写入
+    db.list(destination)?;
+    let value = match db.list_mut(source)? {
+        Some(list) => from.pop(list),
+        None => None,
+    };
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(RespType::BulkString(None)),
+    };
+    db.remove_if_empty(source);
+    to.push(db.list_or_insert(destination)?, value.clone());
+    rdb::add_dirty(1);
+    Ok(RespType::BulkString(Some(value)))
+}
+
+pub async fn lmove(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 4 {
+        return Err(wrong_args("lmove"));
+    }
+    let from = End::parse(&args[2])?;
+    let to = End::parse(&args[3])?;
+    move_element(&args[0], &args[1], from, to).await
+}
+
+pub async fn rpoplpush(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("rpoplpush"));
+    }
+    move_element(&args[0], &args[1], End::Right, End::Left).await
+}
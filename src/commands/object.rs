@@ -0,0 +1,28 @@
+use bytes::Bytes;
+
+use crate::{resp::RespType, storage};
+
+use super::{upper, wrong_args};
+
+pub async fn object(args: Vec<Bytes>) -> Result<RespType, String> {
+    let subcommand = match args.first() {
+        Some(subcommand) => upper(subcommand),
+        None => return Err(wrong_args("object")),
+    };
+    match subcommand.as_str() {
+        "ENCODING" => {
+            if args.len() != 2 {
+                return Err(wrong_args("object|encoding"));
+            }
+            let db = storage::read().await;
+            let encoding = db
+                .get(&args[1])
+                .map(|item| Bytes::from_static(item.value.encoding().as_bytes()));
+            Ok(RespType::BulkString(encoding))
+        }
+        _ => Err(format!(
+            "ERR unknown subcommand '{}'. Try OBJECT HELP.",
+            String::from_utf8_lossy(&args[0])
+        )),
+    }
+}
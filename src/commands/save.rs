@@ -1,5 +1,51 @@
-use crate::resp::RespType;
+use bytes::Bytes;
 
-pub async fn save() -> Result<RespType, String> {
+use crate::{aof, rdb, resp::RespType};
+
+use super::{upper, wrong_args};
+
+const BGSAVE_IN_PROGRESS: &str = "ERR Background save already in progress";
+
+pub async fn save(args: Vec<Bytes>) -> Result<RespType, String> {
+    if !args.is_empty() {
+        return Err(wrong_args("save"));
+    }
+    match rdb::save().await {
+        None => return Err(BGSAVE_IN_PROGRESS.to_string()),
+        Some(Err(e)) => {
+            println!("{}", e);
+            return Err("ERR".to_string());
+        }
+        Some(Ok(())) => {}
+    }
     Ok(RespType::SimpleString("OK".to_string()))
 }
+
+/// 支持 SCHEDULE 参数，但没有其他后台任务，因此与不带参数相同
+pub async fn bgsave(args: Vec<Bytes>) -> Result<RespType, String> {
+    match args.as_slice() {
+        [] => {}
+        [option] if upper(option) == "SCHEDULE" => {}
+        _ => return Err("ERR syntax error".to_string()),
+    }
+    if !rdb::bgsave().await {
+        return Err(BGSAVE_IN_PROGRESS.to_string());
+    }
+    Ok(RespType::SimpleString("Background saving started".to_string()))
+}
+
+/// 未开启 AOF 时也可以执行，只生成基础文件
+pub async fn bgrewriteaof(args: Vec<Bytes>) -> Result<RespType, String> {
+    if !args.is_empty() {
+        return Err(wrong_args("bgrewriteaof"));
+    }
+    aof::rewrite().await?;
+    Ok(RespType::SimpleString("Background append only file rewriting started".to_string()))
+}
+
+pub async fn lastsave(args: Vec<Bytes>) -> Result<RespType, String> {
+    if !args.is_empty() {
+        return Err(wrong_args("lastsave"));
+    }
+    Ok(RespType::Integer(rdb::lastsave()))
+}
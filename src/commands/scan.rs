@@ -0,0 +1,170 @@
+use bytes::Bytes;
+
+use crate::{glob::string_match, resp::RespType, storage};
+
+use super::{parse_int, score_reply, upper, wrong_args};
+
+/// SCAN 系列命令的公共参数
+struct ScanOptions {
+    cursor: u64,
+    pattern: Option<Bytes>,
+    count: usize,
+    /// 仅 SCAN 支持 TYPE
+    type_name: Option<String>,
+    /// 仅 HSCAN 支持 NOVALUES
+    novalues: bool,
+}
+
+impl ScanOptions {
+    /// args 从游标开始
+    fn parse(args: &[Bytes], command: &str) -> Result<Self, String> {
+        let syntax_error = || "ERR syntax error".to_string();
+        let cursor = std::str::from_utf8(&args[0])
+            .ok()
+            .and_then(|cursor| cursor.parse().ok())
+            .ok_or_else(|| "ERR invalid cursor".to_string())?;
+        let mut options = Self {
+            cursor,
+            pattern: None,
+            count: 10,
+            type_name: None,
+            novalues: false,
+        };
+        let mut i = 1;
+        while i < args.len() {
+            let value = args.get(i + 1);
+            match (upper(&args[i]).as_str(), value) {
+                ("COUNT", Some(value)) => {
+                    let count = parse_int(value)?;
+                    if count < 1 {
+                        return Err(syntax_error());
+                    }
+                    options.count = count as usize;
+                    i += 2;
+                }
+                ("MATCH", Some(value)) => {
+                    // `*` 匹配所有元素，无需过滤
+                    options.pattern = match &value[..] {
+                        b"*" => None,
+                        _ => Some(value.clone()),
+                    };
+                    i += 2;
+                }
+                ("TYPE", Some(value)) if command == "scan" => {
+                    let type_name = String::from_utf8_lossy(value).to_lowercase();
+                    if !matches!(type_name.as_str(), "string" | "list" | "set" | "zset" | "hash" | "stream") {
+                        return Err(format!("ERR unknown type name '{}'", String::from_utf8_lossy(value)));
+                    }
+                    options.type_name = Some(type_name);
+                    i += 2;
+                }
+                ("NOVALUES", _) if command == "hscan" => {
+                    options.novalues = true;
+                    i += 1;
+                }
+                _ => return Err(syntax_error()),
+            }
+        }
+        Ok(options)
+    }
+
+    fn matches(&self, element: &[u8]) -> bool {
+        self.pattern.as_ref().is_none_or(|pattern| string_match(pattern, element, false))
+    }
+}
+
+fn scan_reply(cursor: u64, elements: Vec<RespType>) -> RespType {
+    RespType::Array(Some(vec![
+        RespType::BulkString(Some(Bytes::from(cursor.to_string()))),
+        RespType::Array(Some(elements)),
+    ]))
+}
+
+fn bulk(value: &Bytes) -> RespType {
+    RespType::BulkString(Some(value.clone()))
+}
+
+pub async fn scan(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("scan"));
+    }
+    let options = ScanOptions::parse(&args, "scan")?;
+    let db = storage::read().await;
+    let (cursor, found) = db.scan(options.cursor, options.count);
+    let keys = found
+        .into_iter()
+        .filter(|(key, item)| {
+            !item.is_expired()
+                && options.matches(key)
+                && options
+                    .type_name
+                    .as_ref()
+                    .is_none_or(|type_name| item.value.type_name() == type_name)
+        })
+        .map(|(key, _)| bulk(key))
+        .collect();
+    Ok(scan_reply(cursor, keys))
+}
+
+pub async fn hscan(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("hscan"));
+    }
+    let options = ScanOptions::parse(&args[1..], "hscan")?;
+    let db = storage::read().await;
+    let hash = match db.hash(&args[0])? {
+        Some(hash) => hash,
+        None => return Ok(scan_reply(0, vec![])),
+    };
+    let (cursor, found) = hash.scan_batch(options.cursor, options.count);
+    let mut elements = Vec::new();
+    for (field, value) in found {
+        if options.matches(field) {
+            elements.push(bulk(field));
+            if !options.novalues {
+                elements.push(bulk(value));
+            }
+        }
+    }
+    Ok(scan_reply(cursor, elements))
+}
+
+pub async fn sscan(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("sscan"));
+    }
+    let options = ScanOptions::parse(&args[1..], "sscan")?;
+    let db = storage::read().await;
+    let set = match db.set(&args[0])? {
+        Some(set) => set,
+        None => return Ok(scan_reply(0, vec![])),
+    };
+    let (cursor, found) = set.scan(options.cursor, options.count);
+    let members = found
+        .into_iter()
+        .filter(|member| options.matches(member))
+        .map(|member| RespType::BulkString(Some(member)))
+        .collect();
+    Ok(scan_reply(cursor, members))
+}
+
+pub async fn zscan(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("zscan"));
+    }
+    let options = ScanOptions::parse(&args[1..], "zscan")?;
+    let db = storage::read().await;
+    let zset = match db.zset(&args[0])? {
+        Some(zset) => zset,
+        None => return Ok(scan_reply(0, vec![])),
+    };
+    let (cursor, found) = zset.scan(options.cursor, options.count);
+    let mut elements = Vec::new();
+    for (member, score) in found {
+        if options.matches(&member) {
+            elements.push(RespType::BulkString(Some(member)));
+            elements.push(score_reply(score));
+        }
+    }
+    Ok(scan_reply(cursor, elements))
+}
@@ -1,17 +1,118 @@
-use time::{Duration, OffsetDateTime};
+use bytes::Bytes;
+use time::OffsetDateTime;
 
-use crate::{resp::RespType, storage};
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{self, from_unix_ms, now_ms, Item, Value, WRONGTYPE},
+};
 
-pub async fn set(args: Vec<String>) -> Result<RespType, String> {
-    let mut expires = None;
-    if args.len() == 4  {
-        if &args[2].to_uppercase() == "PX" {
-            expires = Some(OffsetDateTime::now_utc() + Duration::milliseconds(args[3].parse::<i64>().unwrap()));
+use super::{parse_int, upper, wrong_args};
+
+/// 解析 EX/PX/EXAT/PXAT 等过期参数，unit 为每单位的毫秒数，absolute 表示参数是时间戳
+pub(super) fn parse_expire(arg: &[u8], name: &str, unit: i64, absolute: bool) -> Result<OffsetDateTime, String> {
+    let invalid = || format!("ERR invalid expire time in '{}' command", name);
+    let value = parse_int(arg)?;
+    if value <= 0 {
+        return Err(invalid());
+    }
+    let base = if absolute { 0 } else { now_ms() as i64 };
+    let ms = value
+        .checked_mul(unit)
+        .and_then(|ms| ms.checked_add(base))
+        .ok_or_else(invalid)?;
+    Ok(from_unix_ms(ms))
+}
+
+/// SET 的可选参数
+#[derive(Default)]
+struct SetOptions {
+    nx: bool,
+    xx: bool,
+    get: bool,
+    keepttl: bool,
+    expires: Option<OffsetDateTime>,
+}
+
+impl SetOptions {
+    fn parse(args: &[Bytes]) -> Result<Self, String> {
+        let syntax_error = || "ERR syntax error".to_string();
+        let mut options = Self::default();
+        // 已经出现的过期方式，同一命令中只能使用一种
+        let mut expire_option: Option<String> = None;
+        let mut expire = None;
+        let mut i = 0;
+        while i < args.len() {
+            let option = upper(&args[i]);
+            match option.as_str() {
+                "NX" if !options.xx => options.nx = true,
+                "XX" if !options.nx => options.xx = true,
+                "GET" => options.get = true,
+                "KEEPTTL" if expire_option.is_none() => options.keepttl = true,
+                "EX" | "PX" | "EXAT" | "PXAT"
+                    if !options.keepttl && expire_option.as_ref().is_none_or(|prev| *prev == option) =>
+                {
+                    let arg = args.get(i + 1).ok_or_else(syntax_error)?;
+                    let (unit, absolute) = match option.as_str() {
+                        "EX" => (1000, false),
+                        "PX" => (1, false),
+                        "EXAT" => (1000, true),
+                        _ => (1, true),
+                    };
+                    expire = Some((arg, unit, absolute));
+                    expire_option = Some(option);
+                    i += 1;
+                }
+                _ => return Err(syntax_error()),
+            }
+            i += 1;
         }
-        if &args[2].to_uppercase() == "EX" {
-            expires = Some(OffsetDateTime::now_utc() + Duration::seconds(args[3].parse::<i64>().unwrap()));
+        // 与 Redis 一样先检查语法，再解析过期时间
+        if let Some((arg, unit, absolute)) = expire {
+            options.expires = Some(parse_expire(arg, "set", unit, absolute)?);
+        }
+        Ok(options)
+    }
+}
+
+pub async fn set(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("set"));
+    }
+    let options = SetOptions::parse(&args[2..])?;
+
+    let mut db = storage::write().await;
+    let (exists, old_expires, old_value) = match db.get_mut(&args[0]) {
+        Some(item) => {
+            let old_value = match &item.value {
+                Value::String(value) => Some(value.to_bytes()),
+                _ if options.get => return Err(WRONGTYPE.to_string()),
+                _ => None,
+            };
+            (true, item.expires, old_value)
         }
+        None => (false, None, None),
+    };
+    let reply = if options.get {
+        RespType::BulkString(old_value)
+    } else {
+        RespType::SimpleString("OK".to_string())
+    };
+    if (options.nx && exists) || (options.xx && !exists) {
+        return Ok(if options.get { reply } else { RespType::BulkString(None) });
     }
-    storage::set(&args[0], &args[1], expires).await;
-    Ok(RespType::SimpleString("OK".to_string()))
+    let expires = match options.expires {
+        Some(expires) => Some(expires),
+        None if options.keepttl => old_expires,
+        None => None,
+    };
+    db.insert(
+        args[0].clone(),
+        Item {
+            value: Value::String(args[1].clone().into()),
+            expires,
+        },
+    );
+    rdb::add_dirty(1);
+    Ok(reply)
 }
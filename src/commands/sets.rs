@@ -0,0 +1,321 @@
+use bytes::Bytes;
+
+use crate::{
+    random, rdb,
+    resp::RespType,
+    storage::{self, Db, Item, Set, Value},
+};
+
+use super::{parse_int, parse_random_count, upper, wrong_args};
+
+fn members_reply(members: Vec<Bytes>) -> RespType {
+    RespType::Array(Some(
+        members
+            .into_iter()
+            .map(|member| RespType::BulkString(Some(member)))
+            .collect(),
+    ))
+}
+
+pub async fn sadd(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("sadd"));
+    }
+    let mut db = storage::write().await;
+    let set = db.set_or_insert(&args[0])?;
+    let added = args[1..]
+        .iter()
+        .filter(|member| set.insert((*member).clone()))
+        .count();
+    rdb::add_dirty(added as u64);
+    Ok(RespType::Integer(added as i64))
+}
+
+pub async fn srem(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("srem"));
+    }
+    let mut db = storage::write().await;
+    let set = match db.set_mut(&args[0])? {
+        Some(set) => set,
+        None => return Ok(RespType::Integer(0)),
+    };
+    let removed = args[1..].iter().filter(|member| set.remove(member)).count();
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty(removed as u64);
+    Ok(RespType::Integer(removed as i64))
+}
+
+pub async fn smembers(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("smembers"));
+    }
+    let db = storage::read().await;
+    let members = db.set(&args[0])?.map_or_else(Vec::new, |set| set.members());
+    Ok(members_reply(members))
+}
+
+pub async fn sismember(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("sismember"));
+    }
+    let db = storage::read().await;
+    let exists = db.set(&args[0])?.is_some_and(|set| set.contains(&args[1]));
+    Ok(RespType::Integer(exists as i64))
+}
+
+pub async fn smismember(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("smismember"));
+    }
+    let db = storage::read().await;
+    let set = db.set(&args[0])?;
+    let values = args[1..]
+        .iter()
+        .map(|member| RespType::Integer(set.is_some_and(|set| set.contains(member)) as i64))
+        .collect();
+    Ok(RespType::Array(Some(values)))
+}
+
+pub async fn scard(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("scard"));
+    }
+    let db = storage::read().await;
+    let len = db.set(&args[0])?.map_or(0, |set| set.len());
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn smove(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("smove"));
+    }
+    let mut db = storage::write().await;
+    db.set(&args[1])?;
+    let removed = match db.set_mut(&args[0])? {
+        Some(set) => set.remove(&args[2]),
+        None => false,
+    };
+    if !removed {
+        return Ok(RespType::Integer(0));
+    }
+    db.remove_if_empty(&args[0]);
+    db.set_or_insert(&args[1])?.insert(args[2].clone());
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(1))
+}
+
+pub async fn spop(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(wrong_args("spop"));
+    }
+    let count = match args.get(1) {
+        Some(count) => {
+            let count = parse_int(count)?;
+            if count < 0 {
+                return Err("ERR value is out of range, must be positive".to_string());
+            }
+            Some(count as usize)
+        }
+        None => None,
+    };
+    let mut db = storage::write().await;
+    let set = match db.set_mut(&args[0])? {
+        Some(set) => set,
+        None if count.is_some() => return Ok(RespType::Array(Some(vec![]))),
+        None => return Ok(RespType::BulkString(None)),
+    };
+    let (reply, popped) = match count {
+        Some(count) => {
+            let picked = random::sample(set.members(), count);
+            for member in &picked {
+                set.remove(member);
+            }
+            let popped = picked.len();
+            (members_reply(picked), popped)
+        }
+        None => {
+            let member = set.random();
+            if let Some(member) = &member {
+                set.remove(member);
+            }
+            let popped = member.is_some() as usize;
+            (RespType::BulkString(member), popped)
+        }
+    };
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty(popped as u64);
+    Ok(reply)
+}
+
+pub async fn srandmember(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(wrong_args("srandmember"));
+    }
+    let count = args.get(1).map(|count| parse_random_count(count)).transpose()?;
+    let db = storage::read().await;
+    let set = db.set(&args[0])?;
+    let count = match count {
+        Some(count) => count,
+        None => return Ok(RespType::BulkString(set.and_then(|set| set.random()))),
+    };
+    let members = match set {
+        Some(set) => set.members(),
+        None => return Ok(RespType::Array(Some(vec![]))),
+    };
+    let picked = if count >= 0 {
+        random::sample(members, count as usize)
+    } else {
+        // 负数表示允许重复
+        (0..count.unsigned_abs())
+            .map(|_| members[random::below(members.len())].clone())
+            .collect()
+    };
+    Ok(members_reply(picked))
+}
+
+#[derive(Clone, Copy)]
+enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+/// 按需逐个计算交集中的成员，从最小的集合开始检查，sets 不能为空
+fn intersection(mut sets: Vec<&Set>) -> impl Iterator<Item = Bytes> + '_ {
+    sets.sort_by_key(|set| set.len());
+    let first = sets.remove(0);
+    first
+        .iter()
+        .filter(move |member| sets.iter().all(|set| set.contains(member)))
+}
+
+/// 计算多个集合的交集、并集或差集，不存在的键视为空集合
+fn compute(db: &Db, keys: &[Bytes], op: SetOp) -> Result<Vec<Bytes>, String> {
+    let mut sets = Vec::with_capacity(keys.len());
+    for key in keys {
+        sets.push(db.set(key)?);
+    }
+    let result = match op {
+        SetOp::Inter => {
+            if sets.iter().any(|set| set.is_none()) {
+                return Ok(vec![]);
+            }
+            intersection(sets.into_iter().flatten().collect()).collect()
+        }
+        SetOp::Union => {
+            let union: Set = sets
+                .into_iter()
+                .flatten()
+                .flat_map(|set| set.members())
+                .collect();
+            union.members()
+        }
+        SetOp::Diff => match sets[0] {
+            Some(first) => first
+                .members()
+                .into_iter()
+                .filter(|member| {
+                    sets[1..]
+                        .iter()
+                        .flatten()
+                        .all(|set| !set.contains(member))
+                })
+                .collect(),
+            None => vec![],
+        },
+    };
+    Ok(result)
+}
+
+async fn set_op(args: Vec<Bytes>, name: &str, op: SetOp) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args(name));
+    }
+    let db = storage::read().await;
+    Ok(members_reply(compute(&db, &args, op)?))
+}
+
+async fn set_op_store(args: Vec<Bytes>, name: &str, op: SetOp) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args(name));
+    }
+    let mut db = storage::write().await;
+    let result: Set = compute(&db, &args[1..], op)?.into_iter().collect();
+    let len = result.len();
+    let deleted = db.delete(&args[0]);
+    if len > 0 {
+        db.insert(args[0].clone(), Item::new(Value::Set(result)));
+    }
+    if deleted || len > 0 {
+        rdb::add_dirty(1);
+    }
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn sinter(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op(args, "sinter", SetOp::Inter).await
+}
+
+pub async fn sunion(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op(args, "sunion", SetOp::Union).await
+}
+
+pub async fn sdiff(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op(args, "sdiff", SetOp::Diff).await
+}
+
+pub async fn sinterstore(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op_store(args, "sinterstore", SetOp::Inter).await
+}
+
+pub async fn sunionstore(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op_store(args, "sunionstore", SetOp::Union).await
+}
+
+pub async fn sdiffstore(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op_store(args, "sdiffstore", SetOp::Diff).await
+}
+
+pub async fn sintercard(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("sintercard"));
+    }
+    let numkeys = parse_int(&args[0])?;
+    if numkeys <= 0 {
+        return Err("ERR numkeys should be greater than 0".to_string());
+    }
+    let numkeys = numkeys as usize;
+    if numkeys > args.len() - 1 {
+        return Err("ERR Number of keys can't be greater than number of args".to_string());
+    }
+    let keys = &args[1..=numkeys];
+    let mut limit = 0;
+    let mut rest = args[numkeys + 1..].iter();
+    while let Some(option) = rest.next() {
+        match (upper(option).as_str(), rest.next()) {
+            ("LIMIT", Some(value)) => {
+                limit = parse_int(value)?;
+                if limit < 0 {
+                    return Err("ERR LIMIT can't be negative".to_string());
+                }
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+    }
+    let db = storage::read().await;
+    let mut sets = Vec::with_capacity(keys.len());
+    for key in keys {
+        sets.push(db.set(key)?);
+    }
+    if sets.iter().any(|set| set.is_none()) {
+        return Ok(RespType::Integer(0));
+    }
+    // 找到 limit 个成员后立即停止
+    let members = intersection(sets.into_iter().flatten().collect());
+    let len = match limit {
+        0 => members.count(),
+        limit => members.take(limit as usize).count(),
+    };
+    Ok(RespType::Integer(len as i64))
+}
@@ -0,0 +1,390 @@
+use std::{ops::Bound, time::Duration};
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{self, IdSpec, Stream, StreamEntry, StreamId, TrimSpec, TrimStrategy, STREAM_ADDED},
+};
+
+use super::{parse_int, upper, wrong_args};
+
+pub(super) fn invalid_id() -> String {
+    "ERR Invalid stream ID specified as stream command argument".to_string()
+}
+
+pub(super) fn parse_id(arg: &[u8], default_seq: u64) -> Result<StreamId, String> {
+    StreamId::parse(arg, default_seq).ok_or_else(invalid_id)
+}
+
+pub(super) fn id_reply(id: StreamId) -> RespType {
+    RespType::BulkString(Some(id.to_bytes()))
+}
+
+pub(super) fn entry_reply((id, fields): StreamEntry) -> RespType {
+    let fields = fields
+        .into_iter()
+        .map(|field| RespType::BulkString(Some(field)))
+        .collect();
+    RespType::Array(Some(vec![id_reply(id), RespType::Array(Some(fields))]))
+}
+
+pub(super) fn entries_reply(entries: Vec<StreamEntry>) -> RespType {
+    RespType::Array(Some(entries.into_iter().map(entry_reply).collect()))
+}
+
+/// 解析 MAXLEN|MINID [=|~] threshold [LIMIT count]，i 指向 MAXLEN/MINID 之后
+fn parse_trim(args: &[Bytes], i: &mut usize, minid: bool) -> Result<TrimSpec, String> {
+    let syntax_error = || "ERR syntax error".to_string();
+    let mut approx = false;
+    match args.get(*i).map(|arg| &arg[..]) {
+        Some(b"~") => {
+            approx = true;
+            *i += 1;
+        }
+        Some(b"=") => *i += 1,
+        _ => {}
+    }
+    let threshold = args.get(*i).ok_or_else(syntax_error)?;
+    *i += 1;
+    let strategy = if minid {
+        TrimStrategy::MinId(parse_id(threshold, 0)?)
+    } else {
+        let max = parse_int(threshold)?;
+        if max < 0 {
+            return Err("ERR The MAXLEN argument must be >= 0.".to_string());
+        }
+        TrimStrategy::MaxLen(max as usize)
+    };
+    let mut limit = 0;
+    if args.get(*i).is_some_and(|arg| upper(arg) == "LIMIT") {
+        let value = args.get(*i + 1).ok_or_else(syntax_error)?;
+        let value = parse_int(value)?;
+        if value < 0 {
+            return Err("ERR The LIMIT argument must be >= 0.".to_string());
+        }
+        if !approx {
+            return Err("ERR syntax error, LIMIT cannot be used without the special ~ option".to_string());
+        }
+        limit = value as usize;
+        *i += 2;
+    } else if approx {
+        limit = 100 * storage::STREAM_NODE_MAX_ENTRIES;
+    }
+    Ok(TrimSpec {
+        strategy,
+        approx,
+        limit,
+    })
+}
+
+pub async fn xadd(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 4 {
+        return Err(wrong_args("xadd"));
+    }
+    let mut nomkstream = false;
+    let mut trim = None;
+    let mut i = 1;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "NOMKSTREAM" => {
+                nomkstream = true;
+                i += 1;
+            }
+            "MAXLEN" | "MINID" => {
+                let minid = upper(&args[i]) == "MINID";
+                i += 1;
+                trim = Some(parse_trim(&args, &mut i, minid)?);
+            }
+            _ => break,
+        }
+    }
+    let fields = args.get(i + 1..).unwrap_or_default();
+    if fields.is_empty() || fields.len() % 2 == 1 {
+        return Err(wrong_args("xadd"));
+    }
+    let spec = match &args[i][..] {
+        b"*" => IdSpec::Auto,
+        id => match id.strip_suffix(b"-*") {
+            Some(ms) => {
+                let ms = std::str::from_utf8(ms).ok().and_then(|ms| ms.parse().ok());
+                IdSpec::AutoSeq(ms.ok_or_else(invalid_id)?)
+            }
+            None => IdSpec::Explicit(parse_id(id, 0)?),
+        },
+    };
+
+    let mut db = storage::write().await;
+    if nomkstream && db.stream(&args[0])?.is_none() {
+        return Ok(RespType::BulkString(None));
+    }
+    let id = match db.stream(&args[0])? {
+        Some(stream) => stream.next_id(spec)?,
+        None => Stream::default().next_id(spec)?,
+    };
+    let stream = db.stream_or_insert(&args[0])?;
+    stream.add(id, fields.to_vec());
+    if let Some(trim) = trim {
+        stream.trim(trim);
+    }
+    drop(db);
+    rdb::add_dirty(1);
+    STREAM_ADDED.notify_waiters();
+    Ok(id_reply(id))
+}
+
+pub async fn xlen(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("xlen"));
+    }
+    let db = storage::read().await;
+    let len = db.stream(&args[0])?.map_or(0, |stream| stream.len());
+    Ok(RespType::Integer(len as i64))
+}
+
+/// 解析 XRANGE 的区间端点，`-`/`+` 表示最小/最大，`(` 前缀表示开区间
+pub(super) fn parse_bound(arg: &[u8], default_seq: u64) -> Result<Bound<StreamId>, String> {
+    match arg {
+        b"-" | b"+" => Ok(Bound::Unbounded),
+        _ => match arg.strip_prefix(b"(") {
+            Some(id) => Ok(Bound::Excluded(parse_id(id, default_seq)?)),
+            None => Ok(Bound::Included(parse_id(arg, default_seq)?)),
+        },
+    }
+}
+
+async fn range(args: Vec<Bytes>, name: &str, rev: bool) -> Result<RespType, String> {
+    if args.len() != 3 && args.len() != 5 {
+        return Err(wrong_args(name));
+    }
+    let (start, end) = if rev { (&args[2], &args[1]) } else { (&args[1], &args[2]) };
+    let start = parse_bound(start, 0)?;
+    let end = parse_bound(end, u64::MAX)?;
+    let count = match args.get(3) {
+        Some(option) if upper(option) == "COUNT" => {
+            let count = parse_int(&args[4])?;
+            if count <= 0 {
+                return Ok(RespType::Array(Some(vec![])));
+            }
+            Some(count as usize)
+        }
+        Some(_) => return Err("ERR syntax error".to_string()),
+        None => None,
+    };
+    let db = storage::read().await;
+    let entries = db
+        .stream(&args[0])?
+        .map_or_else(Vec::new, |stream| stream.range(start, end, rev, count));
+    Ok(entries_reply(entries))
+}
+
+pub async fn xrange(args: Vec<Bytes>) -> Result<RespType, String> {
+    range(args, "xrange", false).await
+}
+
+pub async fn xrevrange(args: Vec<Bytes>) -> Result<RespType, String> {
+    range(args, "xrevrange", true).await
+}
+
+pub async fn xdel(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("xdel"));
+    }
+    let mut ids = Vec::with_capacity(args.len() - 1);
+    for arg in &args[1..] {
+        ids.push(parse_id(arg, 0)?);
+    }
+    let mut db = storage::write().await;
+    let deleted = match db.stream_mut(&args[0])? {
+        Some(stream) => ids.iter().filter(|id| stream.delete(id)).count(),
+        None => 0,
+    };
+    rdb::add_dirty(deleted as u64);
+    Ok(RespType::Integer(deleted as i64))
+}
+
+pub async fn xtrim(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 3 {
+        return Err(wrong_args("xtrim"));
+    }
+    let minid = match upper(&args[1]).as_str() {
+        "MAXLEN" => false,
+        "MINID" => true,
+        _ => return Err("ERR syntax error".to_string()),
+    };
+    let mut i = 2;
+    let trim = parse_trim(&args, &mut i, minid)?;
+    if i != args.len() {
+        return Err("ERR syntax error".to_string());
+    }
+    let mut db = storage::write().await;
+    let removed = match db.stream_mut(&args[0])? {
+        Some(stream) => stream.trim(trim),
+        None => 0,
+    };
+    rdb::add_dirty(removed as u64);
+    Ok(RespType::Integer(removed as i64))
+}
+
+/// XSETID key last-id [ENTRIESADDED entries-added] [MAXDELETEDID max-deleted-id]
+pub async fn xsetid(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("xsetid"));
+    }
+    let id = parse_id(&args[1], 0)?;
+    let mut entries_added = None;
+    let mut max_deleted_id = None;
+    let mut i = 2;
+    while i < args.len() {
+        match (upper(&args[i]).as_str(), args.get(i + 1)) {
+            ("ENTRIESADDED", Some(value)) => {
+                let value = parse_int(value)?;
+                if value < 0 {
+                    return Err("ERR entries_added must be positive".to_string());
+                }
+                entries_added = Some(value as u64);
+            }
+            ("MAXDELETEDID", Some(value)) => {
+                let value = parse_id(value, 0)?;
+                if id < value {
+                    return Err(
+                        "ERR The ID specified in XSETID is smaller than the provided max_deleted_entry_id".to_string(),
+                    );
+                }
+                max_deleted_id = Some(value);
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+        i += 2;
+    }
+
+    let mut db = storage::write().await;
+    let stream = db.stream_mut(&args[0])?.ok_or_else(|| "ERR no such key".to_string())?;
+    if let Some((top, _)) = stream.last_entry() {
+        if id < top {
+            return Err("ERR The ID specified in XSETID is smaller than the target stream top item".to_string());
+        }
+        if entries_added.is_some_and(|entries_added| entries_added < stream.len() as u64) {
+            return Err(
+                "ERR The entries_added specified in XSETID is smaller than the target stream length".to_string(),
+            );
+        }
+    }
+    stream.last_id = id;
+    if let Some(entries_added) = entries_added {
+        stream.entries_added = entries_added;
+    }
+    if let Some(max_deleted_id) = max_deleted_id {
+        stream.max_deleted_id = max_deleted_id;
+    }
+    rdb::add_dirty(1);
+    Ok(RespType::SimpleString("OK".to_string()))
+}
+
+/// 解析阻塞超时，返回截止时间，None 表示永久阻塞
+pub(super) fn parse_block(arg: &[u8]) -> Result<Option<Instant>, String> {
+    let timeout = parse_int(arg).map_err(|_| "ERR timeout is not an integer or out of range".to_string())?;
+    if timeout < 0 {
+        return Err("ERR timeout is negative".to_string());
+    }
+    Ok(match timeout {
+        0 => None,
+        ms => Some(Instant::now() + Duration::from_millis(ms as u64)),
+    })
+}
+
+/// 等待新条目写入，超时返回 false
+pub async fn wait_for_entries(deadline: Option<Instant>, notified: tokio::sync::futures::Notified<'_>) -> bool {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, notified).await.is_ok(),
+        None => {
+            notified.await;
+            true
+        }
+    }
+}
+
+pub async fn xread(args: Vec<Bytes>) -> Result<RespType, String> {
+    let mut count = None;
+    let mut block = None;
+    let mut i = 0;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "COUNT" if i + 1 < args.len() => {
+                let value = parse_int(&args[i + 1])?;
+                count = if value > 0 { Some(value as usize) } else { None };
+                i += 2;
+            }
+            "BLOCK" if i + 1 < args.len() => {
+                block = Some(parse_block(&args[i + 1])?);
+                i += 2;
+            }
+            "STREAMS" => {
+                i += 1;
+                break;
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+    }
+    let streams = args.get(i..).unwrap_or_default();
+    if streams.is_empty() {
+        return Err(wrong_args("xread"));
+    }
+    if streams.len() % 2 == 1 {
+        return Err(
+            "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified."
+                .to_string(),
+        );
+    }
+    let (keys, ids) = streams.split_at(streams.len() / 2);
+
+    // 解析起始 ID，`$` 表示只读取之后新写入的条目，`+` 表示最后一个条目
+    let mut after = Vec::with_capacity(keys.len());
+    {
+        let db = storage::read().await;
+        for (key, id) in keys.iter().zip(ids) {
+            let stream = db.stream(key)?;
+            let start = match &id[..] {
+                b"$" => stream.map_or(StreamId::MIN, |stream| stream.last_id),
+                b"+" => match stream.and_then(|stream| stream.last_entry()) {
+                    Some((last, _)) => last.prev().unwrap_or(StreamId::MIN),
+                    None => StreamId::MIN,
+                },
+                _ => parse_id(id, 0)?,
+            };
+            after.push(start);
+        }
+    }
+
+    loop {
+        let notified = STREAM_ADDED.notified();
+        let mut reply = Vec::new();
+        {
+            let db = storage::read().await;
+            for (key, start) in keys.iter().zip(&after) {
+                let entries = match db.stream(key)? {
+                    Some(stream) => stream.range(Bound::Excluded(*start), Bound::Unbounded, false, count),
+                    None => continue,
+                };
+                if !entries.is_empty() {
+                    reply.push(RespType::Array(Some(vec![
+                        RespType::BulkString(Some(key.clone())),
+                        entries_reply(entries),
+                    ])));
+                }
+            }
+        }
+        if !reply.is_empty() {
+            return Ok(RespType::Array(Some(reply)));
+        }
+        let deadline = match block {
+            Some(deadline) => deadline,
+            None => return Ok(RespType::Array(None)),
+        };
+        if !wait_for_entries(deadline, notified).await {
+            return Ok(RespType::Array(None));
+        }
+    }
+}
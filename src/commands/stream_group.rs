@@ -0,0 +1,719 @@
+use std::ops::Bound;
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{self, now_ms, ClaimOptions, ConsumerGroup, Stream, StreamEntry, StreamId, STREAM_ADDED},
+};
+
+use super::{
+    entries_reply, entry_reply, id_reply, invalid_id, parse_block, parse_bound, parse_id, parse_int, upper,
+    wait_for_entries, wrong_args,
+};
+
+fn bulk(value: impl Into<Bytes>) -> RespType {
+    RespType::BulkString(Some(value.into()))
+}
+
+fn nil() -> RespType {
+    RespType::BulkString(None)
+}
+
+fn optional_int(value: Option<u64>) -> RespType {
+    value.map_or_else(nil, |value| RespType::Integer(value as i64))
+}
+
+fn no_group(key: &[u8], group: &[u8]) -> String {
+    format!(
+        "NOGROUP No such consumer group '{}' for key name '{}'",
+        String::from_utf8_lossy(group),
+        String::from_utf8_lossy(key)
+    )
+}
+
+fn no_key_or_group(key: &[u8], group: &[u8]) -> String {
+    format!(
+        "NOGROUP No such key '{}' or consumer group '{}'",
+        String::from_utf8_lossy(key),
+        String::from_utf8_lossy(group)
+    )
+}
+
+fn ids_reply(ids: Vec<StreamId>) -> RespType {
+    RespType::Array(Some(ids.into_iter().map(id_reply).collect()))
+}
+
+/// 解析 ENTRIESREAD，-1 表示未知
+fn parse_entries_read(arg: &[u8]) -> Result<Option<u64>, String> {
+    match parse_int(arg)? {
+        -1 => Ok(None),
+        value if value >= 0 => Ok(Some(value as u64)),
+        _ => Err("ERR value for ENTRIESREAD must be positive or -1".to_string()),
+    }
+}
+
+/// 解析 XGROUP CREATE/SETID 的 ID，`$` 表示流的最后一个 ID
+fn parse_group_id(arg: &[u8], stream: Option<&Stream>) -> Result<StreamId, String> {
+    match arg {
+        b"$" => Ok(stream.map_or(StreamId::MIN, |stream| stream.last_id)),
+        _ => parse_id(arg, 0),
+    }
+}
+
+pub async fn xgroup(args: Vec<Bytes>) -> Result<RespType, String> {
+    let subcommand = match args.first() {
+        Some(subcommand) => upper(subcommand),
+        None => return Err(wrong_args("xgroup")),
+    };
+    let (min, max) = match subcommand.as_str() {
+        "CREATE" => (4, 7),
+        "SETID" => (4, 6),
+        "DESTROY" => (3, 3),
+        "CREATECONSUMER" | "DELCONSUMER" => (4, 4),
+        _ => {
+            return Err(format!(
+                "ERR unknown subcommand '{}'. Try XGROUP HELP.",
+                String::from_utf8_lossy(&args[0])
+            ))
+        }
+    };
+    if args.len() < min || args.len() > max {
+        return Err(wrong_args(&format!("xgroup|{}", subcommand.to_lowercase())));
+    }
+    let (key, group) = (&args[1], &args[2]);
+
+    let mut mkstream = false;
+    let mut entries_read = None;
+    if subcommand == "CREATE" || subcommand == "SETID" {
+        let mut i = 4;
+        while i < args.len() {
+            match upper(&args[i]).as_str() {
+                "MKSTREAM" if subcommand == "CREATE" => {
+                    mkstream = true;
+                    i += 1;
+                }
+                "ENTRIESREAD" if i + 1 < args.len() => {
+                    entries_read = parse_entries_read(&args[i + 1])?;
+                    i += 2;
+                }
+                _ => return Err("ERR syntax error".to_string()),
+            }
+        }
+    }
+
+    let mut db = storage::write().await;
+    let existing = db.stream(key)?;
+    if existing.is_none() && !mkstream {
+        return Err("ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.".to_string());
+    }
+    let id = match subcommand.as_str() {
+        "CREATE" | "SETID" => parse_group_id(&args[3], existing)?,
+        _ => StreamId::MIN,
+    };
+    let stream = db.stream_or_insert(key)?;
+    match subcommand.as_str() {
+        "CREATE" => {
+            if !stream.create_group(group.clone(), id, entries_read) {
+                return Err("BUSYGROUP Consumer Group name already exists".to_string());
+            }
+            rdb::add_dirty(1);
+            Ok(RespType::SimpleString("OK".to_string()))
+        }
+        "SETID" => {
+            if !stream.set_group_id(group, id, entries_read) {
+                return Err(no_group(key, group));
+            }
+            rdb::add_dirty(1);
+            Ok(RespType::SimpleString("OK".to_string()))
+        }
+        "DESTROY" => {
+            let destroyed = stream.groups.remove(group).is_some();
+            rdb::add_dirty(destroyed as u64);
+            Ok(RespType::Integer(destroyed as i64))
+        }
+        "CREATECONSUMER" => {
+            let group = stream.groups.get_mut(group).ok_or_else(|| no_group(key, group))?;
+            let created = group.create_consumer(&args[3], now_ms());
+            rdb::add_dirty(created as u64);
+            Ok(RespType::Integer(created as i64))
+        }
+        _ => {
+            let group = stream.groups.get_mut(group).ok_or_else(|| no_group(key, group))?;
+            let deleted = group.delete_consumer(&args[3]);
+            rdb::add_dirty(deleted.is_some() as u64);
+            Ok(RespType::Integer(deleted.unwrap_or(0) as i64))
+        }
+    }
+}
+
+/// XREADGROUP 中每个流的起始位置
+enum ReadFrom {
+    /// `>`，读取从未投递过的条目
+    New,
+    /// 读取该消费者在此 ID 之后的待确认条目
+    History(StreamId),
+}
+
+/// 去掉 XREADGROUP 的 BLOCK 选项，返回其余参数和阻塞的截止时间（None 表示永久阻塞）。
+/// 没有 BLOCK 或者超时参数无效时返回 None，由 xreadgroup 报告错误
+pub fn split_block(args: &[Bytes]) -> Option<(Vec<Bytes>, Option<Instant>)> {
+    let mut rest = Vec::with_capacity(args.len());
+    let mut deadline = None;
+    let mut i = 0;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "GROUP" => {
+                rest.extend_from_slice(&args[i..(i + 3).min(args.len())]);
+                i += 3;
+            }
+            "BLOCK" => {
+                deadline = Some(parse_block(args.get(i + 1)?).ok()?);
+                i += 2;
+            }
+            "STREAMS" => {
+                rest.extend_from_slice(&args[i..]);
+                break;
+            }
+            _ => {
+                rest.push(args[i].clone());
+                i += 1;
+            }
+        }
+    }
+    deadline.map(|deadline| (rest, deadline))
+}
+
+pub async fn xreadgroup(args: Vec<Bytes>) -> Result<RespType, String> {
+    let mut group = None;
+    let mut count = None;
+    let mut block = None;
+    let mut noack = false;
+    let mut i = 0;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "GROUP" if i + 2 < args.len() => {
+                group = Some((args[i + 1].clone(), args[i + 2].clone()));
+                i += 3;
+            }
+            "COUNT" if i + 1 < args.len() => {
+                let value = parse_int(&args[i + 1])?;
+                count = if value > 0 { Some(value as usize) } else { None };
+                i += 2;
+            }
+            "BLOCK" if i + 1 < args.len() => {
+                block = Some(parse_block(&args[i + 1])?);
+                i += 2;
+            }
+            "NOACK" => {
+                noack = true;
+                i += 1;
+            }
+            "STREAMS" => {
+                i += 1;
+                break;
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+    }
+    let streams = args.get(i..).unwrap_or_default();
+    if streams.is_empty() {
+        return Err(wrong_args("xreadgroup"));
+    }
+    if streams.len() % 2 == 1 {
+        return Err(
+            "ERR Unbalanced 'xreadgroup' list of streams: for each stream key an ID or '>' must be specified."
+                .to_string(),
+        );
+    }
+    let (group, consumer) = group.ok_or_else(|| "ERR Missing GROUP option for XREADGROUP".to_string())?;
+    let (keys, ids) = streams.split_at(streams.len() / 2);
+    let mut from = Vec::with_capacity(ids.len());
+    for id in ids {
+        from.push(match &id[..] {
+            b">" => ReadFrom::New,
+            b"$" => return Err("ERR The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just return an empty result set.".to_string()),
+            _ => ReadFrom::History(parse_id(id, 0)?),
+        });
+    }
+    let no_group = |key: &[u8]| {
+        format!(
+            "NOGROUP No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+            String::from_utf8_lossy(key),
+            String::from_utf8_lossy(&group)
+        )
+    };
+    // 读取历史时总会立即返回，只有全部为 `>` 时才可能阻塞
+    let history = from.iter().any(|from| matches!(from, ReadFrom::History(_)));
+
+    loop {
+        let notified = STREAM_ADDED.notified();
+        let mut reply = Vec::new();
+        {
+            let mut db = storage::write().await;
+            for key in keys {
+                if !db.stream(key)?.is_some_and(|stream| stream.groups.contains_key(&group)) {
+                    return Err(no_group(key));
+                }
+            }
+            let now = now_ms();
+            for (key, from) in keys.iter().zip(&from) {
+                let stream = db.stream_mut(key)?.ok_or_else(|| no_group(key))?;
+                let entries = match from {
+                    ReadFrom::New => {
+                        let entries = stream
+                            .read_new(&group, &consumer, count, noack, now)
+                            .ok_or_else(|| no_group(key))?;
+                        if entries.is_empty() {
+                            continue;
+                        }
+                        // NOACK 只推进组的 last_id，否则每个投递的条目都新增一个待确认条目
+                        rdb::add_dirty(if noack { 1 } else { entries.len() as u64 });
+                        entries_reply(entries)
+                    }
+                    ReadFrom::History(after) => {
+                        let entries = stream
+                            .read_pending(&group, &consumer, *after, count, now)
+                            .ok_or_else(|| no_group(key))?;
+                        let entries = entries
+                            .into_iter()
+                            .map(|(id, fields)| match fields {
+                                Some(fields) => entry_reply((id, fields)),
+                                None => RespType::Array(Some(vec![id_reply(id), RespType::Array(None)])),
+                            })
+                            .collect();
+                        RespType::Array(Some(entries))
+                    }
+                };
+                reply.push(RespType::Array(Some(vec![bulk(key.clone()), entries])));
+            }
+        }
+        if !reply.is_empty() || history {
+            return Ok(RespType::Array(Some(reply)));
+        }
+        let deadline = match block {
+            Some(deadline) => deadline,
+            None => return Ok(RespType::Array(None)),
+        };
+        if !wait_for_entries(deadline, notified).await {
+            return Ok(RespType::Array(None));
+        }
+    }
+}
+
+pub async fn xack(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 3 {
+        return Err(wrong_args("xack"));
+    }
+    let mut ids = Vec::with_capacity(args.len() - 2);
+    for arg in &args[2..] {
+        ids.push(parse_id(arg, 0)?);
+    }
+    let mut db = storage::write().await;
+    let group = match db.stream_mut(&args[0])? {
+        Some(stream) => stream.groups.get_mut(&args[1]),
+        None => None,
+    };
+    let acked = match group {
+        Some(group) => ids.iter().filter(|id| group.ack(id)).count(),
+        None => 0,
+    };
+    Ok(RespType::Integer(acked as i64))
+}
+
+/// XPENDING 的概要形式
+fn pending_summary(group: &ConsumerGroup) -> RespType {
+    let (min, max) = match (group.pel.keys().next(), group.pel.keys().next_back()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => {
+            return RespType::Array(Some(vec![RespType::Integer(0), nil(), nil(), RespType::Array(None)]));
+        }
+    };
+    let consumers = group
+        .consumers
+        .iter()
+        .filter(|(_, consumer)| !consumer.pending.is_empty())
+        .map(|(name, consumer)| {
+            RespType::Array(Some(vec![
+                bulk(name.clone()),
+                bulk(consumer.pending.len().to_string()),
+            ]))
+        })
+        .collect();
+    RespType::Array(Some(vec![
+        RespType::Integer(group.pel.len() as i64),
+        id_reply(min),
+        id_reply(max),
+        RespType::Array(Some(consumers)),
+    ]))
+}
+
+pub async fn xpending(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("xpending"));
+    }
+    let (key, group) = (&args[0], &args[1]);
+    let mut min_idle = 0;
+    let mut i = 2;
+    if args.get(i).is_some_and(|arg| upper(arg) == "IDLE") {
+        let value = args.get(i + 1).ok_or_else(|| "ERR syntax error".to_string())?;
+        min_idle = parse_int(value)?.max(0) as u64;
+        i += 2;
+    }
+    let extended = args.len() > 2;
+    let rest = &args[i..];
+    if extended && rest.len() != 3 && rest.len() != 4 {
+        return Err("ERR syntax error".to_string());
+    }
+    let range = if extended {
+        let start = parse_bound(&rest[0], 0)?;
+        let end = parse_bound(&rest[1], u64::MAX)?;
+        let count = parse_int(&rest[2])?.max(0) as usize;
+        Some((start, end, count, rest.get(3)))
+    } else {
+        None
+    };
+
+    let db = storage::read().await;
+    let group = db
+        .stream(key)?
+        .and_then(|stream| stream.groups.get(group))
+        .ok_or_else(|| no_key_or_group(key, group))?;
+    let (start, end, count, consumer) = match range {
+        Some(range) => range,
+        None => return Ok(pending_summary(group)),
+    };
+    let empty = match (start, end) {
+        (Bound::Included(s), Bound::Included(e)) => s > e,
+        (Bound::Included(s) | Bound::Excluded(s), Bound::Excluded(e)) | (Bound::Excluded(s), Bound::Included(e)) => {
+            s >= e
+        }
+        _ => false,
+    };
+    if empty || count == 0 {
+        return Ok(RespType::Array(Some(vec![])));
+    }
+    let now = now_ms();
+    let entries = group
+        .pel
+        .range((start, end))
+        .filter(|(_, pending)| consumer.is_none_or(|consumer| pending.consumer == consumer))
+        .filter(|(_, pending)| now.saturating_sub(pending.delivery_time) >= min_idle)
+        .take(count)
+        .map(|(id, pending)| {
+            RespType::Array(Some(vec![
+                id_reply(*id),
+                bulk(pending.consumer.clone()),
+                RespType::Integer(now.saturating_sub(pending.delivery_time) as i64),
+                RespType::Integer(pending.delivery_count as i64),
+            ]))
+        })
+        .collect();
+    Ok(RespType::Array(Some(entries)))
+}
+
+pub async fn xclaim(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 5 {
+        return Err(wrong_args("xclaim"));
+    }
+    let (key, group, consumer) = (&args[0], &args[1], &args[2]);
+    let min_idle = parse_int(&args[3]).map_err(|_| "ERR Invalid min-idle-time argument for XCLAIM".to_string())?;
+    let now = now_ms();
+    let mut options = ClaimOptions {
+        min_idle: min_idle.max(0) as u64,
+        ..Default::default()
+    };
+    // ID 列表一直持续到第一个无法解析为 ID 的参数
+    let mut ids = Vec::new();
+    let mut i = 4;
+    while let Some(id) = args.get(i).and_then(|arg| StreamId::parse(arg, 0)) {
+        ids.push(id);
+        i += 1;
+    }
+    while i < args.len() {
+        let option = upper(&args[i]);
+        let value = args.get(i + 1);
+        match (option.as_str(), value) {
+            ("FORCE", _) => options.force = true,
+            ("JUSTID", _) => options.justid = true,
+            ("IDLE", Some(value)) => {
+                let idle = parse_int(value).map_err(|_| "ERR Invalid IDLE option argument for XCLAIM".to_string())?;
+                options.delivery_time = Some(now.saturating_sub(idle.max(0) as u64));
+                i += 1;
+            }
+            ("TIME", Some(value)) => {
+                let time = parse_int(value).map_err(|_| "ERR Invalid TIME option argument for XCLAIM".to_string())?;
+                options.delivery_time = Some(time.clamp(0, now as i64) as u64);
+                i += 1;
+            }
+            ("RETRYCOUNT", Some(value)) => {
+                let count =
+                    parse_int(value).map_err(|_| "ERR Invalid RETRYCOUNT option argument for XCLAIM".to_string())?;
+                options.retry_count = Some(count.max(0) as u64);
+                i += 1;
+            }
+            ("LASTID", Some(value)) => {
+                options.last_id = Some(parse_id(value, 0)?);
+                i += 1;
+            }
+            _ => {
+                return Err(format!(
+                    "ERR Unrecognized XCLAIM option '{}'",
+                    String::from_utf8_lossy(&args[i])
+                ))
+            }
+        }
+        i += 1;
+    }
+
+    let mut db = storage::write().await;
+    let stream = db.stream_mut(key)?.ok_or_else(|| no_key_or_group(key, group))?;
+    let last_id = stream.groups.get(group).map(|group| group.last_id);
+    let (claimed, deleted) = stream
+        .claim(group, consumer, &ids, &options, now)
+        .ok_or_else(|| no_key_or_group(key, group))?;
+    let advanced = last_id.is_some_and(|last_id| options.last_id.is_some_and(|id| id > last_id));
+    rdb::add_dirty((claimed.len() + deleted) as u64 + advanced as u64);
+    if options.justid {
+        Ok(ids_reply(claimed.into_iter().map(|(id, _)| id).collect()))
+    } else {
+        Ok(entries_reply(claimed))
+    }
+}
+
+pub async fn xautoclaim(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 5 {
+        return Err(wrong_args("xautoclaim"));
+    }
+    let (key, group, consumer) = (&args[0], &args[1], &args[2]);
+    let min_idle =
+        parse_int(&args[3]).map_err(|_| "ERR Invalid min-idle-time argument for XAUTOCLAIM".to_string())?;
+    let start = match parse_bound(&args[4], 0)? {
+        Bound::Included(id) => id,
+        Bound::Excluded(id) => id.next().ok_or_else(invalid_id)?,
+        Bound::Unbounded => StreamId::MIN,
+    };
+    let mut count = 100;
+    let mut options = ClaimOptions {
+        min_idle: min_idle.max(0) as u64,
+        ..Default::default()
+    };
+    let mut i = 5;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "COUNT" if i + 1 < args.len() => {
+                let value = parse_int(&args[i + 1])?;
+                if !(1..=i64::MAX / 10).contains(&value) {
+                    return Err("ERR COUNT must be > 0".to_string());
+                }
+                count = value as usize;
+                i += 2;
+            }
+            "JUSTID" => {
+                options.justid = true;
+                i += 1;
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+    }
+
+    let mut db = storage::write().await;
+    let now = now_ms();
+    let (next, claimed, deleted) = db
+        .stream_mut(key)?
+        .and_then(|stream| stream.auto_claim(group, consumer, start, count, &options, now))
+        .ok_or_else(|| no_key_or_group(key, group))?;
+    rdb::add_dirty((claimed.len() + deleted.len()) as u64);
+    let claimed = if options.justid {
+        ids_reply(claimed.into_iter().map(|(id, _)| id).collect())
+    } else {
+        entries_reply(claimed)
+    };
+    Ok(RespType::Array(Some(vec![id_reply(next), claimed, ids_reply(deleted)])))
+}
+
+/// 将字段名和值交替排列为数组
+fn fields_reply(fields: Vec<(&str, RespType)>) -> RespType {
+    RespType::Array(Some(
+        fields
+            .into_iter()
+            .flat_map(|(name, value)| [bulk(name.to_string()), value])
+            .collect(),
+    ))
+}
+
+fn optional_entry_reply(entry: Option<StreamEntry>) -> RespType {
+    entry.map_or_else(nil, entry_reply)
+}
+
+/// XINFO STREAM 中与基数树相关的字段，按每个节点 STREAM_NODE_MAX_ENTRIES 个条目估算
+fn radix_tree_fields(stream: &Stream) -> Vec<(&'static str, RespType)> {
+    let keys = stream.len().div_ceil(storage::STREAM_NODE_MAX_ENTRIES);
+    vec![
+        ("radix-tree-keys", RespType::Integer(keys as i64)),
+        ("radix-tree-nodes", RespType::Integer(keys as i64 + 1)),
+    ]
+}
+
+fn stream_info(stream: &Stream, full: Option<usize>) -> RespType {
+    let mut fields = vec![("length", RespType::Integer(stream.len() as i64))];
+    fields.extend(radix_tree_fields(stream));
+    fields.extend([
+        ("last-generated-id", id_reply(stream.last_id)),
+        ("max-deleted-entry-id", id_reply(stream.max_deleted_id)),
+        ("entries-added", RespType::Integer(stream.entries_added as i64)),
+        ("recorded-first-entry-id", id_reply(stream.first_id())),
+    ]);
+    let count = match full {
+        Some(count) => count,
+        None => {
+            fields.extend([
+                ("groups", RespType::Integer(stream.groups.len() as i64)),
+                ("first-entry", optional_entry_reply(stream.first_entry())),
+                ("last-entry", optional_entry_reply(stream.last_entry())),
+            ]);
+            return fields_reply(fields);
+        }
+    };
+    let count = if count == 0 { None } else { Some(count) };
+    let entries = stream.range(Bound::Unbounded, Bound::Unbounded, false, count);
+    let groups = stream
+        .groups
+        .iter()
+        .map(|(name, group)| {
+            let pel = group
+                .pel
+                .iter()
+                .take(count.unwrap_or(usize::MAX))
+                .map(|(id, pending)| {
+                    RespType::Array(Some(vec![
+                        id_reply(*id),
+                        bulk(pending.consumer.clone()),
+                        RespType::Integer(pending.delivery_time as i64),
+                        RespType::Integer(pending.delivery_count as i64),
+                    ]))
+                })
+                .collect();
+            let consumers = group
+                .consumers
+                .iter()
+                .map(|(name, consumer)| {
+                    let pel = consumer
+                        .pending
+                        .iter()
+                        .take(count.unwrap_or(usize::MAX))
+                        .filter_map(|id| group.pel.get(id).map(|pending| (id, pending)))
+                        .map(|(id, pending)| {
+                            RespType::Array(Some(vec![
+                                id_reply(*id),
+                                RespType::Integer(pending.delivery_time as i64),
+                                RespType::Integer(pending.delivery_count as i64),
+                            ]))
+                        })
+                        .collect();
+                    fields_reply(vec![
+                        ("name", bulk(name.clone())),
+                        ("seen-time", RespType::Integer(consumer.seen_time as i64)),
+                        ("active-time", consumer.active_time.map_or(RespType::Integer(-1), |time| RespType::Integer(time as i64))),
+                        ("pel-count", RespType::Integer(consumer.pending.len() as i64)),
+                        ("pending", RespType::Array(Some(pel))),
+                    ])
+                })
+                .collect();
+            fields_reply(vec![
+                ("name", bulk(name.clone())),
+                ("last-delivered-id", id_reply(group.last_id)),
+                ("entries-read", optional_int(group.entries_read)),
+                ("lag", optional_int(stream.group_lag(group))),
+                ("pel-count", RespType::Integer(group.pel.len() as i64)),
+                ("pending", RespType::Array(Some(pel))),
+                ("consumers", RespType::Array(Some(consumers))),
+            ])
+        })
+        .collect();
+    fields.extend([
+        ("entries", entries_reply(entries)),
+        ("groups", RespType::Array(Some(groups))),
+    ]);
+    fields_reply(fields)
+}
+
+pub async fn xinfo(args: Vec<Bytes>) -> Result<RespType, String> {
+    let subcommand = match args.first() {
+        Some(subcommand) => upper(subcommand),
+        None => return Err(wrong_args("xinfo")),
+    };
+    let (min, max) = match subcommand.as_str() {
+        "STREAM" => (2, 5),
+        "GROUPS" => (2, 2),
+        "CONSUMERS" => (3, 3),
+        _ => {
+            return Err(format!(
+                "ERR unknown subcommand '{}'. Try XINFO HELP.",
+                String::from_utf8_lossy(&args[0])
+            ))
+        }
+    };
+    if args.len() < min || args.len() > max {
+        return Err(wrong_args(&format!("xinfo|{}", subcommand.to_lowercase())));
+    }
+    let key = &args[1];
+
+    let full = if subcommand == "STREAM" && args.len() > 2 {
+        if upper(&args[2]) != "FULL" {
+            return Err("ERR syntax error".to_string());
+        }
+        match args.get(3..) {
+            Some([option, count]) if upper(option) == "COUNT" => Some(parse_int(count)?.max(0) as usize),
+            Some([]) => Some(10),
+            _ => return Err("ERR syntax error".to_string()),
+        }
+    } else {
+        None
+    };
+
+    let db = storage::read().await;
+    let stream = db.stream(key)?.ok_or_else(|| "ERR no such key".to_string())?;
+    let now = now_ms();
+    match subcommand.as_str() {
+        "STREAM" => Ok(stream_info(stream, full)),
+        "GROUPS" => {
+            let groups = stream
+                .groups
+                .iter()
+                .map(|(name, group)| {
+                    fields_reply(vec![
+                        ("name", bulk(name.clone())),
+                        ("consumers", RespType::Integer(group.consumers.len() as i64)),
+                        ("pending", RespType::Integer(group.pel.len() as i64)),
+                        ("last-delivered-id", id_reply(group.last_id)),
+                        ("entries-read", optional_int(group.entries_read)),
+                        ("lag", optional_int(stream.group_lag(group))),
+                    ])
+                })
+                .collect();
+            Ok(RespType::Array(Some(groups)))
+        }
+        _ => {
+            let group = stream.groups.get(&args[2]).ok_or_else(|| no_group(key, &args[2]))?;
+            let consumers = group
+                .consumers
+                .iter()
+                .map(|(name, consumer)| {
+                    let inactive = consumer
+                        .active_time
+                        .map_or(-1, |time| now.saturating_sub(time) as i64);
+                    fields_reply(vec![
+                        ("name", bulk(name.clone())),
+                        ("pending", RespType::Integer(consumer.pending.len() as i64)),
+                        ("idle", RespType::Integer(now.saturating_sub(consumer.seen_time) as i64)),
+                        ("inactive", RespType::Integer(inactive)),
+                    ])
+                })
+                .collect();
+            Ok(RespType::Array(Some(consumers)))
+        }
+    }
+}
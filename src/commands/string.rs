@@ -0,0 +1,470 @@
+use bytes::Bytes;
+use time::OffsetDateTime;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{self, Item, StringValue, Value, STRING_MAX_LEN},
+};
+
+use super::{incr_float, parse_expire, parse_float, parse_int, upper, wrong_args};
+
+fn ok() -> RespType {
+    RespType::SimpleString("OK".to_string())
+}
+
+fn bulk(value: Bytes) -> RespType {
+    RespType::BulkString(Some(value))
+}
+
+/// 检查修改后的字符串长度是否超出限制
+fn check_string_length(len: usize) -> Result<(), String> {
+    if len > STRING_MAX_LEN {
+        return Err("ERR string exceeds maximum allowed size (proto-max-bulk-len)".to_string());
+    }
+    Ok(())
+}
+
+pub async fn append(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("append"));
+    }
+    let mut db = storage::write().await;
+    let current = db.string_value(&args[0])?.map_or(0, StringValue::len);
+    check_string_length(current + args[1].len())?;
+    let len = db.modify_string(&args[0], 0, |value| {
+        value.extend_from_slice(&args[1]);
+        value.len()
+    })?;
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn strlen(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("strlen"));
+    }
+    let db = storage::read().await;
+    let len = db.string_value(&args[0])?.map_or(0, StringValue::len);
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn getrange(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("getrange"));
+    }
+    let mut start = parse_int(&args[1])?;
+    let mut end = parse_int(&args[2])?;
+    let db = storage::read().await;
+    let value = db.string(&args[0])?.unwrap_or_default();
+    let len = value.len() as i64;
+    if start < 0 && end < 0 && start > end {
+        return Ok(bulk(Bytes::new()));
+    }
+    if start < 0 {
+        start = (start + len).max(0);
+    }
+    if end < 0 {
+        end = (end + len).max(0);
+    }
+    end = end.min(len - 1);
+    if len == 0 || start > end {
+        return Ok(bulk(Bytes::new()));
+    }
+    Ok(bulk(value.slice(start as usize..=end as usize)))
+}
+
+/// 从 offset 开始覆盖字符串，超出原长度的部分用 0 填充
+pub async fn setrange(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("setrange"));
+    }
+    let offset = parse_int(&args[1])?;
+    if offset < 0 {
+        return Err("ERR offset is out of range".to_string());
+    }
+    let offset = offset as usize;
+    let mut db = storage::write().await;
+    let current = db.string_value(&args[0])?.map_or(0, StringValue::len);
+    // 值为空时不修改，也不创建键
+    if args[2].is_empty() {
+        return Ok(RespType::Integer(current as i64));
+    }
+    let end = offset.saturating_add(args[2].len());
+    check_string_length(end)?;
+    let len = db.modify_string(&args[0], end, |value| {
+        value[offset..end].copy_from_slice(&args[2]);
+        value.len()
+    })?;
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn getset(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("getset"));
+    }
+    let mut db = storage::write().await;
+    let old = db.string(&args[0])?;
+    db.insert(args[0].clone(), Item::new(Value::String(args[1].clone().into())));
+    rdb::add_dirty(1);
+    Ok(RespType::BulkString(old))
+}
+
+pub async fn getdel(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("getdel"));
+    }
+    let mut db = storage::write().await;
+    let value = db.string(&args[0])?;
+    if value.is_some() {
+        db.delete(&args[0]);
+        rdb::add_dirty(1);
+    }
+    Ok(RespType::BulkString(value))
+}
+
+/// GETEX 对过期时间的修改
+enum GetExExpire {
+    Keep,
+    Persist,
+    At(OffsetDateTime),
+}
+
+pub async fn getex(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("getex"));
+    }
+    let syntax_error = || "ERR syntax error".to_string();
+    let mut expire = None;
+    let mut persist = false;
+    let mut i = 1;
+    while i < args.len() {
+        let option = upper(&args[i]);
+        match option.as_str() {
+            "PERSIST" if expire.is_none() => persist = true,
+            "EX" | "PX" | "EXAT" | "PXAT" if !persist && expire.is_none() => {
+                let arg = args.get(i + 1).ok_or_else(syntax_error)?;
+                let (unit, absolute) = match option.as_str() {
+                    "EX" => (1000, false),
+                    "PX" => (1, false),
+                    "EXAT" => (1000, true),
+                    _ => (1, true),
+                };
+                expire = Some((arg, unit, absolute));
+                i += 1;
+            }
+            _ => return Err(syntax_error()),
+        }
+        i += 1;
+    }
+    let expire = match expire {
+        Some((arg, unit, absolute)) => GetExExpire::At(parse_expire(arg, "getex", unit, absolute)?),
+        None if persist => GetExExpire::Persist,
+        None => GetExExpire::Keep,
+    };
+
+    let mut db = storage::write().await;
+    let value = db.string(&args[0])?;
+    if value.is_some() {
+        match expire {
+            GetExExpire::Keep => {}
+            GetExExpire::Persist => {
+                if db.get(&args[0]).is_some_and(|item| item.expires.is_some()) {
+                    db.set_expires(&args[0], None);
+                    rdb::add_dirty(1);
+                }
+            }
+            GetExExpire::At(expires) if expires <= OffsetDateTime::now_utc() => {
+                db.delete(&args[0]);
+                rdb::add_dirty(1);
+            }
+            GetExExpire::At(expires) => {
+                db.set_expires(&args[0], Some(expires));
+                rdb::add_dirty(1);
+            }
+        }
+    }
+    Ok(RespType::BulkString(value))
+}
+
+pub async fn setnx(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("setnx"));
+    }
+    let mut db = storage::write().await;
+    if db.get_mut(&args[0]).is_some() {
+        return Ok(RespType::Integer(0));
+    }
+    db.insert(args[0].clone(), Item::new(Value::String(args[1].clone().into())));
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(1))
+}
+
+async fn setex_generic(args: Vec<Bytes>, name: &str, unit: i64) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args(name));
+    }
+    let expires = parse_expire(&args[1], name, unit, false)?;
+    let mut db = storage::write().await;
+    db.insert(
+        args[0].clone(),
+        Item {
+            value: Value::String(args[2].clone().into()),
+            expires: Some(expires),
+        },
+    );
+    rdb::add_dirty(1);
+    Ok(ok())
+}
+
+pub async fn setex(args: Vec<Bytes>) -> Result<RespType, String> {
+    setex_generic(args, "setex", 1000).await
+}
+
+pub async fn psetex(args: Vec<Bytes>) -> Result<RespType, String> {
+    setex_generic(args, "psetex", 1).await
+}
+
+/// MSET 和 MSETNX 在同一次写锁内完成，其他连接不会看到只写入了一部分的状态
+async fn mset_generic(args: Vec<Bytes>, name: &str, nx: bool) -> Result<RespType, String> {
+    if args.is_empty() || args.len() % 2 == 1 {
+        return Err(wrong_args(name));
+    }
+    let mut db = storage::write().await;
+    if nx && args.chunks(2).any(|pair| db.get(&pair[0]).is_some()) {
+        return Ok(RespType::Integer(0));
+    }
+    for pair in args.chunks(2) {
+        db.insert(pair[0].clone(), Item::new(Value::String(pair[1].clone().into())));
+    }
+    rdb::add_dirty((args.len() / 2) as u64);
+    Ok(if nx { RespType::Integer(1) } else { ok() })
+}
+
+pub async fn mset(args: Vec<Bytes>) -> Result<RespType, String> {
+    mset_generic(args, "mset", false).await
+}
+
+pub async fn msetnx(args: Vec<Bytes>) -> Result<RespType, String> {
+    mset_generic(args, "msetnx", true).await
+}
+
+/// 不存在或者不是字符串的键返回 nil
+pub async fn mget(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.is_empty() {
+        return Err(wrong_args("mget"));
+    }
+    let db = storage::read().await;
+    let values = args
+        .iter()
+        .map(|key| RespType::BulkString(db.string(key).ok().flatten()))
+        .collect();
+    Ok(RespType::Array(Some(values)))
+}
+
+/// 计数器在原值上修改，保留过期时间
+async fn incr_generic(key: &Bytes, increment: i64) -> Result<RespType, String> {
+    let mut db = storage::write().await;
+    let current = match db.string_value(key)? {
+        Some(value) => value
+            .as_int()
+            .ok_or_else(|| "ERR value is not an integer or out of range".to_string())?,
+        None => 0,
+    };
+    let value = current
+        .checked_add(increment)
+        .ok_or_else(|| "ERR increment or decrement would overflow".to_string())?;
+    db.update_string(key, StringValue::Int(value));
+    rdb::add_dirty(1);
+    Ok(RespType::Integer(value))
+}
+
+pub async fn incr(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("incr"));
+    }
+    incr_generic(&args[0], 1).await
+}
+
+pub async fn decr(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("decr"));
+    }
+    incr_generic(&args[0], -1).await
+}
+
+pub async fn incrby(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("incrby"));
+    }
+    let increment = parse_int(&args[1])?;
+    incr_generic(&args[0], increment).await
+}
+
+pub async fn decrby(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("decrby"));
+    }
+    let decrement = parse_int(&args[1])?;
+    // i64::MIN 取反会溢出
+    let increment = decrement
+        .checked_neg()
+        .ok_or_else(|| "ERR decrement would overflow".to_string())?;
+    incr_generic(&args[0], increment).await
+}
+
+pub async fn incrbyfloat(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("incrbyfloat"));
+    }
+    parse_float(&args[1])?;
+    let mut db = storage::write().await;
+    let current = match db.string_value(&args[0])? {
+        Some(value) => value.to_bytes(),
+        None => Bytes::from_static(b"0"),
+    };
+    let value = incr_float(&current, &args[1])?;
+    db.update_string(&args[0], StringValue::Raw(value.clone()));
+    rdb::add_dirty(1);
+    Ok(RespType::BulkString(Some(value)))
+}
+
+/// LCS 的可选参数
+#[derive(Default)]
+struct LcsOptions {
+    len: bool,
+    idx: bool,
+    min_match_len: usize,
+    with_match_len: bool,
+}
+
+impl LcsOptions {
+    fn parse(args: &[Bytes]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut i = 0;
+        while i < args.len() {
+            match upper(&args[i]).as_str() {
+                "LEN" => options.len = true,
+                "IDX" => options.idx = true,
+                "WITHMATCHLEN" => options.with_match_len = true,
+                "MINMATCHLEN" if i + 1 < args.len() => {
+                    options.min_match_len = parse_int(&args[i + 1])?.max(0) as usize;
+                    i += 1;
+                }
+                _ => return Err("ERR syntax error".to_string()),
+            }
+            i += 1;
+        }
+        if options.len && options.idx {
+            return Err("ERR If you want both the length and indexes, please just use IDX.".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// 最长公共子序列，动态规划后从表的右下角回溯，与 Redis 一样从后往前输出匹配的区间
+pub async fn lcs(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("lcs"));
+    }
+    let (a, b) = {
+        let db = storage::read().await;
+        let string = |key: &Bytes| {
+            db.string(key)
+                .map_err(|_| "ERR The specified keys must contain string values".to_string())
+        };
+        (string(&args[0])?.unwrap_or_default(), string(&args[1])?.unwrap_or_default())
+    };
+    let options = LcsOptions::parse(&args[2..])?;
+
+    let (alen, blen) = (a.len(), b.len());
+    let cells = (alen + 1)
+        .checked_mul(blen + 1)
+        .filter(|cells| cells.saturating_mul(4) <= STRING_MAX_LEN)
+        .ok_or_else(|| "ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len".to_string())?;
+    // table[j * (alen + 1) + i] 是 a[..i] 和 b[..j] 的 LCS 长度
+    let mut table = vec![0u32; cells];
+    let at = |i: usize, j: usize| j * (alen + 1) + i;
+    for i in 1..=alen {
+        for j in 1..=blen {
+            table[at(i, j)] = if a[i - 1] == b[j - 1] {
+                table[at(i - 1, j - 1)] + 1
+            } else {
+                table[at(i - 1, j)].max(table[at(i, j - 1)])
+            };
+        }
+    }
+    let len = table[at(alen, blen)] as usize;
+    if options.len {
+        return Ok(RespType::Integer(len as i64));
+    }
+
+    let mut result = vec![0u8; len];
+    let mut matches = Vec::new();
+    let (mut i, mut j, mut idx) = (alen, blen, len);
+    // a 的起点等于 alen 表示当前没有正在跟踪的区间
+    let (mut arange_start, mut arange_end, mut brange_start, mut brange_end) = (alen, 0, 0, 0);
+    while i > 0 && j > 0 {
+        let mut emit_range = false;
+        if a[i - 1] == b[j - 1] {
+            result[idx - 1] = a[i - 1];
+            if arange_start == alen {
+                arange_start = i - 1;
+                arange_end = i - 1;
+                brange_start = j - 1;
+                brange_end = j - 1;
+            } else if arange_start == i && brange_start == j {
+                // 与当前区间连续，向前扩展
+                arange_start -= 1;
+                brange_start -= 1;
+            } else {
+                emit_range = true;
+            }
+            // 匹配到了某个字符串的开头，后面不会再有匹配
+            if arange_start == 0 || brange_start == 0 {
+                emit_range = true;
+            }
+            idx -= 1;
+            i -= 1;
+            j -= 1;
+        } else {
+            if table[at(i - 1, j)] > table[at(i, j - 1)] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            if arange_start != alen {
+                emit_range = true;
+            }
+        }
+
+        if emit_range {
+            let match_len = arange_end - arange_start + 1;
+            if options.idx && match_len >= options.min_match_len {
+                let range = |start: usize, end: usize| {
+                    RespType::Array(Some(vec![
+                        RespType::Integer(start as i64),
+                        RespType::Integer(end as i64),
+                    ]))
+                };
+                let mut entry = vec![range(arange_start, arange_end), range(brange_start, brange_end)];
+                if options.with_match_len {
+                    entry.push(RespType::Integer(match_len as i64));
+                }
+                matches.push(RespType::Array(Some(entry)));
+            }
+            arange_start = alen;
+        }
+    }
+
+    if options.idx {
+        return Ok(RespType::Array(Some(vec![
+            bulk(Bytes::from_static(b"matches")),
+            RespType::Array(Some(matches)),
+            bulk(Bytes::from_static(b"len")),
+            RespType::Integer(len as i64),
+        ])));
+    }
+    Ok(bulk(Bytes::from(result)))
+}
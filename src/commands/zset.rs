@@ -0,0 +1,705 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use crate::{
+    rdb,
+    resp::RespType,
+    storage::{self, normalize_range, Db, Item, LexBound, LexRange, ScoreRange, SortedSet, Value, WRONGTYPE},
+};
+
+use super::{format_float, parse_float, parse_int, upper, wrong_args};
+
+fn syntax_error() -> String {
+    "ERR syntax error".to_string()
+}
+
+pub(super) fn score_reply(score: f64) -> RespType {
+    RespType::BulkString(Some(Bytes::from(format_float(score))))
+}
+
+fn entries_reply(entries: Vec<(Bytes, f64)>, withscores: bool) -> RespType {
+    let mut values = Vec::with_capacity(entries.len() * if withscores { 2 } else { 1 });
+    for (member, score) in entries {
+        values.push(RespType::BulkString(Some(member)));
+        if withscores {
+            values.push(score_reply(score));
+        }
+    }
+    RespType::Array(Some(values))
+}
+
+fn parse_score_bound(arg: &[u8]) -> Result<(f64, bool), String> {
+    let (arg, exclusive) = match arg.strip_prefix(b"(") {
+        Some(rest) => (rest, true),
+        None => (arg, false),
+    };
+    let value = parse_float(arg).map_err(|_| "ERR min or max is not a float".to_string())?;
+    Ok((value, exclusive))
+}
+
+fn parse_score_range(min: &[u8], max: &[u8]) -> Result<ScoreRange, String> {
+    let (min, minex) = parse_score_bound(min)?;
+    let (max, maxex) = parse_score_bound(max)?;
+    Ok(ScoreRange {
+        min,
+        max,
+        minex,
+        maxex,
+    })
+}
+
+fn parse_lex_bound(arg: &[u8]) -> Result<LexBound, String> {
+    match arg.first() {
+        Some(b'-') if arg.len() == 1 => Ok(LexBound::NegInf),
+        Some(b'+') if arg.len() == 1 => Ok(LexBound::PosInf),
+        Some(b'[') => Ok(LexBound::Inclusive(Bytes::copy_from_slice(&arg[1..]))),
+        Some(b'(') => Ok(LexBound::Exclusive(Bytes::copy_from_slice(&arg[1..]))),
+        _ => Err("ERR min or max not valid string range item".to_string()),
+    }
+}
+
+fn parse_lex_range(min: &[u8], max: &[u8]) -> Result<LexRange, String> {
+    Ok(LexRange {
+        min: parse_lex_bound(min)?,
+        max: parse_lex_bound(max)?,
+    })
+}
+
+pub async fn zadd(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 3 {
+        return Err(wrong_args("zadd"));
+    }
+    let (mut nx, mut xx, mut gt, mut lt, mut ch, mut incr) = (false, false, false, false, false, false);
+    let mut i = 1;
+    while i < args.len() {
+        match upper(&args[i]).as_str() {
+            "NX" => nx = true,
+            "XX" => xx = true,
+            "GT" => gt = true,
+            "LT" => lt = true,
+            "CH" => ch = true,
+            "INCR" => incr = true,
+            _ => break,
+        }
+        i += 1;
+    }
+    let pairs = &args[i..];
+    if pairs.is_empty() || pairs.len() % 2 == 1 {
+        return Err(syntax_error());
+    }
+    if nx && xx {
+        return Err("ERR XX and NX options at the same time are not compatible".to_string());
+    }
+    if ((gt || lt) && nx) || (gt && lt) {
+        return Err("ERR GT, LT, and/or NX options at the same time are not compatible".to_string());
+    }
+    if incr && pairs.len() > 2 {
+        return Err("ERR INCR option supports a single increment-element pair".to_string());
+    }
+    let mut elements = Vec::with_capacity(pairs.len() / 2);
+    for pair in pairs.chunks(2) {
+        elements.push((parse_float(&pair[0])?, pair[1].clone()));
+    }
+
+    let mut db = storage::write().await;
+    if xx && db.zset(&args[0])?.is_none() {
+        return Ok(if incr {
+            RespType::BulkString(None)
+        } else {
+            RespType::Integer(0)
+        });
+    }
+    let zset = db.zset_or_insert(&args[0])?;
+    let (mut added, mut updated) = (0, 0);
+    let mut result = None;
+    for (score, member) in elements {
+        match zset.score(&member) {
+            Some(current) => {
+                if nx {
+                    continue;
+                }
+                let score = if incr { current + score } else { score };
+                if score.is_nan() {
+                    return Err("ERR resulting score is not a number (NaN)".to_string());
+                }
+                if (gt && score <= current) || (lt && score >= current) {
+                    continue;
+                }
+                if score != current {
+                    zset.insert(member, score);
+                    updated += 1;
+                }
+                result = Some(score);
+            }
+            None => {
+                if xx {
+                    continue;
+                }
+                zset.insert(member, score);
+                added += 1;
+                result = Some(score);
+            }
+        }
+    }
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty((added + updated) as u64);
+    if incr {
+        return Ok(match result {
+            Some(score) => score_reply(score),
+            None => RespType::BulkString(None),
+        });
+    }
+    Ok(RespType::Integer(if ch { added + updated } else { added }))
+}
+
+pub async fn zincrby(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("zincrby"));
+    }
+    let increment = parse_float(&args[1])?;
+    let mut db = storage::write().await;
+    let zset = db.zset_or_insert(&args[0])?;
+    let score = zset.score(&args[2]).unwrap_or(0.0) + increment;
+    if score.is_nan() {
+        db.remove_if_empty(&args[0]);
+        return Err("ERR resulting score is not a number (NaN)".to_string());
+    }
+    zset.insert(args[2].clone(), score);
+    rdb::add_dirty(1);
+    Ok(score_reply(score))
+}
+
+pub async fn zrem(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("zrem"));
+    }
+    let mut db = storage::write().await;
+    let zset = match db.zset_mut(&args[0])? {
+        Some(zset) => zset,
+        None => return Ok(RespType::Integer(0)),
+    };
+    let removed = args[1..].iter().filter(|member| zset.remove(member)).count();
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty(removed as u64);
+    Ok(RespType::Integer(removed as i64))
+}
+
+pub async fn zcard(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 1 {
+        return Err(wrong_args("zcard"));
+    }
+    let db = storage::read().await;
+    let len = db.zset(&args[0])?.map_or(0, |zset| zset.len());
+    Ok(RespType::Integer(len as i64))
+}
+
+pub async fn zscore(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 2 {
+        return Err(wrong_args("zscore"));
+    }
+    let db = storage::read().await;
+    Ok(match db.zset(&args[0])?.and_then(|zset| zset.score(&args[1])) {
+        Some(score) => score_reply(score),
+        None => RespType::BulkString(None),
+    })
+}
+
+pub async fn zmscore(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 2 {
+        return Err(wrong_args("zmscore"));
+    }
+    let db = storage::read().await;
+    let zset = db.zset(&args[0])?;
+    let values = args[1..]
+        .iter()
+        .map(|member| match zset.and_then(|zset| zset.score(member)) {
+            Some(score) => score_reply(score),
+            None => RespType::BulkString(None),
+        })
+        .collect();
+    Ok(RespType::Array(Some(values)))
+}
+
+async fn rank(args: Vec<Bytes>, name: &str, rev: bool) -> Result<RespType, String> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(wrong_args(name));
+    }
+    let withscore = match args.get(2) {
+        Some(arg) if upper(arg) == "WITHSCORE" => true,
+        Some(_) => return Err(syntax_error()),
+        None => false,
+    };
+    let db = storage::read().await;
+    let zset = db.zset(&args[0])?;
+    let rank = zset.and_then(|zset| zset.rank(&args[1], rev));
+    Ok(match (rank, withscore) {
+        (Some(rank), false) => RespType::Integer(rank as i64),
+        (Some(rank), true) => {
+            let score = zset.and_then(|zset| zset.score(&args[1])).unwrap_or_default();
+            RespType::Array(Some(vec![RespType::Integer(rank as i64), score_reply(score)]))
+        }
+        (None, false) => RespType::BulkString(None),
+        (None, true) => RespType::Array(None),
+    })
+}
+
+pub async fn zrank(args: Vec<Bytes>) -> Result<RespType, String> {
+    rank(args, "zrank", false).await
+}
+
+pub async fn zrevrank(args: Vec<Bytes>) -> Result<RespType, String> {
+    rank(args, "zrevrank", true).await
+}
+
+pub async fn zcount(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("zcount"));
+    }
+    let range = parse_score_range(&args[1], &args[2])?;
+    let db = storage::read().await;
+    let count = db
+        .zset(&args[0])?
+        .map_or(0, |zset| zset.count_in_score_range(&range));
+    Ok(RespType::Integer(count as i64))
+}
+
+pub async fn zlexcount(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args("zlexcount"));
+    }
+    let range = parse_lex_range(&args[1], &args[2])?;
+    let db = storage::read().await;
+    let count = db
+        .zset(&args[0])?
+        .map_or(0, |zset| zset.count_in_lex_range(&range));
+    Ok(RespType::Integer(count as i64))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RangeBy {
+    Rank,
+    Score,
+    Lex,
+}
+
+/// ZRANGE 系列命令的参数
+struct RangeSpec {
+    by: RangeBy,
+    rev: bool,
+    limit: Option<(i64, i64)>,
+    withscores: bool,
+}
+
+impl RangeSpec {
+    fn new(by: RangeBy, rev: bool) -> Self {
+        Self {
+            by,
+            rev,
+            limit: None,
+            withscores: false,
+        }
+    }
+
+    /// 解析 WITHSCORES 与 LIMIT 等可选参数
+    fn parse_options(&mut self, options: &[Bytes], unified: bool) -> Result<(), String> {
+        let mut i = 0;
+        while i < options.len() {
+            match upper(&options[i]).as_str() {
+                "WITHSCORES" if unified || self.by != RangeBy::Lex => self.withscores = true,
+                "LIMIT" if unified || self.by != RangeBy::Rank => {
+                    if i + 2 >= options.len() {
+                        return Err(syntax_error());
+                    }
+                    self.limit = Some((parse_int(&options[i + 1])?, parse_int(&options[i + 2])?));
+                    i += 2;
+                }
+                "BYSCORE" if unified => self.by = RangeBy::Score,
+                "BYLEX" if unified => self.by = RangeBy::Lex,
+                "REV" if unified => self.rev = true,
+                _ => return Err(syntax_error()),
+            }
+            i += 1;
+        }
+        if self.limit.is_some() && self.by == RangeBy::Rank {
+            return Err(
+                "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                    .to_string(),
+            );
+        }
+        if self.withscores && self.by == RangeBy::Lex {
+            return Err("ERR syntax error, WITHSCORES not supported in combination with BYLEX".to_string());
+        }
+        Ok(())
+    }
+
+    /// 执行范围查询；start/stop 为命令中的原始参数，REV 时依次为 max、min
+    fn run(&self, db: &Db, key: &[u8], start: &[u8], stop: &[u8]) -> Result<Vec<(Bytes, f64)>, String> {
+        let (min, max) = if self.rev { (stop, start) } else { (start, stop) };
+        let (offset, count) = match self.limit {
+            Some((offset, _)) if offset < 0 => return Ok(vec![]),
+            Some((offset, count)) => (offset as usize, usize::try_from(count).ok()),
+            None => (0, None),
+        };
+        match self.by {
+            RangeBy::Rank => {
+                let start = parse_int(start)?;
+                let stop = parse_int(stop)?;
+                let zset = match db.zset(key)? {
+                    Some(zset) => zset,
+                    None => return Ok(vec![]),
+                };
+                Ok(match normalize_range(start, stop, zset.len()) {
+                    Some((start, end)) => zset.range_by_rank(start, end, self.rev),
+                    None => vec![],
+                })
+            }
+            RangeBy::Score => {
+                let range = parse_score_range(min, max)?;
+                Ok(db
+                    .zset(key)?
+                    .map_or_else(Vec::new, |zset| zset.range_by_score(&range, self.rev, offset, count)))
+            }
+            RangeBy::Lex => {
+                let range = parse_lex_range(min, max)?;
+                Ok(db
+                    .zset(key)?
+                    .map_or_else(Vec::new, |zset| zset.range_by_lex(&range, self.rev, offset, count)))
+            }
+        }
+    }
+}
+
+async fn range(args: Vec<Bytes>, name: &str, mut spec: RangeSpec, unified: bool) -> Result<RespType, String> {
+    if args.len() < 3 {
+        return Err(wrong_args(name));
+    }
+    spec.parse_options(&args[3..], unified)?;
+    let db = storage::read().await;
+    let entries = spec.run(&db, &args[0], &args[1], &args[2])?;
+    Ok(entries_reply(entries, spec.withscores))
+}
+
+pub async fn zrange(args: Vec<Bytes>) -> Result<RespType, String> {
+    range(args, "zrange", RangeSpec::new(RangeBy::Rank, false), true).await
+}
+
+pub async fn zrevrange(args: Vec<Bytes>) -> Result<RespType, String> {
+    range(args, "zrevrange", RangeSpec::new(RangeBy::Rank, true), false).await
+}
+
+pub async fn zrangebyscore(args: Vec<Bytes>) -> Result<RespType, String> {
+    range(args, "zrangebyscore", RangeSpec::new(RangeBy::Score, false), false).await
+}
+
+pub async fn zrevrangebyscore(args: Vec<Bytes>) -> Result<RespType, String> {
+    range(args, "zrevrangebyscore", RangeSpec::new(RangeBy::Score, true), false).await
+}
+
+pub async fn zrangebylex(args: Vec<Bytes>) -> Result<RespType, String> {
+    range(args, "zrangebylex", RangeSpec::new(RangeBy::Lex, false), false).await
+}
+
+pub async fn zrevrangebylex(args: Vec<Bytes>) -> Result<RespType, String> {
+    range(args, "zrevrangebylex", RangeSpec::new(RangeBy::Lex, true), false).await
+}
+
+pub async fn zrangestore(args: Vec<Bytes>) -> Result<RespType, String> {
+    if args.len() < 4 {
+        return Err(wrong_args("zrangestore"));
+    }
+    let mut spec = RangeSpec::new(RangeBy::Rank, false);
+    spec.parse_options(&args[4..], true)?;
+    if spec.withscores {
+        return Err(syntax_error());
+    }
+    let mut db = storage::write().await;
+    let entries = spec.run(&db, &args[1], &args[2], &args[3])?;
+    Ok(RespType::Integer(store(&mut db, &args[0], entries) as i64))
+}
+
+/// 将结果写入目标键，结果为空时删除目标键
+fn store(db: &mut Db, key: &Bytes, entries: impl IntoIterator<Item = (Bytes, f64)>) -> usize {
+    let mut zset = SortedSet::default();
+    for (member, score) in entries {
+        zset.insert(member, score);
+    }
+    let len = zset.len();
+    let deleted = db.delete(key);
+    if len > 0 {
+        db.insert(key.clone(), Item::new(Value::ZSet(zset)));
+    }
+    if deleted || len > 0 {
+        rdb::add_dirty(1);
+    }
+    len
+}
+
+async fn remrange(args: Vec<Bytes>, name: &str, by: RangeBy) -> Result<RespType, String> {
+    if args.len() != 3 {
+        return Err(wrong_args(name));
+    }
+    let spec = RangeSpec::new(by, false);
+    let mut db = storage::write().await;
+    let entries = spec.run(&db, &args[0], &args[1], &args[2])?;
+    if let Some(zset) = db.zset_mut(&args[0])? {
+        for (member, _) in &entries {
+            zset.remove(member);
+        }
+    }
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty(entries.len() as u64);
+    Ok(RespType::Integer(entries.len() as i64))
+}
+
+pub async fn zremrangebyrank(args: Vec<Bytes>) -> Result<RespType, String> {
+    remrange(args, "zremrangebyrank", RangeBy::Rank).await
+}
+
+pub async fn zremrangebyscore(args: Vec<Bytes>) -> Result<RespType, String> {
+    remrange(args, "zremrangebyscore", RangeBy::Score).await
+}
+
+pub async fn zremrangebylex(args: Vec<Bytes>) -> Result<RespType, String> {
+    remrange(args, "zremrangebylex", RangeBy::Lex).await
+}
+
+async fn pop(args: Vec<Bytes>, name: &str, max: bool) -> Result<RespType, String> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(wrong_args(name));
+    }
+    let count = match args.get(1) {
+        Some(count) => {
+            let count = parse_int(count)?;
+            if count < 0 {
+                return Err("ERR value is out of range, must be positive".to_string());
+            }
+            count as usize
+        }
+        None => 1,
+    };
+    let mut db = storage::write().await;
+    let zset = match db.zset_mut(&args[0])? {
+        Some(zset) => zset,
+        None => return Ok(RespType::Array(Some(vec![]))),
+    };
+    let mut entries = Vec::new();
+    while entries.len() < count {
+        match zset.pop(max) {
+            Some(entry) => entries.push(entry),
+            None => break,
+        }
+    }
+    db.remove_if_empty(&args[0]);
+    rdb::add_dirty(entries.len() as u64);
+    Ok(entries_reply(entries, true))
+}
+
+pub async fn zpopmin(args: Vec<Bytes>) -> Result<RespType, String> {
+    pop(args, "zpopmin", false).await
+}
+
+pub async fn zpopmax(args: Vec<Bytes>) -> Result<RespType, String> {
+    pop(args, "zpopmax", true).await
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SetOp {
+    Union,
+    Inter,
+    Diff,
+}
+
+#[derive(Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            // inf + -inf 的结果按 0 处理
+            Aggregate::Sum => {
+                let sum = a + b;
+                if sum.is_nan() {
+                    0.0
+                } else {
+                    sum
+                }
+            }
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+/// ZUNION/ZINTER/ZDIFF 及其 STORE 形式的参数
+struct SetOpSpec {
+    keys: Vec<Bytes>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+    withscores: bool,
+}
+
+impl SetOpSpec {
+    fn parse(args: &[Bytes], name: &str, op: SetOp, allow_withscores: bool) -> Result<Self, String> {
+        if args.is_empty() {
+            return Err(wrong_args(name));
+        }
+        let numkeys = parse_int(&args[0])?;
+        if numkeys <= 0 {
+            return Err(format!("ERR at least 1 input key is needed for '{}' command", name));
+        }
+        let numkeys = numkeys as usize;
+        if numkeys > args.len() - 1 {
+            return Err(syntax_error());
+        }
+        let mut spec = Self {
+            keys: args[1..=numkeys].to_vec(),
+            weights: vec![1.0; numkeys],
+            aggregate: Aggregate::Sum,
+            withscores: false,
+        };
+        let options = &args[numkeys + 1..];
+        let mut i = 0;
+        while i < options.len() {
+            match upper(&options[i]).as_str() {
+                "WEIGHTS" if op != SetOp::Diff && i + numkeys < options.len() => {
+                    for j in 0..numkeys {
+                        spec.weights[j] = parse_float(&options[i + 1 + j])
+                            .map_err(|_| "ERR weight value is not a float".to_string())?;
+                    }
+                    i += numkeys;
+                }
+                "AGGREGATE" if op != SetOp::Diff && i + 1 < options.len() => {
+                    spec.aggregate = match upper(&options[i + 1]).as_str() {
+                        "SUM" => Aggregate::Sum,
+                        "MIN" => Aggregate::Min,
+                        "MAX" => Aggregate::Max,
+                        _ => return Err(syntax_error()),
+                    };
+                    i += 1;
+                }
+                "WITHSCORES" if allow_withscores => spec.withscores = true,
+                _ => return Err(syntax_error()),
+            }
+            i += 1;
+        }
+        Ok(spec)
+    }
+
+    /// 读取源键，集合类型的成员分数视为 1
+    fn source(db: &Db, key: &[u8]) -> Result<Option<HashMap<Bytes, f64>>, String> {
+        match db.get(key).map(|item| &item.value) {
+            Some(Value::ZSet(zset)) => Ok(Some(
+                zset.iter().map(|(member, score)| (member.clone(), score)).collect(),
+            )),
+            Some(Value::Set(set)) => Ok(Some(set.members().into_iter().map(|m| (m, 1.0)).collect())),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    fn compute(&self, db: &Db, op: SetOp) -> Result<Vec<(Bytes, f64)>, String> {
+        let mut sources = Vec::with_capacity(self.keys.len());
+        for key in &self.keys {
+            sources.push(Self::source(db, key)?);
+        }
+        let weighted = |score: f64, weight: f64| {
+            let value = score * weight;
+            if value.is_nan() {
+                0.0
+            } else {
+                value
+            }
+        };
+        let mut result: HashMap<Bytes, f64> = HashMap::new();
+        match op {
+            SetOp::Union => {
+                for (source, weight) in sources.iter().zip(&self.weights) {
+                    for (member, score) in source.iter().flatten() {
+                        let score = weighted(*score, *weight);
+                        result
+                            .entry(member.clone())
+                            .and_modify(|current| *current = self.aggregate.apply(*current, score))
+                            .or_insert(score);
+                    }
+                }
+            }
+            SetOp::Inter => {
+                if sources.iter().any(|source| source.is_none()) {
+                    return Ok(vec![]);
+                }
+                let sources: Vec<_> = sources.into_iter().flatten().collect();
+                'members: for (member, score) in &sources[0] {
+                    let mut value = weighted(*score, self.weights[0]);
+                    for (source, weight) in sources[1..].iter().zip(&self.weights[1..]) {
+                        match source.get(member) {
+                            Some(score) => value = self.aggregate.apply(value, weighted(*score, *weight)),
+                            None => continue 'members,
+                        }
+                    }
+                    result.insert(member.clone(), value);
+                }
+            }
+            SetOp::Diff => {
+                if let Some(first) = &sources[0] {
+                    for (member, score) in first {
+                        if sources[1..].iter().flatten().all(|source| !source.contains_key(member)) {
+                            result.insert(member.clone(), *score);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(result.into_iter().collect())
+    }
+}
+
+async fn set_op(args: Vec<Bytes>, name: &str, op: SetOp) -> Result<RespType, String> {
+    let spec = SetOpSpec::parse(&args, name, op, true)?;
+    let db = storage::read().await;
+    let entries = spec.compute(&db, op)?;
+    let mut zset = SortedSet::default();
+    for (member, score) in entries {
+        zset.insert(member, score);
+    }
+    let entries = zset.iter().map(|(member, score)| (member.clone(), score)).collect();
+    Ok(entries_reply(entries, spec.withscores))
+}
+
+async fn set_op_store(args: Vec<Bytes>, name: &str, op: SetOp) -> Result<RespType, String> {
+    if args.len() < 3 {
+        return Err(wrong_args(name));
+    }
+    let spec = SetOpSpec::parse(&args[1..], name, op, false)?;
+    let mut db = storage::write().await;
+    let entries = spec.compute(&db, op)?;
+    Ok(RespType::Integer(store(&mut db, &args[0], entries) as i64))
+}
+
+pub async fn zunion(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op(args, "zunion", SetOp::Union).await
+}
+
+pub async fn zinter(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op(args, "zinter", SetOp::Inter).await
+}
+
+pub async fn zdiff(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op(args, "zdiff", SetOp::Diff).await
+}
+
+pub async fn zunionstore(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op_store(args, "zunionstore", SetOp::Union).await
+}
+
+pub async fn zinterstore(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op_store(args, "zinterstore", SetOp::Inter).await
+}
+
+pub async fn zdiffstore(args: Vec<Bytes>) -> Result<RespType, String> {
+    set_op_store(args, "zdiffstore", SetOp::Diff).await
+}
@@ -13,3 +13,33 @@ pub async fn get(key: &str) -> Option<String> {
     let config = CONFIG.lock().await;
     config.get(key).cloned()
 }
+
+/// 所有配置项，按名称排序
+pub async fn entries() -> Vec<(String, String)> {
+    let config = CONFIG.lock().await;
+    let mut entries: Vec<(String, String)> = config
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// 解析带单位的内存大小，与 Redis 的 memtoll 一致：k/m/g 为 1000 的倍数，kb/mb/gb 为 1024 的倍数，
+/// 单位不区分大小写
+pub fn parse_memory(value: &str) -> Option<u64> {
+    let value = value.to_lowercase();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
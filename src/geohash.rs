@@ -0,0 +1,422 @@
+//! geohash 编码与距离计算，与 Redis 的 geohash.c 和 geohash_helper.c 一致。
+//! GEO 的分数是 52 位的交错编码：偶数位是纬度，奇数位是经度
+
+pub const GEO_STEP_MAX: u8 = 26;
+pub const GEO_LAT_MIN: f64 = -85.051_128_78;
+pub const GEO_LAT_MAX: f64 = 85.051_128_78;
+pub const GEO_LONG_MIN: f64 = -180.0;
+pub const GEO_LONG_MAX: f64 = 180.0;
+const EARTH_RADIUS_IN_METERS: f64 = 6_372_797.560_856;
+const MERCATOR_MAX: f64 = 20_037_726.37;
+/// 标准 geohash 使用的 base32 字母表
+const GEO_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// 编码后的 geohash，step 是每个坐标使用的位数
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct HashBits {
+    pub bits: u64,
+    pub step: u8,
+}
+
+impl HashBits {
+    fn is_zero(&self) -> bool {
+        self.bits == 0 && self.step == 0
+    }
+
+    /// 对齐到 52 位后作为有序集合的分数
+    pub fn align52(&self) -> u64 {
+        self.bits << (52 - self.step as u32 * 2)
+    }
+
+    fn move_x(&mut self, d: i8) {
+        let shift = 64 - self.step as u32 * 2;
+        let mut x = self.bits & 0xaaaa_aaaa_aaaa_aaaa;
+        let y = self.bits & 0x5555_5555_5555_5555;
+        let zz = 0x5555_5555_5555_5555u64 >> shift;
+        if d > 0 {
+            x = x.wrapping_add(zz + 1);
+        } else {
+            x = (x | zz).wrapping_sub(zz + 1);
+        }
+        x &= 0xaaaa_aaaa_aaaa_aaaau64 >> shift;
+        self.bits = x | y;
+    }
+
+    fn move_y(&mut self, d: i8) {
+        let shift = 64 - self.step as u32 * 2;
+        let x = self.bits & 0xaaaa_aaaa_aaaa_aaaa;
+        let mut y = self.bits & 0x5555_5555_5555_5555;
+        let zz = 0xaaaa_aaaa_aaaa_aaaau64 >> shift;
+        if d > 0 {
+            y = y.wrapping_add(zz + 1);
+        } else {
+            y = (y | zz).wrapping_sub(zz + 1);
+        }
+        y &= 0x5555_5555_5555_5555u64 >> shift;
+        self.bits = x | y;
+    }
+
+    /// 区域之后的第一个 geohash，用于计算区域对应的分数范围
+    pub fn neighbor_max(&self) -> HashBits {
+        HashBits {
+            bits: self.bits + 1,
+            step: self.step,
+        }
+    }
+
+    /// 向经度方向移动 dx 格、纬度方向移动 dy 格
+    fn neighbor(&self, dx: i8, dy: i8) -> HashBits {
+        let mut hash = *self;
+        if dx != 0 {
+            hash.move_x(dx);
+        }
+        if dy != 0 {
+            hash.move_y(dy);
+        }
+        hash
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Range {
+    min: f64,
+    max: f64,
+}
+
+const LONG_RANGE: Range = Range {
+    min: GEO_LONG_MIN,
+    max: GEO_LONG_MAX,
+};
+const LAT_RANGE: Range = Range {
+    min: GEO_LAT_MIN,
+    max: GEO_LAT_MAX,
+};
+
+/// geohash 覆盖的经纬度范围
+struct Area {
+    longitude: Range,
+    latitude: Range,
+}
+
+/// 把 x 和 y 的位交错排列，x 占偶数位
+fn interleave64(x: u32, y: u32) -> u64 {
+    const B: [u64; 5] = [
+        0x5555_5555_5555_5555,
+        0x3333_3333_3333_3333,
+        0x0f0f_0f0f_0f0f_0f0f,
+        0x00ff_00ff_00ff_00ff,
+        0x0000_ffff_0000_ffff,
+    ];
+    const S: [u32; 5] = [1, 2, 4, 8, 16];
+    let (mut x, mut y) = (x as u64, y as u64);
+    for i in (0..5).rev() {
+        x = (x | (x << S[i])) & B[i];
+        y = (y | (y << S[i])) & B[i];
+    }
+    x | (y << 1)
+}
+
+/// interleave64 的逆运算，偶数位放在低 32 位，奇数位放在高 32 位
+fn deinterleave64(interleaved: u64) -> u64 {
+    const B: [u64; 6] = [
+        0x5555_5555_5555_5555,
+        0x3333_3333_3333_3333,
+        0x0f0f_0f0f_0f0f_0f0f,
+        0x00ff_00ff_00ff_00ff,
+        0x0000_ffff_0000_ffff,
+        0x0000_0000_ffff_ffff,
+    ];
+    const S: [u32; 6] = [0, 1, 2, 4, 8, 16];
+    let mut x = interleaved;
+    let mut y = interleaved >> 1;
+    for i in 0..6 {
+        x = (x | (x >> S[i])) & B[i];
+        y = (y | (y >> S[i])) & B[i];
+    }
+    x | (y << 32)
+}
+
+fn encode(long_range: Range, lat_range: Range, longitude: f64, latitude: f64, step: u8) -> Option<HashBits> {
+    if !(GEO_LONG_MIN..=GEO_LONG_MAX).contains(&longitude) || !(GEO_LAT_MIN..=GEO_LAT_MAX).contains(&latitude) {
+        return None;
+    }
+    if latitude < lat_range.min || latitude > lat_range.max || longitude < long_range.min || longitude > long_range.max
+    {
+        return None;
+    }
+    let scale = (1u64 << step) as f64;
+    let lat_offset = (latitude - lat_range.min) / (lat_range.max - lat_range.min) * scale;
+    let long_offset = (longitude - long_range.min) / (long_range.max - long_range.min) * scale;
+    Some(HashBits {
+        bits: interleave64(lat_offset as u32, long_offset as u32),
+        step,
+    })
+}
+
+/// 按 GEO 使用的经纬度范围编码，坐标超出范围时返回 None
+pub fn encode_wgs84(longitude: f64, latitude: f64, step: u8) -> Option<HashBits> {
+    encode(LONG_RANGE, LAT_RANGE, longitude, latitude, step)
+}
+
+fn decode(hash: HashBits) -> Area {
+    let separated = deinterleave64(hash.bits);
+    let scale = (1u64 << hash.step) as f64;
+    let lat = separated as u32 as f64;
+    let long = (separated >> 32) as u32 as f64;
+    let lat_scale = LAT_RANGE.max - LAT_RANGE.min;
+    let long_scale = LONG_RANGE.max - LONG_RANGE.min;
+    Area {
+        latitude: Range {
+            min: LAT_RANGE.min + (lat / scale) * lat_scale,
+            max: LAT_RANGE.min + ((lat + 1.0) / scale) * lat_scale,
+        },
+        longitude: Range {
+            min: LONG_RANGE.min + (long / scale) * long_scale,
+            max: LONG_RANGE.min + ((long + 1.0) / scale) * long_scale,
+        },
+    }
+}
+
+/// 把分数解码为所在区域中心的经纬度
+pub fn decode_score(score: f64) -> (f64, f64) {
+    let area = decode(HashBits {
+        bits: score as u64,
+        step: GEO_STEP_MAX,
+    });
+    let longitude = ((area.longitude.min + area.longitude.max) / 2.0).clamp(GEO_LONG_MIN, GEO_LONG_MAX);
+    let latitude = ((area.latitude.min + area.latitude.max) / 2.0).clamp(GEO_LAT_MIN, GEO_LAT_MAX);
+    (longitude, latitude)
+}
+
+/// 标准的 11 位 geohash 字符串，标准算法的纬度范围是 -90 到 90，需要重新编码
+pub fn geohash_string(score: f64) -> String {
+    let (longitude, latitude) = decode_score(score);
+    let range = Range { min: -90.0, max: 90.0 };
+    let hash = encode(LONG_RANGE, range, longitude, latitude, GEO_STEP_MAX).unwrap_or_default();
+    (0..11)
+        .map(|i| {
+            // 只有 52 位，最后一个字符固定为 0
+            let index = if i == 10 {
+                0
+            } else {
+                (hash.bits >> (52 - (i + 1) * 5)) & 0x1f
+            };
+            GEO_ALPHABET[index as usize] as char
+        })
+        .collect()
+}
+
+fn deg_rad(angle: f64) -> f64 {
+    angle * (std::f64::consts::PI / 180.0)
+}
+
+fn rad_deg(angle: f64) -> f64 {
+    angle / (std::f64::consts::PI / 180.0)
+}
+
+fn lat_distance(lat1: f64, lat2: f64) -> f64 {
+    EARTH_RADIUS_IN_METERS * (deg_rad(lat2) - deg_rad(lat1)).abs()
+}
+
+/// 使用半正矢公式计算两点间的距离，单位为米
+pub fn distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let lon1r = deg_rad(lon1);
+    let lon2r = deg_rad(lon2);
+    let v = ((lon2r - lon1r) / 2.0).sin();
+    // 经度几乎相同时只需要计算纬度的距离
+    if v == 0.0 {
+        return lat_distance(lat1, lat2);
+    }
+    let lat1r = deg_rad(lat1);
+    let lat2r = deg_rad(lat2);
+    let u = ((lat2r - lat1r) / 2.0).sin();
+    let a = u * u + lat1r.cos() * lat2r.cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+/// 搜索的形状，尺寸使用用户指定的单位
+#[derive(Clone, Copy)]
+pub enum Shape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+/// GEOSEARCH 的搜索范围，conversion 是单位对应的米数
+pub struct GeoShape {
+    pub longitude: f64,
+    pub latitude: f64,
+    pub conversion: f64,
+    pub shape: Shape,
+}
+
+impl GeoShape {
+    /// 点在范围内时返回它到中心的距离，单位为米
+    pub fn distance_if_within(&self, longitude: f64, latitude: f64) -> Option<f64> {
+        match self.shape {
+            Shape::Radius(radius) => {
+                let distance = distance(self.longitude, self.latitude, longitude, latitude);
+                (distance <= radius * self.conversion).then_some(distance)
+            }
+            Shape::Box { width, height } => {
+                // 先检查计算量较小的纬度方向
+                if lat_distance(latitude, self.latitude) > height * self.conversion / 2.0 {
+                    return None;
+                }
+                if distance(longitude, latitude, self.longitude, latitude) > width * self.conversion / 2.0 {
+                    return None;
+                }
+                Some(distance(self.longitude, self.latitude, longitude, latitude))
+            }
+        }
+    }
+
+    /// 返回 [最小经度, 最小纬度, 最大经度, 最大纬度]
+    fn bounding_box(&self) -> [f64; 4] {
+        let (half_width, half_height) = match self.shape {
+            Shape::Radius(radius) => (radius, radius),
+            Shape::Box { width, height } => (width / 2.0, height / 2.0),
+        };
+        let width = self.conversion * half_width;
+        let height = self.conversion * half_height;
+        let lat_delta = rad_deg(height / EARTH_RADIUS_IN_METERS);
+        let long_delta_top = rad_deg(width / EARTH_RADIUS_IN_METERS / deg_rad(self.latitude + lat_delta).cos());
+        let long_delta_bottom = rad_deg(width / EARTH_RADIUS_IN_METERS / deg_rad(self.latitude - lat_delta).cos());
+        // 南北半球的方向相反，选择不同的点作为经度的边界
+        let long_delta = if self.latitude < 0.0 {
+            long_delta_bottom
+        } else {
+            long_delta_top
+        };
+        [
+            self.longitude - long_delta,
+            self.latitude - lat_delta,
+            self.longitude + long_delta,
+            self.latitude + lat_delta,
+        ]
+    }
+
+    /// 返回需要搜索的 geohash 区域：中心区域和周围的 8 个区域，去掉不可能有结果的区域，
+    /// 顺序与 Redis 一致，因此不排序时返回的结果顺序也一致
+    pub fn search_areas(&self) -> Vec<HashBits> {
+        let [min_lon, min_lat, max_lon, max_lat] = self.bounding_box();
+        let radius = match self.shape {
+            Shape::Radius(radius) => radius,
+            // 中心到角的距离
+            Shape::Box { width, height } => ((width / 2.0).powi(2) + (height / 2.0).powi(2)).sqrt(),
+        } * self.conversion;
+        let mut step = estimate_steps(radius, self.latitude);
+        let mut hash = encode_wgs84(self.longitude, self.latitude, step).unwrap_or_default();
+        let mut neighbors = Neighbors::new(hash);
+
+        // 搜索范围靠近区域的边缘时，估算的精度可能不足以用相邻的区域覆盖整个范围
+        let decrease_step = decode(neighbors.north).latitude.max < max_lat
+            || decode(neighbors.south).latitude.min > min_lat
+            || decode(neighbors.east).longitude.max < max_lon
+            || decode(neighbors.west).longitude.min > min_lon;
+        if step > 1 && decrease_step {
+            step -= 1;
+            hash = encode_wgs84(self.longitude, self.latitude, step).unwrap_or_default();
+            neighbors = Neighbors::new(hash);
+        }
+        let area = decode(hash);
+
+        let zero = HashBits::default();
+        if step >= 2 {
+            if area.latitude.min < min_lat {
+                neighbors.south = zero;
+                neighbors.south_west = zero;
+                neighbors.south_east = zero;
+            }
+            if area.latitude.max > max_lat {
+                neighbors.north = zero;
+                neighbors.north_east = zero;
+                neighbors.north_west = zero;
+            }
+            if area.longitude.min < min_lon {
+                neighbors.west = zero;
+                neighbors.south_west = zero;
+                neighbors.north_west = zero;
+            }
+            if area.longitude.max > max_lon {
+                neighbors.east = zero;
+                neighbors.south_east = zero;
+                neighbors.north_east = zero;
+            }
+        }
+
+        let all = [
+            hash,
+            neighbors.north,
+            neighbors.south,
+            neighbors.east,
+            neighbors.west,
+            neighbors.north_east,
+            neighbors.north_west,
+            neighbors.south_east,
+            neighbors.south_west,
+        ];
+        let mut areas = Vec::new();
+        let mut last_processed = 0;
+        for (i, area) in all.iter().enumerate() {
+            if area.is_zero() {
+                continue;
+            }
+            // 半径很大时相邻的区域可能相同，跳过与上一个处理过的区域相同的区域。
+            // 与 Redis 一样，上一个处理的是中心区域时不做比较
+            if last_processed != 0 && *area == all[last_processed] {
+                continue;
+            }
+            areas.push(*area);
+            last_processed = i;
+        }
+        areas
+    }
+}
+
+struct Neighbors {
+    north: HashBits,
+    south: HashBits,
+    east: HashBits,
+    west: HashBits,
+    north_east: HashBits,
+    north_west: HashBits,
+    south_east: HashBits,
+    south_west: HashBits,
+}
+
+impl Neighbors {
+    fn new(hash: HashBits) -> Self {
+        Self {
+            north: hash.neighbor(0, 1),
+            south: hash.neighbor(0, -1),
+            east: hash.neighbor(1, 0),
+            west: hash.neighbor(-1, 0),
+            north_east: hash.neighbor(1, 1),
+            north_west: hash.neighbor(-1, 1),
+            south_east: hash.neighbor(1, -1),
+            south_west: hash.neighbor(-1, -1),
+        }
+    }
+}
+
+/// 根据搜索半径估算 geohash 的精度
+fn estimate_steps(mut range_meters: f64, latitude: f64) -> u8 {
+    if range_meters == 0.0 {
+        return GEO_STEP_MAX;
+    }
+    let mut step: i32 = 1;
+    while range_meters < MERCATOR_MAX {
+        range_meters *= 2.0;
+        step += 1;
+    }
+    // 保证大多数情况下范围能被覆盖
+    step -= 2;
+    // 靠近两极时需要更大的区域
+    if !(-66.0..=66.0).contains(&latitude) {
+        step -= 1;
+        if !(-80.0..=80.0).contains(&latitude) {
+            step -= 1;
+        }
+    }
+    step.clamp(1, GEO_STEP_MAX as i32) as u8
+}
@@ -0,0 +1,129 @@
+/// glob 风格的模式匹配，行为与 Redis 的 stringmatchlen 完全一致：
+/// 支持 `*`、`?`、`[abc]`、`[^a]`、`[a-z]` 和 `\` 转义，nocase 为 true 时忽略大小写
+pub fn string_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let mut skip_longer_matches = false;
+    match_impl(pattern, string, nocase, &mut skip_longer_matches, 0)
+}
+
+/// 与 C 的 tolower 一致，只转换 ASCII 字母
+fn to_lower(c: i32) -> i32 {
+    if (b'A' as i32..=b'Z' as i32).contains(&c) {
+        c + 32
+    } else {
+        c
+    }
+}
+
+fn equal(a: u8, b: u8, nocase: bool) -> bool {
+    if nocase {
+        a.eq_ignore_ascii_case(&b)
+    } else {
+        a == b
+    }
+}
+
+fn match_impl(pattern: &[u8], string: &[u8], nocase: bool, skip_longer_matches: &mut bool, nesting: usize) -> bool {
+    // 防止恶意的模式导致过深的递归
+    if nesting > 1000 {
+        return false;
+    }
+    // 越界时与 C 字符串一样读到结尾的 0
+    let pat = |i: usize| pattern.get(i).copied().unwrap_or(0);
+    let (mut p, mut s) = (0, 0);
+    while p < pattern.len() && s < string.len() {
+        match pattern[p] {
+            b'*' => {
+                while pat(p + 1) == b'*' {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                while s < string.len() {
+                    if match_impl(&pattern[p + 1..], &string[s..], nocase, skip_longer_matches, nesting + 1) {
+                        return true;
+                    }
+                    if *skip_longer_matches {
+                        return false;
+                    }
+                    s += 1;
+                }
+                // 剩余的模式无法从字符串的任何位置开始匹配，
+                // 前面的 `*` 匹配更长的子串也不可能成功，可以提前结束
+                *skip_longer_matches = true;
+                return false;
+            }
+            b'?' => s += 1,
+            b'[' => {
+                p += 1;
+                let not = pat(p) == b'^';
+                if not {
+                    p += 1;
+                }
+                let mut matched = false;
+                loop {
+                    let remaining = pattern.len() - p;
+                    if pat(p) == b'\\' && remaining >= 2 {
+                        p += 1;
+                        if pattern[p] == string[s] {
+                            matched = true;
+                        }
+                    } else if pat(p) == b']' {
+                        break;
+                    } else if remaining == 0 {
+                        // 缺少 `]`，回退一位，交给下面统一前进
+                        p -= 1;
+                        break;
+                    } else if remaining >= 3 && pattern[p + 1] == b'-' {
+                        // 与 C 一样按有符号 char 比较
+                        let mut start = pattern[p] as i8 as i32;
+                        let mut end = pattern[p + 2] as i8 as i32;
+                        let mut c = string[s] as i8 as i32;
+                        if start > end {
+                            std::mem::swap(&mut start, &mut end);
+                        }
+                        if nocase {
+                            start = to_lower(start);
+                            end = to_lower(end);
+                            c = to_lower(c);
+                        }
+                        p += 2;
+                        if c >= start && c <= end {
+                            matched = true;
+                        }
+                    } else if equal(pattern[p], string[s], nocase) {
+                        matched = true;
+                    }
+                    p += 1;
+                }
+                if not {
+                    matched = !matched;
+                }
+                if !matched {
+                    return false;
+                }
+                s += 1;
+            }
+            c => {
+                let c = if c == b'\\' && pattern.len() - p >= 2 {
+                    p += 1;
+                    pattern[p]
+                } else {
+                    c
+                };
+                if !equal(c, string[s], nocase) {
+                    return false;
+                }
+                s += 1;
+            }
+        }
+        p += 1;
+        if s == string.len() {
+            while pat(p) == b'*' {
+                p += 1;
+            }
+            break;
+        }
+    }
+    p == pattern.len() && s == string.len()
+}
@@ -2,18 +2,25 @@
 use std::env;
 use std::path::Path;
 
+use bytes::{Bytes, BytesMut};
 use clap::{command, Parser};
-use resp::{RespParser, RespType};
-use time::OffsetDateTime;
+use resp::{RequestParser, RespError, RespType};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Instant;
 
+mod aof;
 mod commands;
 mod config;
+mod geohash;
+mod glob;
+mod random;
 mod rdb;
 mod resp;
 mod storage;
 
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
 #[derive(Parser)]
 #[command(name = "Rust-Redis", version = "0.1.0", author = "Your Name")]
 struct Args {
@@ -28,29 +35,110 @@ struct Args {
 
     #[arg(long)]
     replicaof: Option<String>,
+
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    databases: Option<u32>,
+
+    #[arg(long)]
+    save: Option<String>,
+
+    #[arg(long, value_parser = ["yes", "no"])]
+    appendonly: Option<String>,
+
+    #[arg(long)]
+    appendfilename: Option<String>,
+
+    #[arg(long)]
+    appenddirname: Option<String>,
+
+    #[arg(long, value_parser = ["always", "everysec", "no"])]
+    appendfsync: Option<String>,
+
+    #[arg(long, value_parser = ["yes", "no"])]
+    aof_load_truncated: Option<String>,
+
+    #[arg(long, value_parser = ["yes", "no"])]
+    aof_use_rdb_preamble: Option<String>,
+
+    #[arg(long)]
+    auto_aof_rewrite_percentage: Option<u32>,
+
+    #[arg(long)]
+    auto_aof_rewrite_min_size: Option<String>,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
-    if args.dir.is_some() {
-        config::set("dir", &args.dir.unwrap()).await;
+    let dir = args.dir.unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().into_owned());
+    config::set("dir", &dir).await;
+    let dbfilename = args.dbfilename.unwrap_or_else(|| rdb::DEFAULT_DBFILENAME.to_string());
+    config::set("dbfilename", &dbfilename).await;
+    if let Some(replicaof) = args.replicaof {
+        config::set("replicaof", &replicaof).await;
     }
-    if args.dbfilename.is_some() {
-        config::set("dbfilename", &args.dbfilename.unwrap()).await;
-    }
-    if args.replicaof.is_some() {
-        config::set("replicaof", &args.replicaof.unwrap()).await;
+    let databases = args.databases.map_or(storage::DEFAULT_DATABASES, |databases| databases as usize);
+    config::set("databases", &databases.to_string()).await;
+    storage::init(databases);
+    let save = args.save.unwrap_or_else(|| rdb::DEFAULT_SAVE_PARAMS.to_string());
+    if rdb::parse_save_params(&save).is_none() {
+        println!("Invalid save parameters: {}", save);
+        std::process::exit(1);
     }
+    config::set("save", &save).await;
+    let appendonly = args.appendonly.unwrap_or_else(|| "no".to_string());
+    config::set("appendonly", &appendonly).await;
+    let appendfilename = args.appendfilename.unwrap_or_else(|| aof::DEFAULT_APPENDFILENAME.to_string());
+    config::set("appendfilename", &appendfilename).await;
+    let appendfsync = args.appendfsync.unwrap_or_else(|| "everysec".to_string());
+    config::set("appendfsync", &appendfsync).await;
+    let aof_load_truncated = args.aof_load_truncated.unwrap_or_else(|| "yes".to_string());
+    config::set("aof-load-truncated", &aof_load_truncated).await;
+    let appenddirname = args.appenddirname.unwrap_or_else(|| aof::DEFAULT_APPENDDIRNAME.to_string());
+    config::set("appenddirname", &appenddirname).await;
+    let aof_use_rdb_preamble = args.aof_use_rdb_preamble.unwrap_or_else(|| "yes".to_string());
+    config::set("aof-use-rdb-preamble", &aof_use_rdb_preamble).await;
+    let auto_aof_rewrite_percentage = args
+        .auto_aof_rewrite_percentage
+        .map_or_else(|| aof::DEFAULT_AUTO_REWRITE_PERCENTAGE.to_string(), |percentage| percentage.to_string());
+    config::set("auto-aof-rewrite-percentage", &auto_aof_rewrite_percentage).await;
+    // 与 Redis 一致，CONFIG GET 返回换算后的字节数
+    let auto_aof_rewrite_min_size = args
+        .auto_aof_rewrite_min_size
+        .unwrap_or_else(|| aof::DEFAULT_AUTO_REWRITE_MIN_SIZE.to_string());
+    let auto_aof_rewrite_min_size = match config::parse_memory(&auto_aof_rewrite_min_size) {
+        Some(size) => size,
+        None => {
+            println!("Invalid auto-aof-rewrite-min-size: {}", auto_aof_rewrite_min_size);
+            std::process::exit(1);
+        }
+    };
+    config::set("auto-aof-rewrite-min-size", &auto_aof_rewrite_min_size.to_string()).await;
     let port = args.port.map_or(6379, |port| port);
     let listener = TcpListener::bind(format!("127.0.0.1:{}", port))
         .await
         .unwrap();
-    load_data_from_rdb().await;
+    // 开启 AOF 时只从 AOF 加载数据，与 Redis 一致
+    if appendonly == "yes" {
+        load_data_from_aof(aof_load_truncated == "yes").await;
+        let fsync = aof::Fsync::parse(&appendfsync).unwrap();
+        if let Err(e) = aof::open(fsync).await {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+        tokio::spawn(aof::fsync_cron());
+        tokio::spawn(aof::rewrite_cron());
+    } else {
+        load_data_from_rdb().await;
+    }
+    rdb::reset_dirty();
+    rdb::reset_lastsave();
+    tokio::spawn(storage::active_expire());
+    tokio::spawn(rdb::save_cron());
     loop {
         match listener.accept().await {
             Ok((stream, _)) => {
-                tokio::spawn(handle_connection(stream));
+                tokio::spawn(storage::with_selected_db(handle_connection(stream)));
             }
             Err(e) => {
                 println!("Error: {}", e);
@@ -60,41 +148,57 @@ async fn main() {
 }
 
 async fn handle_connection(mut stream: TcpStream) {
+    let mut buffer = BytesMut::with_capacity(READ_BUFFER_SIZE);
+    let mut parser = RequestParser::new();
     loop {
-        let mut buffer = [0; 1024];
-        let n = stream.read(&mut buffer).await.unwrap();
-        if n == 0 {
-            break;
+        buffer.reserve(READ_BUFFER_SIZE);
+        match stream.read_buf(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
         }
-        let mut parser = RespParser::new(&buffer[..n]);
-        match parser.parse() {
-            Ok(resp) => match execute_command(resp).await {
-                Ok(response) => {
-                    stream.write_all(&response.serialize()).await.unwrap();
+
+        // 一次读取可能包含多条（流水线）命令，也可能只有半条命令
+        let mut output = Vec::new();
+        let mut closing = false;
+        loop {
+            match parser.parse(&mut buffer) {
+                Ok(resp) => {
+                    let response = match execute_command(resp).await {
+                        Ok(response) => response,
+                        Err(err) => RespType::SimpleError(err),
+                    };
+                    output.extend(response.serialize());
                 }
-                Err(err) => {
-                    stream
-                        .write_all(&RespType::SimpleError(err).serialize())
-                        .await
-                        .unwrap();
+                Err(RespError::Incomplete) => break,
+                Err(RespError::Invalid(e)) => {
+                    output.extend(
+                        RespType::SimpleError(format!("ERR Protocol error: {}", e)).serialize(),
+                    );
+                    closing = true;
+                    break;
                 }
-            },
-
-            Err(e) => {
-                stream
-                    .write_all(&RespType::SimpleError(e).serialize())
-                    .await
-                    .unwrap();
             }
         }
+        if !output.is_empty() && stream.write_all(&output).await.is_err() {
+            break;
+        }
+        if closing {
+            break;
+        }
+        if parser.query_buffer_exceeded(&buffer) {
+            println!("Closing client that reached max query buffer length");
+            break;
+        }
     }
 }
 
 async fn execute_command(resp: RespType) -> Result<RespType, String> {
     match resp {
         RespType::Array(Some(elements)) => {
-            let command = match &elements[0] {
-                RespType::BulkString(Some(cmd)) => cmd.to_uppercase(),
+            let command = match elements.first() {
+                Some(RespType::BulkString(Some(cmd))) => {
+                    String::from_utf8_lossy(cmd).to_uppercase()
+                }
                 _ => return Err("Invalid command format".to_string()),
             };
             let args = elements[1..]
@@ -103,26 +207,270 @@ async fn execute_command(resp: RespType) -> Result<RespType, String> {
                     RespType::BulkString(Some(value)) => Some(value.clone()),
                     _ => None,
                 })
-                .collect::<Vec<String>>();
-            match command.as_str() {
-                "ECHO" => commands::echo(args),
-                "SET" => commands::set(args).await,
-                "GET" => commands::get(args).await,
-                "PING" => Ok(RespType::SimpleString("PONG".to_string())),
-                "CONFIG" => match args[0].to_uppercase().as_ref() {
-                    "GET" => commands::config_get(args).await,
-                    _ => Err(format!("Unknown config command: {}", args[1])),
-                },
-                "KEYS" => commands::keys(args).await,
-                "SAVE" => commands::save().await,
-                "INFO" => commands::info().await,
-                _ => Err(format!("Unknown command: {}", command)),
+                .collect::<Vec<Bytes>>();
+            if !commands::is_write(&command) {
+                return dispatch(&command, args).await;
+            }
+            if command == "XREADGROUP" {
+                if let Some((args, deadline)) = commands::split_block(&args) {
+                    return execute_blocking_xreadgroup(args, deadline).await;
+                }
+            }
+            let mut aof = aof::lock().await;
+            let (result, dirty) = rdb::count_dirty(dispatch(&command, args.clone())).await;
+            if let (Ok(reply), true) = (&result, dirty > 0) {
+                propagate(aof.as_deref_mut(), &command, &args, reply).await;
             }
+            result
         }
         _ => Ok(RespType::SimpleString("Invalid command".to_string())),
     }
 }
 
+/// 写命令执行成功并且确实修改了数据时追加到 AOF
+async fn propagate(aof: Option<&mut aof::AofState>, command: &str, args: &[Bytes], reply: &RespType) {
+    if let Some(aof) = aof {
+        if let Err(e) = aof.feed(storage::selected_db(), command, args, reply).await {
+            println!("Error writing to the AOF file: {}", e);
+        }
+    }
+}
+
+/// 阻塞的 XREADGROUP 等待期间不能持有 AOF 的锁，否则 XADD 无法执行。不持锁等待新条目，
+/// 再持锁以非阻塞的方式重新读取，这样读取和写入 AOF 之间不会穿插其他写命令或者重写的快照
+async fn execute_blocking_xreadgroup(args: Vec<Bytes>, deadline: Option<Instant>) -> Result<RespType, String> {
+    loop {
+        let notified = storage::STREAM_ADDED.notified();
+        let mut aof = aof::lock().await;
+        let (result, dirty) = rdb::count_dirty(dispatch("XREADGROUP", args.clone())).await;
+        let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if !matches!(result, Ok(RespType::Array(None))) || timed_out {
+            if let (Ok(reply), true) = (&result, dirty > 0) {
+                propagate(aof.as_deref_mut(), "XREADGROUP", &args, reply).await;
+            }
+            return result;
+        }
+        drop(aof);
+        commands::wait_for_entries(deadline, notified).await;
+    }
+}
+
+async fn dispatch(command: &str, args: Vec<Bytes>) -> Result<RespType, String> {
+    match command {
+        "ECHO" => commands::echo(args),
+        "SET" => commands::set(args).await,
+        "GET" => commands::get(args).await,
+        "APPEND" => commands::append(args).await,
+        "STRLEN" => commands::strlen(args).await,
+        "GETRANGE" => commands::getrange(args).await,
+        "SETRANGE" => commands::setrange(args).await,
+        "GETSET" => commands::getset(args).await,
+        "GETDEL" => commands::getdel(args).await,
+        "GETEX" => commands::getex(args).await,
+        "SETNX" => commands::setnx(args).await,
+        "SETEX" => commands::setex(args).await,
+        "PSETEX" => commands::psetex(args).await,
+        "MSET" => commands::mset(args).await,
+        "MSETNX" => commands::msetnx(args).await,
+        "MGET" => commands::mget(args).await,
+        "LCS" => commands::lcs(args).await,
+        "INCR" => commands::incr(args).await,
+        "DECR" => commands::decr(args).await,
+        "INCRBY" => commands::incrby(args).await,
+        "DECRBY" => commands::decrby(args).await,
+        "INCRBYFLOAT" => commands::incrbyfloat(args).await,
+        "SETBIT" => commands::setbit(args).await,
+        "GETBIT" => commands::getbit(args).await,
+        "BITCOUNT" => commands::bitcount(args).await,
+        "BITPOS" => commands::bitpos(args).await,
+        "BITOP" => commands::bitop(args).await,
+        "BITFIELD" => commands::bitfield(args).await,
+        "BITFIELD_RO" => commands::bitfield_ro(args).await,
+        "PFADD" => commands::pfadd(args).await,
+        "PFCOUNT" => commands::pfcount(args).await,
+        "PFMERGE" => commands::pfmerge(args).await,
+        "GEOADD" => commands::geoadd(args).await,
+        "GEOPOS" => commands::geopos(args).await,
+        "GEODIST" => commands::geodist(args).await,
+        "GEOHASH" => commands::geohash(args).await,
+        "GEOSEARCH" => commands::geosearch(args).await,
+        "GEOSEARCHSTORE" => commands::geosearchstore(args).await,
+        "PING" => Ok(RespType::SimpleString("PONG".to_string())),
+        "CONFIG" => match args.first().map(|sub| commands::upper(sub)).as_deref() {
+            Some("GET") => commands::config_get(args).await,
+            Some(sub) => Err(format!("Unknown config command: {}", sub)),
+            None => Err(commands::wrong_args("config")),
+        },
+        "KEYS" => commands::keys(args).await,
+        "SCAN" => commands::scan(args).await,
+        "HSCAN" => commands::hscan(args).await,
+        "SSCAN" => commands::sscan(args).await,
+        "ZSCAN" => commands::zscan(args).await,
+        "DEL" => commands::del(args).await,
+        "UNLINK" => commands::unlink(args).await,
+        "EXISTS" => commands::exists(args).await,
+        "TOUCH" => commands::touch(args).await,
+        "TYPE" => commands::key_type(args).await,
+        "RENAME" => commands::rename(args).await,
+        "RENAMENX" => commands::renamenx(args).await,
+        "COPY" => commands::copy(args).await,
+        "RANDOMKEY" => commands::randomkey(args).await,
+        "DBSIZE" => commands::dbsize(args).await,
+        "SELECT" => commands::select(args),
+        "MOVE" => commands::move_key(args).await,
+        "SWAPDB" => commands::swapdb(args).await,
+        "FLUSHDB" => commands::flushdb(args).await,
+        "FLUSHALL" => commands::flushall(args).await,
+        "EXPIRE" => commands::expire(args).await,
+        "PEXPIRE" => commands::pexpire(args).await,
+        "EXPIREAT" => commands::expireat(args).await,
+        "PEXPIREAT" => commands::pexpireat(args).await,
+        "TTL" => commands::ttl(args).await,
+        "PTTL" => commands::pttl(args).await,
+        "EXPIRETIME" => commands::expiretime(args).await,
+        "PEXPIRETIME" => commands::pexpiretime(args).await,
+        "PERSIST" => commands::persist(args).await,
+        "LPUSH" => commands::lpush(args).await,
+        "RPUSH" => commands::rpush(args).await,
+        "LPUSHX" => commands::lpushx(args).await,
+        "RPUSHX" => commands::rpushx(args).await,
+        "LPOP" => commands::lpop(args).await,
+        "RPOP" => commands::rpop(args).await,
+        "LLEN" => commands::llen(args).await,
+        "LRANGE" => commands::lrange(args).await,
+        "LINDEX" => commands::lindex(args).await,
+        "LSET" => commands::lset(args).await,
+        "LREM" => commands::lrem(args).await,
+        "LTRIM" => commands::ltrim(args).await,
+        "LINSERT" => commands::linsert(args).await,
+        "LMOVE" => commands::lmove(args).await,
+        "RPOPLPUSH" => commands::rpoplpush(args).await,
+        "HSET" => commands::hset(args).await,
+        "HMSET" => commands::hmset(args).await,
+        "HSETNX" => commands::hsetnx(args).await,
+        "HGET" => commands::hget(args).await,
+        "HMGET" => commands::hmget(args).await,
+        "HDEL" => commands::hdel(args).await,
+        "HGETALL" => commands::hgetall(args).await,
+        "HKEYS" => commands::hkeys(args).await,
+        "HVALS" => commands::hvals(args).await,
+        "HLEN" => commands::hlen(args).await,
+        "HEXISTS" => commands::hexists(args).await,
+        "HSTRLEN" => commands::hstrlen(args).await,
+        "HINCRBY" => commands::hincrby(args).await,
+        "HINCRBYFLOAT" => commands::hincrbyfloat(args).await,
+        "HRANDFIELD" => commands::hrandfield(args).await,
+        "SADD" => commands::sadd(args).await,
+        "SREM" => commands::srem(args).await,
+        "SMEMBERS" => commands::smembers(args).await,
+        "SISMEMBER" => commands::sismember(args).await,
+        "SMISMEMBER" => commands::smismember(args).await,
+        "SCARD" => commands::scard(args).await,
+        "SMOVE" => commands::smove(args).await,
+        "SPOP" => commands::spop(args).await,
+        "SRANDMEMBER" => commands::srandmember(args).await,
+        "SINTER" => commands::sinter(args).await,
+        "SUNION" => commands::sunion(args).await,
+        "SDIFF" => commands::sdiff(args).await,
+        "SINTERSTORE" => commands::sinterstore(args).await,
+        "SUNIONSTORE" => commands::sunionstore(args).await,
+        "SDIFFSTORE" => commands::sdiffstore(args).await,
+        "SINTERCARD" => commands::sintercard(args).await,
+        "ZADD" => commands::zadd(args).await,
+        "ZINCRBY" => commands::zincrby(args).await,
+        "ZREM" => commands::zrem(args).await,
+        "ZCARD" => commands::zcard(args).await,
+        "ZSCORE" => commands::zscore(args).await,
+        "ZMSCORE" => commands::zmscore(args).await,
+        "ZRANK" => commands::zrank(args).await,
+        "ZREVRANK" => commands::zrevrank(args).await,
+        "ZCOUNT" => commands::zcount(args).await,
+        "ZLEXCOUNT" => commands::zlexcount(args).await,
+        "ZRANGE" => commands::zrange(args).await,
+        "ZREVRANGE" => commands::zrevrange(args).await,
+        "ZRANGEBYSCORE" => commands::zrangebyscore(args).await,
+        "ZREVRANGEBYSCORE" => commands::zrevrangebyscore(args).await,
+        "ZRANGEBYLEX" => commands::zrangebylex(args).await,
+        "ZREVRANGEBYLEX" => commands::zrevrangebylex(args).await,
+        "ZRANGESTORE" => commands::zrangestore(args).await,
+        "ZREMRANGEBYRANK" => commands::zremrangebyrank(args).await,
+        "ZREMRANGEBYSCORE" => commands::zremrangebyscore(args).await,
+        "ZREMRANGEBYLEX" => commands::zremrangebylex(args).await,
+        "ZPOPMIN" => commands::zpopmin(args).await,
+        "ZPOPMAX" => commands::zpopmax(args).await,
+        "ZUNION" => commands::zunion(args).await,
+        "ZINTER" => commands::zinter(args).await,
+        "ZDIFF" => commands::zdiff(args).await,
+        "ZUNIONSTORE" => commands::zunionstore(args).await,
+        "ZINTERSTORE" => commands::zinterstore(args).await,
+        "ZDIFFSTORE" => commands::zdiffstore(args).await,
+        "XADD" => commands::xadd(args).await,
+        "XLEN" => commands::xlen(args).await,
+        "XRANGE" => commands::xrange(args).await,
+        "XREVRANGE" => commands::xrevrange(args).await,
+        "XDEL" => commands::xdel(args).await,
+        "XTRIM" => commands::xtrim(args).await,
+        "XSETID" => commands::xsetid(args).await,
+        "XREAD" => commands::xread(args).await,
+        "XGROUP" => commands::xgroup(args).await,
+        "XREADGROUP" => commands::xreadgroup(args).await,
+        "XACK" => commands::xack(args).await,
+        "XPENDING" => commands::xpending(args).await,
+        "XCLAIM" => commands::xclaim(args).await,
+        "XAUTOCLAIM" => commands::xautoclaim(args).await,
+        "XINFO" => commands::xinfo(args).await,
+        "OBJECT" => commands::object(args).await,
+        "SAVE" => commands::save(args).await,
+        "BGSAVE" => commands::bgsave(args).await,
+        "BGREWRITEAOF" => commands::bgrewriteaof(args).await,
+        "LASTSAVE" => commands::lastsave(args).await,
+        "INFO" => commands::info(args).await,
+        _ => Err(format!("Unknown command: {}", command)),
+    }
+}
+
+/// 按清单依次加载基础文件和增量文件。基础文件可能是 RDB 格式，其余文件通过 execute_command 重放，
+/// 命令执行出错说明文件已损坏，直接退出。只有最后一个文件允许末尾不完整
+async fn load_data_from_aof(allow_truncated: bool) {
+    let paths = match aof::load_manifest().await {
+        Ok(paths) => paths,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    for (i, path) in paths.iter().enumerate() {
+        let is_rdb = match tokio::fs::File::open(path).await {
+            Ok(mut file) => {
+                let mut magic = [0; 5];
+                file.read_exact(&mut magic).await.is_ok() && &magic == b"REDIS"
+            }
+            Err(_) => false,
+        };
+        if is_rdb {
+            load_rdb_file(path).await;
+            continue;
+        }
+        let last = i + 1 == paths.len();
+        let commands = match aof::read_commands(path, allow_truncated && last).await {
+            Ok(commands) => commands,
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        storage::with_selected_db(async {
+            for command in commands {
+                if let Err(e) = execute_command(command).await {
+                    println!("Error replaying the append only file {}: {}. Exiting.", path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        })
+        .await;
+    }
+}
+
 async fn load_data_from_rdb() {
     let dir = match config::get("dir").await {
         Some(dir) => dir,
@@ -134,25 +482,40 @@ async fn load_data_from_rdb() {
     };
     let path = Path::new(&dir).join(&dbfilename);
     if path.exists() {
-        let buf = tokio::fs::read(&path).await.unwrap();
-        rdb::RdbParser::new(buf, |_, key, value, expire| {
-            tokio::spawn(async move {
-                println!("key: {}, value: {}, expire: {:?}", key, value, expire);
-
-                let expires = match expire {
-                    Some(expires_at) => {
-                        let time = OffsetDateTime::from_unix_timestamp_nanos(
-                            expires_at as i128 * 1_000_000,
-                        )
-                        .unwrap();
-                        Some(time)
-                    }
-                    None => None,
-                };
-                storage::set(&key, &value, expires).await;
-            });
-        })
-        .parse()
-        .unwrap();
+        load_rdb_file(&path).await;
+    }
+}
+
+/// 加载期间持有所有数据库的写锁，逐个插入解析出的键
+async fn load_rdb_file(path: &Path) {
+    let buf = match tokio::fs::read(path).await {
+        Ok(buf) => buf,
+        Err(e) => {
+            println!("Fatal error loading the DB: {}: {}. Exiting.", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let mut dbs = storage::write_all().await;
+    let mut keys = 0;
+    let mut skipped = 0;
+    let result = rdb::RdbParser::new(buf, |db, key, item| match dbs.get_mut(db) {
+        Some(store) => {
+            store.insert(key, item);
+            keys += 1;
+        }
+        None => skipped += 1,
+    })
+    .parse();
+    if skipped > 0 {
+        println!(
+            "Data file was created with a server configured to handle more than {} databases, {} keys skipped",
+            storage::db_count(),
+            skipped
+        );
+    }
+    if let Err(e) = result {
+        println!("Fatal error loading the DB: {}. Exiting.", e);
+        std::process::exit(1);
     }
+    println!("Done loading RDB, keys loaded: {}", keys);
 }
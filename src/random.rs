@@ -0,0 +1,43 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0x9E37_79B9_7F4A_7C15);
+    hasher.finish() | 1
+}
+
+/// xorshift64* 伪随机数
+pub fn next_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+/// [0, n) 范围内的随机数，n 必须大于 0
+pub fn below(n: usize) -> usize {
+    (next_u64() % n as u64) as usize
+}
+
+/// 不重复地随机挑选最多 count 个元素
+pub fn sample<T>(mut items: Vec<T>, count: usize) -> Vec<T> {
+    let count = count.min(items.len());
+    for i in 0..count {
+        let j = i + below(items.len() - i);
+        items.swap(i, j);
+    }
+    items.truncate(count);
+    items
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, VecDeque},
     fmt::{self, Display, Formatter},
     io::{Cursor, Read},
     path::PathBuf,
@@ -7,15 +7,34 @@ use std::{
 };
 
 use byteorder::{LittleEndian, ReadBytesExt};
+use bytes::Bytes;
 // use tokio::io::AsyncReadExt;
 
+use crate::storage::{
+    self, Consumer, ConsumerGroup, Hash, Item, PendingEntry, Set, SortedSet, Stream, StreamEntry, StreamId, Value,
+};
+
+mod crc64;
+mod listpack;
+mod save;
+mod writer;
+mod ziplist;
+
+pub use save::*;
+pub use writer::RdbWriter;
+
 enum OpCode {
-    AUX = 0xFA,
-    RESIZEDB = 0xFB,
-    EXPIRETIMEMS = 0xFC,
-    EXPIRETIME = 0xFD,
-    SELECTDB = 0xFE,
-    EOF = 0xFF,
+    SlotInfo = 0xF4,
+    Function2 = 0xF5,
+    ModuleAux = 0xF7,
+    Idle = 0xF8,
+    Freq = 0xF9,
+    Aux = 0xFA,
+    ResizeDb = 0xFB,
+    ExpireTimeMs = 0xFC,
+    ExpireTime = 0xFD,
+    SelectDb = 0xFE,
+    Eof = 0xFF,
     Unknown,
 }
 enum RdValueType {
@@ -24,31 +43,48 @@ enum RdValueType {
     Set = 2,
     SortedSet = 3,
     Hash = 4,
+    SortedSet2 = 5,
+    ModulePreGa = 6,
+    Module2 = 7,
     ZipMap = 9,
     ZipList = 10,
     IntSet = 11,
     SortedSetInZipList = 12,
     HashMapInZipList = 13,
     ZipInQuickList = 14,
+    StreamListpacks = 15,
+    HashListpack = 16,
+    SortedSetListpack = 17,
+    QuickList2 = 18,
+    StreamListpacks2 = 19,
+    SetListpack = 20,
+    StreamListpacks3 = 21,
     Unknown,
 }
 enum RdLength {
     Integer(u8),
-    Len(u32),
-    LZF,
+    Len(u64),
+    Lzf,
 }
 #[derive(Debug)]
-enum RdbString {
+pub enum RdbString {
     String(Vec<u8>),
     Integer(Vec<u8>),
-    LZF(Vec<u8>),
+    Lzf(Vec<u8>),
 }
-impl ToString for RdbString {
-    fn to_string(&self) -> String {
+impl RdbString {
+    pub fn into_bytes(self) -> Vec<u8> {
         match self {
-            RdbString::String(s) => String::from_utf8_lossy(s).to_string(),
-            RdbString::Integer(i) => String::from_utf8_lossy(i).to_string(),
-            RdbString::LZF(_) => "LZF".to_string(),
+            RdbString::String(s) | RdbString::Integer(s) | RdbString::Lzf(s) => s,
+        }
+    }
+}
+impl Display for RdbString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RdbString::String(s) => write!(f, "{}", String::from_utf8_lossy(s)),
+            RdbString::Integer(i) => write!(f, "{}", String::from_utf8_lossy(i)),
+            RdbString::Lzf(s) => write!(f, "{}", String::from_utf8_lossy(s)),
         }
     }
 }
@@ -56,12 +92,17 @@ impl ToString for RdbString {
 impl From<u8> for OpCode {
     fn from(v: u8) -> Self {
         match v {
-            0xFA => OpCode::AUX,
-            0xFB => OpCode::RESIZEDB,
-            0xFC => OpCode::EXPIRETIMEMS,
-            0xFD => OpCode::EXPIRETIME,
-            0xFE => OpCode::SELECTDB,
-            0xFF => OpCode::EOF,
+            0xF4 => OpCode::SlotInfo,
+            0xF5 => OpCode::Function2,
+            0xF7 => OpCode::ModuleAux,
+            0xF8 => OpCode::Idle,
+            0xF9 => OpCode::Freq,
+            0xFA => OpCode::Aux,
+            0xFB => OpCode::ResizeDb,
+            0xFC => OpCode::ExpireTimeMs,
+            0xFD => OpCode::ExpireTime,
+            0xFE => OpCode::SelectDb,
+            0xFF => OpCode::Eof,
             _ => OpCode::Unknown,
         }
     }
@@ -74,26 +115,55 @@ impl From<u8> for RdValueType {
             2 => RdValueType::Set,
             3 => RdValueType::SortedSet,
             4 => RdValueType::Hash,
+            5 => RdValueType::SortedSet2,
+            6 => RdValueType::ModulePreGa,
+            7 => RdValueType::Module2,
             9 => RdValueType::ZipMap,
             10 => RdValueType::ZipList,
             11 => RdValueType::IntSet,
             12 => RdValueType::SortedSetInZipList,
             13 => RdValueType::HashMapInZipList,
             14 => RdValueType::ZipInQuickList,
+            15 => RdValueType::StreamListpacks,
+            16 => RdValueType::HashListpack,
+            17 => RdValueType::SortedSetListpack,
+            18 => RdValueType::QuickList2,
+            19 => RdValueType::StreamListpacks2,
+            20 => RdValueType::SetListpack,
+            21 => RdValueType::StreamListpacks3,
             _ => RdValueType::Unknown,
         }
     }
 }
-pub struct RdbParser {
+/// 能够读取的最高 RDB 版本。Redis 7.4 的版本 12 只增加了带字段过期时间的哈希类型，
+/// 其余格式不变，遇到这些类型时报错
+const RDB_MAX_VERSION: u32 = 12;
+
+/// 流条目的标志位
+const STREAM_ITEM_FLAG_DELETED: i64 = 1;
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 1 << 1;
+
+const NAN_SCORE: &str = "Zset with NAN score detected";
+
+/// quicklist 节点的类型：单个大元素或 listpack
+const QUICKLIST_NODE_CONTAINER_PLAIN: u64 = 1;
+
+/// 模块数据中每个值之前的类型标记
+const RDB_MODULE_OPCODE_EOF: u64 = 0;
+const RDB_MODULE_OPCODE_SINT: u64 = 1;
+const RDB_MODULE_OPCODE_UINT: u64 = 2;
+const RDB_MODULE_OPCODE_FLOAT: u64 = 3;
+const RDB_MODULE_OPCODE_DOUBLE: u64 = 4;
+const RDB_MODULE_OPCODE_STRING: u64 = 5;
+
+/// 逐个读取 RDB 中的键，每读到一个未过期的键就交给 handler，参数为数据库编号、键和值
+pub struct RdbParser<F> {
     input: Vec<u8>,
     pos: usize,
-    handler: fn(db: usize, key: String, value: String, expire: Option<u128>),
+    handler: F,
 }
-impl RdbParser {
-    pub fn new(
-        input: Vec<u8>,
-        handler: fn(db: usize, key: String, value: String, expire: Option<u128>),
-    ) -> Self {
+impl<F: FnMut(usize, Bytes, Item)> RdbParser<F> {
+    pub fn new(input: Vec<u8>, handler: F) -> Self {
         Self {
             input,
             pos: 0,
@@ -107,7 +177,6 @@ impl RdbParser {
             metadata: RdbMetadata {
                 info: HashMap::new(),
             },
-            // databases: vec![],
             checksum: 0,
         };
 
@@ -117,39 +186,38 @@ impl RdbParser {
         loop {
             let next_op = self.read_byte()?;
             match OpCode::from(next_op) {
-                OpCode::AUX => {
+                OpCode::Aux => {
                     let key = self.read_string()?;
                     let value = self.read_string()?;
                     rdb.metadata.info.insert(key.to_string(), value.to_string());
                 }
-                OpCode::EOF => {
-                    // TODO: checksum
-                    let _ = self.read_bytes(8)?;
+                OpCode::Eof => {
+                    // 校验和为 0 表示保存时没有计算
+                    let end = self.pos;
+                    let checksum = self.read_bytes(8)?;
+                    rdb.checksum = u64::from_le_bytes(checksum.try_into().unwrap());
+                    if rdb.checksum != 0 && rdb.checksum != crc64::crc64(0, &self.input[..end]) {
+                        return Err("Wrong RDB checksum".to_string());
+                    }
                     break;
                 }
-                OpCode::SELECTDB => {
-                    let db_number = self.read_length()?;
-                    match db_number {
-                        RdLength::Len(num) => {
-                            db_index = num as usize;
-                        }
-                        _ => return Err("Invalid db number".to_string()),
-                    };
+                OpCode::SelectDb => {
+                    db_index = self.read_len()?;
                 }
-                OpCode::RESIZEDB => {
-                    let db_size = self.read_length()?;
-                    let expires_size = self.read_length()?;
+                OpCode::ResizeDb => {
+                    let _db_size = self.read_length()?;
+                    let _expires_size = self.read_length()?;
                 }
-                OpCode::EXPIRETIME => {
+                OpCode::ExpireTime => {
                     let timestamp = self.read_bytes(4)?;
                     let mut rdr = Cursor::new(timestamp);
                     expires_at = Some(
-                        (rdr.read_u32::<LittleEndian>()
-                            .map_err(|_| "fail to read expire")?
-                            * 1000) as u128,
+                        rdr.read_u32::<LittleEndian>()
+                            .map_err(|_| "fail to read expire")? as u128
+                            * 1000,
                     );
                 }
-                OpCode::EXPIRETIMEMS => {
+                OpCode::ExpireTimeMs => {
                     let timestamp = self.read_bytes(8)?;
                     let mut rdr = Cursor::new(timestamp);
                     expires_at = Some(
@@ -157,14 +225,38 @@ impl RdbParser {
                             .map_err(|_| "fail to read expire")? as u128,
                     );
                 }
-                _ => {
+                // 淘汰策略使用的空闲时间和访问频率，不需要
+                OpCode::Idle => {
+                    self.read_len()?;
+                }
+                OpCode::Freq => {
+                    self.read_byte()?;
+                }
+                OpCode::SlotInfo => {
+                    for _ in 0..3 {
+                        self.read_len()?;
+                    }
+                }
+                OpCode::Function2 => {
+                    self.read_string()?;
+                    println!("Skipping a function library, functions are not supported");
+                }
+                OpCode::ModuleAux => {
+                    let _module_id = self.read_len()?;
+                    let _when_opcode = self.read_len()?;
+                    let _when = self.read_len()?;
+                    self.skip_module_values()?;
+                }
+                OpCode::Unknown => {
                     let value_type = next_op;
-                    let key = self.read_string()?;
+                    let key = Bytes::from(self.read_string()?.into_bytes());
                     let value = self.read_value(value_type)?;
-                    let val_str = value.to_string();
-                    (self.handler)(db_index, key.to_string(), val_str, expires_at);
-
-                    expires_at = None;
+                    let mut item = Item::new(value);
+                    item.expires = expires_at.take().map(|ms| storage::from_unix_ms(ms as i64));
+                    // 与 Redis 一致，跳过已过期的键和空的集合
+                    if !item.is_expired() && !item.value.is_empty_collection() {
+                        (self.handler)(db_index, key, item);
+                    }
                 }
             }
         }
@@ -175,19 +267,31 @@ impl RdbParser {
         let magic_bytes = self.read_bytes(5)?;
         let magic =
             String::from_utf8(magic_bytes.to_vec()).map_err(|_| "Invalid magic".to_string())?;
+        if magic != "REDIS" {
+            return Err("Wrong signature trying to load DB from file".to_string());
+        }
 
         let version_bytes = self.read_bytes(4)?;
         let version =
             String::from_utf8(version_bytes.to_vec()).map_err(|_| "Invalid version".to_string())?;
+        match version.parse::<u32>() {
+            Ok(1..=RDB_MAX_VERSION) => {}
+            _ => return Err(format!("Can't handle RDB format version {}", version)),
+        }
         Ok(RdbHeader { magic, version })
     }
     fn read_bytes(&mut self, length: usize) -> Result<&[u8], String> {
-        if self.pos + length > self.input.len() {
-            return Err("length exceeds input".to_string());
-        }
+        let end = match self.pos.checked_add(length) {
+            Some(end) if end <= self.input.len() => end,
+            _ => return Err("length exceeds input".to_string()),
+        };
         let start = self.pos;
-        self.pos += length;
-        Ok(&self.input[start..self.pos])
+        self.pos = end;
+        Ok(&self.input[start..end])
+    }
+    /// 文件中的长度不可信，预先分配的元素个数不超过剩余的字节数
+    fn capacity(&self, len: usize) -> usize {
+        len.min(self.input.len() - self.pos)
     }
     fn read_byte(&mut self) -> Result<u8, String> {
         let byte = self.read_bytes(1)?[0];
@@ -197,27 +301,35 @@ impl RdbParser {
         let byte = self.read_byte()?;
         match byte >> 6 {
             0b00 => {
-                let result = (byte & 0b0011_1111) as u32;
+                let result = (byte & 0b0011_1111) as u64;
                 Ok(RdLength::Len(result))
             }
             0b01 => {
-                let next_byte = self.read_byte()? as u32;
-                let rest = (byte & 0b1100_0000) as u32;
+                let next_byte = self.read_byte()? as u64;
+                let rest = (byte & 0b0011_1111) as u64;
                 let result = (rest << 8) | next_byte;
                 Ok(RdLength::Len(result))
             }
-            0b10 => {
-                let next_bytes = self.read_bytes(4)?;
-                let result = u32::from_be_bytes(next_bytes.try_into().unwrap());
-                Ok(RdLength::Len(result))
-            }
+            0b10 => match byte {
+                0x80 => {
+                    let next_bytes = self.read_bytes(4)?;
+                    let result = u32::from_be_bytes(next_bytes.try_into().unwrap());
+                    Ok(RdLength::Len(result as u64))
+                }
+                0x81 => {
+                    let next_bytes = self.read_bytes(8)?;
+                    let result = u64::from_be_bytes(next_bytes.try_into().unwrap());
+                    Ok(RdLength::Len(result))
+                }
+                _ => Err("Invalid length format".to_string()),
+            },
             0b11 => {
                 let format = byte & 0b0011_1111;
                 match format {
                     0 => Ok(RdLength::Integer(1)),
                     1 => Ok(RdLength::Integer(2)),
                     2 => Ok(RdLength::Integer(4)),
-                    3 => Ok(RdLength::LZF),
+                    3 => Ok(RdLength::Lzf),
                     _ => Err("Invalid length format".to_string()),
                 }
             }
@@ -232,23 +344,406 @@ impl RdbParser {
             }
             RdLength::Integer(length) => {
                 let value = self.read_bytes(length as usize)?;
-                Ok(RdbString::Integer(value.to_vec()))
+                let number = match length {
+                    1 => value[0] as i8 as i64,
+                    2 => i16::from_le_bytes(value.try_into().unwrap()) as i64,
+                    _ => i32::from_le_bytes(value.try_into().unwrap()) as i64,
+                };
+                Ok(RdbString::Integer(number.to_string().into_bytes()))
+            }
+            RdLength::Lzf => {
+                let compressed_len = self.read_len()?;
+                let uncompressed_len = self.read_len()?;
+                let compressed = self.read_bytes(compressed_len)?;
+                let value = lzf_decompress(compressed, uncompressed_len)?;
+                Ok(RdbString::Lzf(value))
+            }
+        }
+    }
+    fn read_bytes_string(&mut self) -> Result<Bytes, String> {
+        Ok(Bytes::from(self.read_string()?.into_bytes()))
+    }
+    /// 读取普通长度编码，不允许特殊编码
+    fn read_len(&mut self) -> Result<usize, String> {
+        match self.read_length()? {
+            RdLength::Len(length) => Ok(length as usize),
+            _ => Err("Invalid length".to_string()),
+        }
+    }
+    fn read_u64(&mut self) -> Result<u64, String> {
+        match self.read_length()? {
+            RdLength::Len(length) => Ok(length),
+            _ => Err("Invalid length".to_string()),
+        }
+    }
+    /// 旧版本有序集合的分数：长度加十进制字符串，253/254/255 分别表示 nan、inf 和 -inf，nan 不是合法的分数
+    fn read_double_string(&mut self) -> Result<f64, String> {
+        match self.read_byte()? {
+            253 => Err(NAN_SCORE.to_string()),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => {
+                let value = self.read_bytes(len as usize)?;
+                parse_score(value)
             }
-            RdLength::LZF => Ok(RdbString::LZF(vec![])),
         }
     }
-    fn read_value(&mut self, value_type: u8) -> Result<RdbValue, String> {
-        match RdValueType::from(value_type) {
-            RdValueType::String => {
-                let value = self.read_string()?;
-                Ok(RdbValue::String(value))
+    /// 跳过模块保存的数据，每个值之前都有类型标记，直到 EOF 标记为止
+    fn skip_module_values(&mut self) -> Result<(), String> {
+        loop {
+            match self.read_u64()? {
+                RDB_MODULE_OPCODE_EOF => return Ok(()),
+                RDB_MODULE_OPCODE_SINT | RDB_MODULE_OPCODE_UINT => {
+                    self.read_length()?;
+                }
+                RDB_MODULE_OPCODE_FLOAT => {
+                    self.read_bytes(4)?;
+                }
+                RDB_MODULE_OPCODE_DOUBLE => {
+                    self.read_bytes(8)?;
+                }
+                RDB_MODULE_OPCODE_STRING => {
+                    self.read_string()?;
+                }
+                _ => return Err("Unknown module opcode".to_string()),
             }
-            _ => Err("Invalid value type".to_string()),
         }
     }
+    fn read_value(&mut self, value_type: u8) -> Result<Value, String> {
+        let value = match RdValueType::from(value_type) {
+            RdValueType::String => Value::String(self.read_bytes_string()?.into()),
+            RdValueType::List => {
+                let len = self.read_len()?;
+                let mut list = VecDeque::with_capacity(self.capacity(len));
+                for _ in 0..len {
+                    list.push_back(self.read_bytes_string()?);
+                }
+                Value::List(list)
+            }
+            RdValueType::Set => {
+                let len = self.read_len()?;
+                let mut set = Set::default();
+                for _ in 0..len {
+                    set.insert(self.read_bytes_string()?);
+                }
+                Value::Set(set)
+            }
+            RdValueType::SortedSet | RdValueType::SortedSet2 => {
+                let binary = matches!(RdValueType::from(value_type), RdValueType::SortedSet2);
+                let len = self.read_len()?;
+                let mut zset = SortedSet::default();
+                for _ in 0..len {
+                    let member = self.read_bytes_string()?;
+                    let score = if binary {
+                        f64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap())
+                    } else {
+                        self.read_double_string()?
+                    };
+                    if score.is_nan() {
+                        return Err(NAN_SCORE.to_string());
+                    }
+                    zset.insert(member, score);
+                }
+                Value::ZSet(zset)
+            }
+            RdValueType::Hash => {
+                let len = self.read_len()?;
+                let mut hash = Hash::new();
+                for _ in 0..len {
+                    let field = self.read_bytes_string()?;
+                    let value = self.read_bytes_string()?;
+                    hash.insert(field, value);
+                }
+                Value::Hash(hash)
+            }
+            RdValueType::ZipMap => {
+                let data = self.read_string()?.into_bytes();
+                Value::Hash(ziplist::read_zipmap(&data)?.into_iter().collect())
+            }
+            RdValueType::ZipList => {
+                let data = self.read_string()?.into_bytes();
+                Value::List(ziplist::read_ziplist(&data)?.into())
+            }
+            RdValueType::IntSet => {
+                let data = self.read_string()?.into_bytes();
+                let ints = ziplist::read_intset(&data)?;
+                Value::Set(ints.into_iter().map(|n| Bytes::from(n.to_string())).collect())
+            }
+            RdValueType::SortedSetInZipList | RdValueType::SortedSetListpack => {
+                let data = self.read_string()?.into_bytes();
+                let elements = match RdValueType::from(value_type) {
+                    RdValueType::SortedSetInZipList => ziplist::read_ziplist(&data)?,
+                    _ => listpack::read_listpack(&data)?,
+                };
+                Value::ZSet(sorted_set_from_pairs(elements)?)
+            }
+            RdValueType::HashMapInZipList | RdValueType::HashListpack => {
+                let data = self.read_string()?.into_bytes();
+                let elements = match RdValueType::from(value_type) {
+                    RdValueType::HashMapInZipList => ziplist::read_ziplist(&data)?,
+                    _ => listpack::read_listpack(&data)?,
+                };
+                Value::Hash(hash_from_pairs(elements)?)
+            }
+            RdValueType::SetListpack => {
+                let data = self.read_string()?.into_bytes();
+                Value::Set(listpack::read_listpack(&data)?.into_iter().collect())
+            }
+            RdValueType::ZipInQuickList => {
+                let nodes = self.read_len()?;
+                let mut list = VecDeque::new();
+                for _ in 0..nodes {
+                    let data = self.read_string()?.into_bytes();
+                    list.extend(ziplist::read_ziplist(&data)?);
+                }
+                Value::List(list)
+            }
+            RdValueType::QuickList2 => {
+                let nodes = self.read_len()?;
+                let mut list = VecDeque::new();
+                for _ in 0..nodes {
+                    let container = self.read_u64()?;
+                    let data = self.read_bytes_string()?;
+                    if container == QUICKLIST_NODE_CONTAINER_PLAIN {
+                        list.push_back(data);
+                    } else {
+                        list.extend(listpack::read_listpack(&data)?);
+                    }
+                }
+                Value::List(list)
+            }
+            RdValueType::StreamListpacks => Value::Stream(self.read_stream(1)?),
+            RdValueType::StreamListpacks2 => Value::Stream(self.read_stream(2)?),
+            RdValueType::StreamListpacks3 => Value::Stream(self.read_stream(3)?),
+            RdValueType::ModulePreGa | RdValueType::Module2 => {
+                return Err("Module value types are not supported".to_string())
+            }
+            RdValueType::Unknown => return Err(format!("Unknown RDB encoding type {}", value_type)),
+        };
+        Ok(value)
+    }
+    fn read_stream_id(&mut self) -> Result<StreamId, String> {
+        Ok(StreamId::new(self.read_u64()?, self.read_u64()?))
+    }
+    /// 128 位大端序的 ID
+    fn read_raw_stream_id(&mut self) -> Result<StreamId, String> {
+        let bytes = self.read_bytes(16)?;
+        let ms = u64::from_be_bytes(bytes[..8].try_into().unwrap());
+        let seq = u64::from_be_bytes(bytes[8..].try_into().unwrap());
+        Ok(StreamId::new(ms, seq))
+    }
+    fn read_ms(&mut self) -> Result<i64, String> {
+        Ok(i64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+    /// 读取流，version 为 1、2、3 分别对应 STREAM_LISTPACKS、STREAM_LISTPACKS_2 和 STREAM_LISTPACKS_3。
+    /// 版本 2 增加了第一个 ID、最大删除 ID、添加的条目数和消费者组的已读取数，版本 3 增加了消费者的活跃时间
+    fn read_stream(&mut self, version: u8) -> Result<Stream, String> {
+        let mut stream = Stream::default();
+        let nodes = self.read_len()?;
+        for _ in 0..nodes {
+            let key = self.read_string()?.into_bytes();
+            if key.len() != 16 {
+                return Err("Stream node key entry is not the size of a stream ID".to_string());
+            }
+            let master_id = StreamId::new(
+                u64::from_be_bytes(key[..8].try_into().unwrap()),
+                u64::from_be_bytes(key[8..].try_into().unwrap()),
+            );
+            let data = self.read_string()?.into_bytes();
+            for (id, fields) in stream_node_entries(master_id, &listpack::read_listpack(&data)?)? {
+                stream.add(id, fields);
+            }
+        }
+        let len = self.read_len()?;
+        stream.last_id = self.read_stream_id()?;
+        stream.entries_added = len as u64;
+        if version >= 2 {
+            let _first_id = self.read_stream_id()?;
+            stream.max_deleted_id = self.read_stream_id()?;
+            stream.entries_added = self.read_u64()?;
+        }
+        if stream.len() != len {
+            return Err("Stream length inconsistent with the entries".to_string());
+        }
+
+        let groups = self.read_len()?;
+        for _ in 0..groups {
+            let name = self.read_bytes_string()?;
+            let last_id = self.read_stream_id()?;
+            // 无法确定已读取数时保存为 -1
+            let entries_read = match version {
+                1 => None,
+                _ => Some(self.read_u64()?).filter(|&entries_read| entries_read != u64::MAX),
+            };
+            let mut group = ConsumerGroup::new(last_id, entries_read);
+            let pel = self.read_len()?;
+            for _ in 0..pel {
+                let id = self.read_raw_stream_id()?;
+                let delivery_time = self.read_ms()? as u64;
+                let delivery_count = self.read_u64()?;
+                let pending = PendingEntry {
+                    consumer: Bytes::new(),
+                    delivery_time,
+                    delivery_count,
+                };
+                group.pel.insert(id, pending);
+            }
+            let consumers = self.read_len()?;
+            for _ in 0..consumers {
+                let consumer_name = self.read_bytes_string()?;
+                let seen_time = self.read_ms()? as u64;
+                let active_time = match version {
+                    3 => Some(self.read_ms()?).filter(|&time| time >= 0).map(|time| time as u64),
+                    _ => Some(seen_time),
+                };
+                let mut consumer = Consumer {
+                    seen_time,
+                    active_time,
+                    pending: BTreeSet::new(),
+                };
+                // 消费者的 PEL 只保存 ID，条目在消费者组的 PEL 中
+                let pending = self.read_len()?;
+                for _ in 0..pending {
+                    let id = self.read_raw_stream_id()?;
+                    let entry = group
+                        .pel
+                        .get_mut(&id)
+                        .ok_or_else(|| "Consumer entry not found in group global PEL".to_string())?;
+                    entry.consumer = consumer_name.clone();
+                    consumer.pending.insert(id);
+                }
+                group.consumers.insert(consumer_name, consumer);
+            }
+            if group.pel.values().any(|pending| pending.consumer.is_empty()) {
+                return Err("Group PEL entry without a consumer".to_string());
+            }
+            stream.groups.insert(name, group);
+        }
+        Ok(stream)
+    }
+}
+
+fn parse_score(value: &[u8]) -> Result<f64, String> {
+    let invalid = || "Invalid sorted set score".to_string();
+    let value = str::from_utf8(value).map_err(|_| invalid())?;
+    match value.to_lowercase().as_str() {
+        "inf" | "+inf" => Ok(f64::INFINITY),
+        "-inf" => Ok(f64::NEG_INFINITY),
+        _ => match value.parse::<f64>() {
+            Ok(score) if score.is_nan() => Err(NAN_SCORE.to_string()),
+            Ok(score) => Ok(score),
+            Err(_) => Err(invalid()),
+        },
+    }
+}
+
+/// ziplist 或 listpack 中成员和分数交替排列
+fn sorted_set_from_pairs(elements: Vec<Bytes>) -> Result<SortedSet, String> {
+    let pairs = elements.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err("Sorted set ziplist or listpack has an odd number of elements".to_string());
+    }
+    let mut zset = SortedSet::default();
+    for pair in pairs {
+        zset.insert(pair[0].clone(), parse_score(&pair[1])?);
+    }
+    Ok(zset)
+}
+
+/// ziplist 或 listpack 中字段和值交替排列
+fn hash_from_pairs(elements: Vec<Bytes>) -> Result<Hash, String> {
+    let pairs = elements.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err("Hash ziplist or listpack has an odd number of elements".to_string());
+    }
+    Ok(pairs.map(|pair| (pair[0].clone(), pair[1].clone())).collect())
+}
+
+/// 解析一个流节点，与 writer 中的 stream_node 相反，跳过已标记删除的条目
+fn stream_node_entries(master_id: StreamId, lp: &[Bytes]) -> Result<Vec<StreamEntry>, String> {
+    let invalid = || "Invalid stream listpack".to_string();
+    let mut iter = lp.iter();
+    let mut next = || iter.next().ok_or_else(invalid);
+    let int = |value: &Bytes| storage::parse_strict_int(value).ok_or_else(invalid);
+    let count = int(next()?)?;
+    let deleted = int(next()?)?;
+    let master_fields_len = int(next()?)? as usize;
+    let mut master_fields = Vec::with_capacity(master_fields_len.min(lp.len()));
+    for _ in 0..master_fields_len {
+        master_fields.push(next()?.clone());
+    }
+    // 主条目的结束标记
+    next()?;
+    let mut entries = Vec::new();
+    for _ in 0..count.checked_add(deleted).ok_or_else(invalid)? {
+        let flags = int(next()?)?;
+        let ms = master_id.ms.wrapping_add(int(next()?)? as u64);
+        let seq = master_id.seq.wrapping_add(int(next()?)? as u64);
+        let mut fields = Vec::new();
+        if flags & STREAM_ITEM_FLAG_SAMEFIELDS != 0 {
+            for name in &master_fields {
+                fields.push(name.clone());
+                fields.push(next()?.clone());
+            }
+        } else {
+            let len = int(next()?)? as usize;
+            for _ in 0..len.checked_mul(2).ok_or_else(invalid)? {
+                fields.push(next()?.clone());
+            }
+        }
+        // 条目占用的元素个数，用于从后向前遍历
+        next()?;
+        if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+            entries.push((StreamId::new(ms, seq), fields));
+        }
+    }
+    Ok(entries)
+}
+
+/// 压缩数据的每个字节最多解压出的字节数：3 个字节的回溯引用最多输出 264 个字节
+const LZF_MAX_RATIO: usize = 88;
+
+/// LZF 解压
+fn lzf_decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(expected_len.min(input.len().saturating_mul(LZF_MAX_RATIO)));
+    let mut pos = 0;
+    while pos < input.len() {
+        let ctrl = input[pos] as usize;
+        pos += 1;
+        if ctrl < 32 {
+            // 字面量
+            let len = ctrl + 1;
+            if pos + len > input.len() {
+                return Err("Invalid LZF data".to_string());
+            }
+            output.extend_from_slice(&input[pos..pos + len]);
+            pos += len;
+        } else {
+            // 回溯引用
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += *input.get(pos).ok_or("Invalid LZF data")? as usize;
+                pos += 1;
+            }
+            let offset = ((ctrl & 0x1f) << 8) + *input.get(pos).ok_or("Invalid LZF data")? as usize + 1;
+            pos += 1;
+            if offset > output.len() {
+                return Err("Invalid LZF data".to_string());
+            }
+            let start = output.len() - offset;
+            for i in 0..len + 2 {
+                output.push(output[start + i]);
+            }
+        }
+    }
+    if output.len() != expected_len {
+        return Err("Invalid LZF length".to_string());
+    }
+    Ok(output)
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct Rdb {
     pub header: RdbHeader,
     pub metadata: RdbMetadata,
@@ -257,6 +752,7 @@ pub struct Rdb {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct RdbHeader {
     pub magic: String,
     pub version: String,
@@ -280,44 +776,161 @@ pub struct RdbMetadata {
 //     pub expired: Option<u64>,
 // }
 
-#[derive(Debug)]
-pub enum RdbValue {
-    /// 字符串类型
-    String(RdbString),
-    /// 列表类型
-    List(Vec<String>),
-    /// 集合类型
-    Set(Vec<String>),
-    /// 有序集合类型
-    SortedSet(Vec<SortedSetEntry>),
-    /// 哈希类型
-    Hash(HashMap<String, String>),
-}
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
 
-impl ToString for RdbValue {
-    fn to_string(&self) -> String {
-        match self {
-            RdbValue::String(s) => s.to_string(),
-            RdbValue::List(l) => l.join(","),
-            RdbValue::Set(s) => s.join(","),
-            RdbValue::SortedSet(s) => s
-                .iter()
-                .map(|e| format!("{}:{}", e.member, e.score))
-                .collect::<Vec<String>>()
-                .join(","),
-            RdbValue::Hash(h) => h
-                .iter()
-                .map(|(k, v)| format!("{}:{}", k, v))
-                .collect::<Vec<String>>()
-                .join(","),
+    use bytes::Bytes;
+    use time::OffsetDateTime;
+
+    use super::{encode, RdbParser};
+    use crate::storage::{
+        from_unix_ms, unix_ms, ConsumerGroup, Consumer, Hash, Item, PendingEntry, Set, SortedSet, Stream, StreamId,
+        StringValue, Value,
+    };
+
+    fn parse(input: Vec<u8>) -> Result<Vec<(usize, Bytes, Item)>, String> {
+        let mut items = Vec::new();
+        RdbParser::new(input, |db, key, item| items.push((db, key, item))).parse()?;
+        Ok(items)
+    }
+
+    /// 与编码无关的值表示，集合类型按成员排序
+    fn canonical(value: &Value) -> String {
+        match value {
+            Value::String(value) => format!("string {:?}", value.to_bytes()),
+            Value::List(list) => format!("list {:?}", list),
+            Value::Hash(hash) => format!("hash {:?}", hash.iter().collect::<BTreeMap<_, _>>()),
+            Value::Set(set) => format!("set {:?}", set.members().into_iter().collect::<BTreeSet<_>>()),
+            Value::ZSet(zset) => format!("zset {:?}", zset.iter().collect::<Vec<_>>()),
+            Value::Stream(stream) => {
+                let groups = stream.groups.iter().map(|(name, group)| {
+                    let pel = group
+                        .pel
+                        .iter()
+                        .map(|(id, pending)| (*id, pending.consumer.clone(), pending.delivery_time, pending.delivery_count))
+                        .collect::<Vec<_>>();
+                    let consumers = group
+                        .consumers
+                        .iter()
+                        .map(|(name, consumer)| (name.clone(), consumer.seen_time, consumer.active_time, consumer.pending.clone()))
+                        .collect::<Vec<_>>();
+                    (name.clone(), group.last_id, group.entries_read, pel, consumers)
+                });
+                format!(
+                    "stream {:?} {:?} {:?} {} {:?}",
+                    stream.iter().collect::<Vec<_>>(),
+                    stream.last_id,
+                    stream.max_deleted_id,
+                    stream.entries_added,
+                    groups.collect::<Vec<_>>()
+                )
+            }
         }
     }
-}
 
-#[derive(Debug)]
-pub struct SortedSetEntry {
-    /// 成员
-    pub member: String,
-    /// 分数
-    pub score: f64,
+    fn sample() -> Vec<(Bytes, Item)> {
+        let bytes = |value: &str| Bytes::copy_from_slice(value.as_bytes());
+        let mut stream = Stream::default();
+        stream.add(StreamId::new(1, 0), vec![bytes("a"), bytes("1")]);
+        stream.add(StreamId::new(1, 1), vec![bytes("a"), bytes("2")]);
+        stream.add(StreamId::new(2, 0), vec![bytes("b"), bytes("3"), bytes("c"), bytes("4")]);
+        stream.max_deleted_id = StreamId::new(0, 5);
+        let mut group = ConsumerGroup::new(StreamId::new(1, 1), Some(2));
+        group.pel.insert(
+            StreamId::new(1, 0),
+            PendingEntry {
+                consumer: bytes("alice"),
+                delivery_time: 1000,
+                delivery_count: 2,
+            },
+        );
+        group.consumers.insert(
+            bytes("alice"),
+            Consumer {
+                seen_time: 1000,
+                active_time: Some(1000),
+                pending: BTreeSet::from([StreamId::new(1, 0)]),
+            },
+        );
+        group.consumers.insert(
+            bytes("bob"),
+            Consumer {
+                seen_time: 500,
+                active_time: None,
+                pending: BTreeSet::new(),
+            },
+        );
+        stream.groups.insert(bytes("g"), group);
+
+        let mut zset = SortedSet::default();
+        zset.insert(bytes("one"), 1.0);
+        zset.insert(bytes("half"), 0.5);
+        zset.insert(bytes("inf"), f64::INFINITY);
+        let mut expiring = Item::new(Value::String(StringValue::Raw(bytes("soon"))));
+        expiring.expires = Some(from_unix_ms(unix_ms(OffsetDateTime::now_utc()) + 3_600_000));
+        vec![
+            (bytes("raw"), Item::new(Value::String(StringValue::Raw(bytes("hello world"))))),
+            (bytes("int"), Item::new(Value::String(StringValue::Int(-12345)))),
+            (bytes("list"), Item::new(Value::List([bytes("x"), bytes(""), bytes("z")].into()))),
+            (bytes("intset"), Item::new(Value::Set([bytes("3"), bytes("1"), bytes("2")].into_iter().collect::<Set>()))),
+            (bytes("set"), Item::new(Value::Set([bytes("a"), bytes("b")].into_iter().collect::<Set>()))),
+            (bytes("zset"), Item::new(Value::ZSet(zset))),
+            (
+                bytes("hash"),
+                Item::new(Value::Hash([(bytes("f1"), bytes("v1")), (bytes("f2"), bytes("v2"))].into_iter().collect::<Hash>())),
+            ),
+            (bytes("stream"), Item::new(Value::Stream(stream))),
+            (bytes("expiring"), expiring),
+        ]
+    }
+
+    #[test]
+    fn write_then_load() {
+        let db0 = sample();
+        let db3 = vec![(Bytes::from_static(b"other"), Item::new(Value::String(StringValue::Int(7))))];
+        let data = encode(&vec![(0, db0.clone()), (3, db3.clone())], false);
+        let loaded = parse(data).unwrap();
+
+        let expected = db0.iter().map(|entry| (0, entry)).chain(db3.iter().map(|entry| (3, entry)));
+        let expected = expected
+            .map(|(db, (key, item))| (db, key.clone(), canonical(&item.value), item.expires.map(unix_ms)))
+            .collect::<BTreeSet<_>>();
+        let loaded = loaded
+            .iter()
+            .map(|(db, key, item)| (*db, key.clone(), canonical(&item.value), item.expires.map(unix_ms)))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(loaded, expected);
+    }
+
+    #[test]
+    fn load_fixture() {
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/dump.rdb")).unwrap();
+        let loaded = parse(data).unwrap();
+        let loaded = loaded
+            .iter()
+            .map(|(db, key, item)| (*db, key.clone(), canonical(&item.value)))
+            .collect::<BTreeSet<_>>();
+        // banana 已经过期，加载时被跳过
+        let expected = [("orange", "pineapple"), ("pear", "grape"), ("mango", "raspberry")]
+            .into_iter()
+            .map(|(key, value)| (0, Bytes::from(key), format!("string {:?}", Bytes::from(value))))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(loaded, expected);
+    }
+
+    #[test]
+    fn reject_oversized_lengths() {
+        let data = encode(&vec![(0, sample())], false);
+        for end in 0..data.len() {
+            assert!(parse(data[..end].to_vec()).is_err());
+        }
+        // 列表长度为 u64::MAX，字符串长度为 u32::MAX
+        let mut list = b"REDIS0011\xfe\x00\x01\x01k\x81".to_vec();
+        list.extend_from_slice(&u64::MAX.to_be_bytes());
+        assert!(parse(list).is_err());
+        let mut string = b"REDIS0011\xfe\x00\x00\x01k\x80".to_vec();
+        string.extend_from_slice(&u32::MAX.to_be_bytes());
+        assert!(parse(string).is_err());
+    }
 }
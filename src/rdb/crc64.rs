@@ -0,0 +1,26 @@
+//! Redis 使用的 CRC-64/Jones：多项式 0xad93d23594c935a9，输入输出均反转，初始值为 0
+
+use std::sync::LazyLock;
+
+/// 反转后的多项式
+const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+
+static TABLE: LazyLock<[u64; 256]> = LazyLock::new(|| {
+    let mut table = [0; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut crc = n as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLY } else { crc >> 1 };
+        }
+        *entry = crc;
+    }
+    table
+});
+
+/// 在 crc 的基础上继续计算 data 的校验和
+pub fn crc64(mut crc: u64, data: &[u8]) -> u64 {
+    for &byte in data {
+        crc = TABLE[((crc ^ byte as u64) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
@@ -0,0 +1,164 @@
+//! listpack 编码，RDB 中的流节点使用这种格式，Redis 7 的小型列表、集合、有序集合和哈希也使用这种格式
+
+use bytes::Bytes;
+
+use crate::storage::parse_strict_int;
+
+/// 头部：总字节数（4 字节）和元素个数（2 字节）
+const LP_HDR_SIZE: usize = 6;
+const LP_EOF: u8 = 0xff;
+
+#[derive(Default)]
+pub struct ListpackWriter {
+    entries: Vec<u8>,
+    len: usize,
+}
+
+impl ListpackWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加整数，按数值大小选择最短的编码
+    pub fn append_int(&mut self, value: i64) {
+        let start = self.entries.len();
+        match value {
+            0..=127 => self.entries.push(value as u8),
+            -4096..=4095 => {
+                let value = value as u64 & 0x1fff;
+                self.entries.push(0xc0 | (value >> 8) as u8);
+                self.entries.push(value as u8);
+            }
+            -32768..=32767 => {
+                self.entries.push(0xf1);
+                self.entries.extend_from_slice(&(value as i16).to_le_bytes());
+            }
+            -8388608..=8388607 => {
+                self.entries.push(0xf2);
+                self.entries.extend_from_slice(&(value as i32).to_le_bytes()[..3]);
+            }
+            -2147483648..=2147483647 => {
+                self.entries.push(0xf3);
+                self.entries.extend_from_slice(&(value as i32).to_le_bytes());
+            }
+            _ => {
+                self.entries.push(0xf4);
+                self.entries.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        self.finish_entry(start);
+    }
+
+    /// 追加字符串，能表示为整数的字符串与 Redis 一样使用整数编码
+    pub fn append(&mut self, value: &[u8]) {
+        if let Some(value) = parse_strict_int(value) {
+            return self.append_int(value);
+        }
+        let start = self.entries.len();
+        let len = value.len();
+        if len < 64 {
+            self.entries.push(0x80 | len as u8);
+        } else if len < 4096 {
+            self.entries.push(0xe0 | (len >> 8) as u8);
+            self.entries.push(len as u8);
+        } else {
+            self.entries.push(0xf0);
+            self.entries.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+        self.entries.extend_from_slice(value);
+        self.finish_entry(start);
+    }
+
+    /// 在元素末尾写入反向编码的元素长度，用于从后向前遍历
+    fn finish_entry(&mut self, start: usize) {
+        // 每字节 7 位，低位在后；除最高的一组外都设置最高位，表示前面还有字节
+        let len = (self.entries.len() - start) as u64;
+        let mut backlen = vec![(len & 127) as u8];
+        let mut rest = len >> 7;
+        while rest > 0 {
+            *backlen.last_mut().unwrap() |= 128;
+            backlen.push((rest & 127) as u8);
+            rest >>= 7;
+        }
+        self.entries.extend(backlen.iter().rev());
+        self.len += 1;
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let total = LP_HDR_SIZE + self.entries.len() + 1;
+        let mut buf = Vec::with_capacity(total);
+        buf.extend_from_slice(&(total as u32).to_le_bytes());
+        buf.extend_from_slice(&(self.len.min(u16::MAX as usize) as u16).to_le_bytes());
+        buf.extend_from_slice(&self.entries);
+        buf.push(LP_EOF);
+        buf
+    }
+}
+
+/// 元素末尾反向编码的长度所占的字节数
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16383 => 2,
+        16384..=2097151 => 3,
+        2097152..=268435455 => 4,
+        _ => 5,
+    }
+}
+
+/// 读取 listpack 中的所有元素，整数编码的元素转为十进制字符串
+pub fn read_listpack(data: &[u8]) -> Result<Vec<Bytes>, String> {
+    let invalid = || "Invalid listpack encoding".to_string();
+    let slice = |pos: usize, len: usize| data.get(pos..pos + len).ok_or_else(invalid);
+    let mut elements = Vec::new();
+    let mut pos = LP_HDR_SIZE;
+    loop {
+        let encoding = *data.get(pos).ok_or_else(invalid)?;
+        if encoding == LP_EOF {
+            break;
+        }
+        // 元素和它占用的字节数，不含末尾的反向长度
+        let (element, size) = if encoding & 0x80 == 0 {
+            (Bytes::from((encoding & 0x7f).to_string()), 1)
+        } else if encoding & 0xc0 == 0x80 {
+            let len = (encoding & 0x3f) as usize;
+            (Bytes::copy_from_slice(slice(pos + 1, len)?), 1 + len)
+        } else if encoding & 0xe0 == 0xc0 {
+            // 13 位有符号整数
+            let value = ((encoding & 0x1f) as i64) << 8 | *data.get(pos + 1).ok_or_else(invalid)? as i64;
+            let value = if value >= 1 << 12 { value - (1 << 13) } else { value };
+            (Bytes::from(value.to_string()), 2)
+        } else if encoding & 0xf0 == 0xe0 {
+            let len = ((encoding & 0x0f) as usize) << 8 | *data.get(pos + 1).ok_or_else(invalid)? as usize;
+            (Bytes::copy_from_slice(slice(pos + 2, len)?), 2 + len)
+        } else {
+            match encoding {
+                0xf0 => {
+                    let len = u32::from_le_bytes(slice(pos + 1, 4)?.try_into().unwrap()) as usize;
+                    (Bytes::copy_from_slice(slice(pos + 5, len)?), 5 + len)
+                }
+                0xf1 => {
+                    let value = i16::from_le_bytes(slice(pos + 1, 2)?.try_into().unwrap());
+                    (Bytes::from(value.to_string()), 3)
+                }
+                0xf2 => {
+                    let bytes = slice(pos + 1, 3)?;
+                    let value = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+                    (Bytes::from(value.to_string()), 4)
+                }
+                0xf3 => {
+                    let value = i32::from_le_bytes(slice(pos + 1, 4)?.try_into().unwrap());
+                    (Bytes::from(value.to_string()), 5)
+                }
+                0xf4 => {
+                    let value = i64::from_le_bytes(slice(pos + 1, 8)?.try_into().unwrap());
+                    (Bytes::from(value.to_string()), 9)
+                }
+                _ => return Err(invalid()),
+            }
+        };
+        elements.push(element);
+        pos += size + backlen_size(size);
+    }
+    Ok(elements)
+}
@@ -0,0 +1,248 @@
+//! SAVE 和 BGSAVE：生成 RDB 文件并原子地替换 dir/dbfilename
+
+use std::{
+    cell::Cell,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    config,
+    storage::{self, Item},
+};
+
+use super::RdbWriter;
+
+pub const DEFAULT_DBFILENAME: &str = "dump.rdb";
+/// 与 Redis 的默认值一致：3600 秒内 1 次修改、300 秒内 100 次或 60 秒内 10000 次时保存
+pub const DEFAULT_SAVE_PARAMS: &str = "3600 1 300 100 60 10000";
+/// 后台保存失败后，至少间隔这么久（秒）才会按规则重试
+const BGSAVE_RETRY_DELAY: i64 = 5;
+/// 与 Redis 默认的 hz 一致，每秒检查 10 次保存规则
+const SAVE_CRON_PERIOD: Duration = Duration::from_millis(100);
+
+/// 最近一次成功保存的时间（秒）
+static LASTSAVE: AtomicI64 = AtomicI64::new(0);
+/// SAVE 或 BGSAVE 正在进行，两者不能同时执行
+static SAVE_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+/// 最近一次尝试后台保存的时间（秒）
+static LASTBGSAVE_TRY: AtomicI64 = AtomicI64::new(0);
+static LASTBGSAVE_OK: AtomicBool = AtomicBool::new(true);
+/// 上次保存之后的修改数
+static DIRTY: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    /// 正在执行的写命令产生的修改数
+    static COMMAND_DIRTY: Cell<u64>;
+}
+
+/// 所有数据库的快照，每项为数据库编号和其中的键值对
+pub type Snapshot = Vec<(usize, Vec<(Bytes, Item)>)>;
+
+fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp()
+}
+
+pub fn add_dirty(changes: u64) {
+    DIRTY.fetch_add(changes, Ordering::Relaxed);
+    let _ = COMMAND_DIRTY.try_with(|dirty| dirty.set(dirty.get() + changes));
+}
+
+/// 执行一条命令，同时返回它产生的修改数
+pub async fn count_dirty<F: Future>(f: F) -> (F::Output, u64) {
+    COMMAND_DIRTY
+        .scope(Cell::new(0), async {
+            let output = f.await;
+            (output, COMMAND_DIRTY.with(Cell::get))
+        })
+        .await
+}
+
+/// 加载数据之后清零
+pub fn reset_dirty() {
+    DIRTY.store(0, Ordering::Relaxed);
+}
+
+pub fn dirty() -> u64 {
+    DIRTY.load(Ordering::Relaxed)
+}
+
+/// 最近一次后台保存是否成功
+pub fn lastbgsave_ok() -> bool {
+    LASTBGSAVE_OK.load(Ordering::Relaxed)
+}
+
+pub fn lastsave() -> i64 {
+    LASTSAVE.load(Ordering::Relaxed)
+}
+
+/// 启动时视为刚保存过，与 Redis 一致
+pub fn reset_lastsave() {
+    LASTSAVE.store(now(), Ordering::Relaxed);
+}
+
+pub fn bgsave_in_progress() -> bool {
+    SAVE_IN_PROGRESS.load(Ordering::Relaxed)
+}
+
+/// 标记开始保存，已有保存在进行时返回 false
+fn begin_save() -> bool {
+    SAVE_IN_PROGRESS
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+}
+
+/// 写入一个数据库，空的数据库不写入
+fn write_db<'a>(writer: &mut RdbWriter, index: usize, entries: impl Iterator<Item = (&'a Bytes, &'a Item)>) {
+    let entries: Vec<_> = entries.collect();
+    if entries.is_empty() {
+        return;
+    }
+    let expires = entries.iter().filter(|(_, item)| item.expires.is_some()).count();
+    writer.select_db(index, entries.len(), expires);
+    for (key, item) in entries {
+        writer.write_entry(key, item);
+    }
+}
+
+async fn dir() -> PathBuf {
+    PathBuf::from(config::get("dir").await.unwrap_or_else(|| ".".to_string()))
+}
+
+/// RDB 文件的路径
+pub async fn rdb_path() -> PathBuf {
+    let dbfilename = config::get("dbfilename").await;
+    dir().await.join(dbfilename.as_deref().unwrap_or(DEFAULT_DBFILENAME))
+}
+
+/// 先写入临时文件并刷到磁盘，再重命名为目标文件，避免留下不完整的文件。SAVE 和 BGSAVE 使用不同的
+/// 临时文件名（tmp_prefix）。dirty 是生成快照时的修改数，保存成功后从计数中减去，快照之后的修改仍然保留
+async fn write_file(data: &[u8], tmp_prefix: &str, dirty: u64) -> Result<(), String> {
+    let tmp = dir().await.join(format!("{}-{}.rdb", tmp_prefix, std::process::id()));
+    let path = rdb_path().await;
+    let result = async {
+        let mut file = tokio::fs::File::create(&tmp).await?;
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp, &path).await
+    }
+    .await;
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(format!("Failed saving the DB to {}: {}", display(&path), e));
+    }
+    DIRTY.fetch_sub(dirty, Ordering::Relaxed);
+    LASTSAVE.store(now(), Ordering::Relaxed);
+    LASTBGSAVE_OK.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+fn display(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// 在前台保存，保存期间阻塞所有写入。已有保存在进行时返回 None
+pub async fn save() -> Option<Result<(), String>> {
+    if !begin_save() {
+        return None;
+    }
+    let dbs = storage::read_all().await;
+    let dirty = dirty();
+    let mut writer = RdbWriter::new(false);
+    for (index, db) in dbs.iter().enumerate() {
+        write_db(&mut writer, index, db.iter());
+    }
+    drop(dbs);
+    let result = write_file(&writer.finish(), "temp", dirty).await;
+    SAVE_IN_PROGRESS.store(false, Ordering::Release);
+    Some(result)
+}
+
+/// 持有所有数据库的读锁，复制其中未过期的键
+pub async fn snapshot() -> Snapshot {
+    storage::read_all()
+        .await
+        .iter()
+        .enumerate()
+        .map(|(index, db)| (index, db.iter().map(|(key, item)| (key.clone(), item.clone())).collect()))
+        .collect()
+}
+
+/// 把快照编码为 RDB，aof_base 表示用作 AOF 的基础文件
+pub fn encode(snapshot: &Snapshot, aof_base: bool) -> Vec<u8> {
+    let mut writer = RdbWriter::new(aof_base);
+    for (index, entries) in snapshot {
+        write_db(&mut writer, *index, entries.iter().map(|(key, item)| (key, item)));
+    }
+    writer.finish()
+}
+
+/// 复制所有数据库后在后台保存，已有后台保存在进行时返回 false
+pub async fn bgsave() -> bool {
+    if !begin_save() {
+        return false;
+    }
+    LASTBGSAVE_TRY.store(now(), Ordering::Relaxed);
+    let dirty = dirty();
+    let snapshot = snapshot().await;
+    tokio::spawn(async move {
+        let data = tokio::task::spawn_blocking(move || encode(&snapshot, false)).await;
+        let result = match data {
+            Ok(data) => write_file(&data, "temp-bg", dirty).await,
+            Err(e) => Err(format!("Background saving error: {}", e)),
+        };
+        match result {
+            Ok(()) => println!("Background saving terminated with success"),
+            Err(e) => {
+                println!("{}", e);
+                LASTBGSAVE_OK.store(false, Ordering::Relaxed);
+            }
+        }
+        SAVE_IN_PROGRESS.store(false, Ordering::Release);
+    });
+    true
+}
+
+/// 解析 save 配置：成对的 `<seconds> <changes>`，空字符串表示不自动保存
+pub fn parse_save_params(value: &str) -> Option<Vec<(i64, u64)>> {
+    let numbers = value
+        .split_whitespace()
+        .map(|n| n.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let pairs = numbers.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    Some(pairs.map(|pair| (pair[0] as i64, pair[1])).collect())
+}
+
+/// 后台任务，满足任意一条 save 规则时开始后台保存，与 Redis 的 serverCron 一致
+pub async fn save_cron() {
+    let mut interval = tokio::time::interval(SAVE_CRON_PERIOD);
+    loop {
+        interval.tick().await;
+        if bgsave_in_progress() {
+            continue;
+        }
+        let params = match config::get("save").await {
+            Some(value) => parse_save_params(&value).unwrap_or_default(),
+            None => continue,
+        };
+        let now = now();
+        // 保存失败后等待一段时间再重试
+        let can_retry = now - LASTBGSAVE_TRY.load(Ordering::Relaxed) > BGSAVE_RETRY_DELAY || lastbgsave_ok();
+        let rule = params
+            .iter()
+            .find(|(seconds, changes)| dirty() >= *changes && now - lastsave() > *seconds && can_retry);
+        if let Some((seconds, changes)) = rule {
+            println!("{} changes in {} seconds. Saving...", changes, seconds);
+            bgsave().await;
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! RDB 文件的序列化，与 RdbParser 相反，格式与 Redis 7.2（RDB 版本 11）一致
+
+use bytes::Bytes;
+use time::OffsetDateTime;
+
+use crate::storage::{parse_strict_int, unix_ms, Item, Stream, StreamId, Value, STREAM_NODE_MAX_ENTRIES};
+
+use super::{crc64::crc64, listpack::ListpackWriter, OpCode, RdValueType};
+
+pub const RDB_VERSION: u32 = 11;
+/// 写入 AUX 字段的 Redis 版本，表示兼容的格式
+pub const REDIS_VERSION: &str = "7.2.0";
+
+/// 流条目的标志位
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 1 << 1;
+
+pub struct RdbWriter {
+    buf: Vec<u8>,
+}
+
+impl RdbWriter {
+    /// 写入文件头和 AUX 字段，aof_base 表示用作 AOF 的基础文件
+    pub fn new(aof_base: bool) -> Self {
+        let mut writer = Self {
+            buf: format!("REDIS{:04}", RDB_VERSION).into_bytes(),
+        };
+        writer.write_aux("redis-ver", REDIS_VERSION);
+        writer.write_aux("redis-bits", "64");
+        writer.write_aux("ctime", &OffsetDateTime::now_utc().unix_timestamp().to_string());
+        writer.write_aux("used-mem", &used_memory().to_string());
+        writer.write_aux("aof-base", if aof_base { "1" } else { "0" });
+        writer
+    }
+
+    fn write_aux(&mut self, key: &str, value: &str) {
+        self.buf.push(OpCode::Aux as u8);
+        self.write_string(key.as_bytes());
+        self.write_string(value.as_bytes());
+    }
+
+    /// 开始一个数据库，size 和 expires 是键数和设置了过期时间的键数
+    pub fn select_db(&mut self, index: usize, size: usize, expires: usize) {
+        self.buf.push(OpCode::SelectDb as u8);
+        self.write_len(index as u64);
+        self.buf.push(OpCode::ResizeDb as u8);
+        self.write_len(size as u64);
+        self.write_len(expires as u64);
+    }
+
+    pub fn write_entry(&mut self, key: &[u8], item: &Item) {
+        if let Some(expires) = item.expires {
+            self.buf.push(OpCode::ExpireTimeMs as u8);
+            self.buf.extend_from_slice(&unix_ms(expires).to_le_bytes());
+        }
+        let value_type = match &item.value {
+            Value::String(_) => RdValueType::String,
+            Value::List(_) => RdValueType::List,
+            Value::Set(_) => RdValueType::Set,
+            Value::ZSet(_) => RdValueType::SortedSet2,
+            Value::Hash(_) => RdValueType::Hash,
+            Value::Stream(_) => RdValueType::StreamListpacks3,
+        };
+        self.buf.push(value_type as u8);
+        self.write_string(key);
+        match &item.value {
+            Value::String(value) => self.write_string(&value.to_bytes()),
+            Value::List(list) => {
+                self.write_len(list.len() as u64);
+                for element in list {
+                    self.write_string(element);
+                }
+            }
+            Value::Set(set) => {
+                let members = set.members();
+                self.write_len(members.len() as u64);
+                for member in members {
+                    self.write_string(&member);
+                }
+            }
+            Value::ZSet(zset) => {
+                self.write_len(zset.len() as u64);
+                for (member, score) in zset.iter() {
+                    self.write_string(member);
+                    self.buf.extend_from_slice(&score.to_le_bytes());
+                }
+            }
+            Value::Hash(hash) => {
+                self.write_len(hash.len() as u64);
+                for (field, value) in hash.iter() {
+                    self.write_string(field);
+                    self.write_string(value);
+                }
+            }
+            Value::Stream(stream) => self.write_stream(stream),
+        }
+    }
+
+    /// 写入 EOF 和 CRC64 校验和，返回完整的文件内容
+    pub fn finish(mut self) -> Vec<u8> {
+        self.buf.push(OpCode::Eof as u8);
+        let checksum = crc64(0, &self.buf);
+        self.buf.extend_from_slice(&checksum.to_le_bytes());
+        self.buf
+    }
+
+    /// 长度编码：6 位、14 位、32 位或 64 位
+    fn write_len(&mut self, len: u64) {
+        if len < 1 << 6 {
+            self.buf.push(len as u8);
+        } else if len < 1 << 14 {
+            self.buf.push(0x40 | (len >> 8) as u8);
+            self.buf.push(len as u8);
+        } else if len <= u32::MAX as u64 {
+            self.buf.push(0x80);
+            self.buf.extend_from_slice(&(len as u32).to_be_bytes());
+        } else {
+            self.buf.push(0x81);
+            self.buf.extend_from_slice(&len.to_be_bytes());
+        }
+    }
+
+    /// 能表示为 32 位以内整数的短字符串使用整数编码，其余按长度加内容写入
+    fn write_string(&mut self, value: &[u8]) {
+        if value.len() <= 11 {
+            if let Some(n) = parse_strict_int(value) {
+                if let Ok(n) = i8::try_from(n) {
+                    self.buf.push(0xc0);
+                    self.buf.extend_from_slice(&n.to_le_bytes());
+                    return;
+                } else if let Ok(n) = i16::try_from(n) {
+                    self.buf.push(0xc1);
+                    self.buf.extend_from_slice(&n.to_le_bytes());
+                    return;
+                } else if let Ok(n) = i32::try_from(n) {
+                    self.buf.push(0xc2);
+                    self.buf.extend_from_slice(&n.to_le_bytes());
+                    return;
+                }
+            }
+        }
+        self.write_len(value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn write_stream_id(&mut self, id: StreamId) {
+        self.write_len(id.ms);
+        self.write_len(id.seq);
+    }
+
+    /// 流以 listpack 节点的形式写入，之后是元数据和消费者组
+    fn write_stream(&mut self, stream: &Stream) {
+        let entries: Vec<_> = stream.iter().collect();
+        let nodes: Vec<_> = entries.chunks(STREAM_NODE_MAX_ENTRIES).collect();
+        self.write_len(nodes.len() as u64);
+        for node in nodes {
+            let (master_id, master_fields) = node[0];
+            let mut key = master_id.ms.to_be_bytes().to_vec();
+            key.extend_from_slice(&master_id.seq.to_be_bytes());
+            self.write_string(&key);
+            self.write_string(&stream_node(*master_id, master_fields, node));
+        }
+        self.write_len(stream.len() as u64);
+        self.write_stream_id(stream.last_id);
+        self.write_stream_id(stream.first_id());
+        self.write_stream_id(stream.max_deleted_id);
+        self.write_len(stream.entries_added);
+
+        self.write_len(stream.groups.len() as u64);
+        for (name, group) in &stream.groups {
+            self.write_string(name);
+            self.write_stream_id(group.last_id);
+            // 无法确定已读取数时写入 -1
+            self.write_len(group.entries_read.unwrap_or(u64::MAX));
+            self.write_len(group.pel.len() as u64);
+            for (id, pending) in &group.pel {
+                self.write_raw_stream_id(*id);
+                self.buf.extend_from_slice(&pending.delivery_time.to_le_bytes());
+                self.write_len(pending.delivery_count);
+            }
+            self.write_len(group.consumers.len() as u64);
+            for (name, consumer) in &group.consumers {
+                self.write_string(name);
+                self.buf.extend_from_slice(&consumer.seen_time.to_le_bytes());
+                let active_time = consumer.active_time.map_or(-1, |time| time as i64);
+                self.buf.extend_from_slice(&active_time.to_le_bytes());
+                // 消费者的 PEL 只写 ID，加载时从消费者组的 PEL 中查找
+                self.write_len(consumer.pending.len() as u64);
+                for id in &consumer.pending {
+                    self.write_raw_stream_id(*id);
+                }
+            }
+        }
+    }
+
+    /// 128 位大端序的 ID
+    fn write_raw_stream_id(&mut self, id: StreamId) {
+        self.buf.extend_from_slice(&id.ms.to_be_bytes());
+        self.buf.extend_from_slice(&id.seq.to_be_bytes());
+    }
+}
+
+/// 生成一个流节点：主条目记录条目数和第一个条目的字段名，
+/// 字段名与主条目相同的条目只写入值，ID 以相对主条目 ID 的差值保存
+fn stream_node(master_id: StreamId, master_fields: &[Bytes], node: &[(&StreamId, &Vec<Bytes>)]) -> Vec<u8> {
+    let master_names: Vec<_> = master_fields.iter().step_by(2).collect();
+    let mut lp = ListpackWriter::new();
+    lp.append_int(node.len() as i64);
+    lp.append_int(0);
+    lp.append_int(master_names.len() as i64);
+    for name in &master_names {
+        lp.append(name);
+    }
+    lp.append_int(0);
+    for (id, fields) in node {
+        let same_fields = fields.len() / 2 == master_names.len()
+            && fields.iter().step_by(2).zip(&master_names).all(|(name, master)| name == *master);
+        let flags = if same_fields { STREAM_ITEM_FLAG_SAMEFIELDS } else { 0 };
+        lp.append_int(flags);
+        lp.append_int(id.ms.wrapping_sub(master_id.ms) as i64);
+        lp.append_int(id.seq.wrapping_sub(master_id.seq) as i64);
+        let num_fields = fields.len() / 2;
+        if same_fields {
+            for value in fields.iter().skip(1).step_by(2) {
+                lp.append(value);
+            }
+            lp.append_int(num_fields as i64 + 3);
+        } else {
+            lp.append_int(num_fields as i64);
+            for element in fields.iter() {
+                lp.append(element);
+            }
+            lp.append_int(num_fields as i64 * 2 + 4);
+        }
+    }
+    lp.finish()
+}
+
+/// 进程的常驻内存，无法读取时为 0
+fn used_memory() -> u64 {
+    std::fs::read_to_string("/proc/self/statm")
+        .ok()
+        .and_then(|statm| statm.split_whitespace().nth(1)?.parse::<u64>().ok())
+        .map_or(0, |pages| pages * 4096)
+}
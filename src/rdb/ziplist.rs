@@ -0,0 +1,123 @@
+//! 旧版本 Redis 使用的紧凑编码：ziplist、intset 和 zipmap，只需要读取
+
+use bytes::Bytes;
+
+/// 头部：总字节数（4 字节）、最后一个元素的偏移（4 字节）和元素个数（2 字节）
+const ZIPLIST_HEADER_SIZE: usize = 10;
+const ZIP_END: u8 = 0xff;
+/// 前一个元素的长度不小于这个值时，用 5 个字节保存
+const ZIP_BIG_PREVLEN: u8 = 254;
+
+fn invalid(name: &str) -> String {
+    format!("Invalid {} encoding", name)
+}
+
+/// 按偏移读取定长的字节，越界时返回错误
+fn slice<'a>(data: &'a [u8], pos: usize, len: usize, name: &str) -> Result<&'a [u8], String> {
+    data.get(pos..pos + len).ok_or_else(|| invalid(name))
+}
+
+/// 读取 ziplist 中的所有元素，整数编码的元素转为十进制字符串
+pub fn read_ziplist(data: &[u8]) -> Result<Vec<Bytes>, String> {
+    let name = "ziplist";
+    let mut elements = Vec::new();
+    let mut pos = ZIPLIST_HEADER_SIZE;
+    loop {
+        let prevlen = *data.get(pos).ok_or_else(|| invalid(name))?;
+        if prevlen == ZIP_END {
+            break;
+        }
+        pos += if prevlen < ZIP_BIG_PREVLEN { 1 } else { 5 };
+        let encoding = *data.get(pos).ok_or_else(|| invalid(name))?;
+        pos += 1;
+        let string_len = match encoding >> 6 {
+            0b00 => Some((encoding & 0x3f) as usize),
+            0b01 => {
+                let next = *data.get(pos).ok_or_else(|| invalid(name))?;
+                pos += 1;
+                Some(((encoding & 0x3f) as usize) << 8 | next as usize)
+            }
+            0b10 => {
+                let len = slice(data, pos, 4, name)?;
+                pos += 4;
+                Some(u32::from_be_bytes(len.try_into().unwrap()) as usize)
+            }
+            _ => None,
+        };
+        if let Some(len) = string_len {
+            elements.push(Bytes::copy_from_slice(slice(data, pos, len, name)?));
+            pos += len;
+            continue;
+        }
+        let (value, size) = match encoding {
+            0xc0 => (i16::from_le_bytes(slice(data, pos, 2, name)?.try_into().unwrap()) as i64, 2),
+            0xd0 => (i32::from_le_bytes(slice(data, pos, 4, name)?.try_into().unwrap()) as i64, 4),
+            0xe0 => (i64::from_le_bytes(slice(data, pos, 8, name)?.try_into().unwrap()), 8),
+            0xf0 => {
+                let bytes = slice(data, pos, 3, name)?;
+                // 24 位有符号整数，先放到高位再算术右移
+                (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as i64 >> 8, 3)
+            }
+            0xfe => (*data.get(pos).ok_or_else(|| invalid(name))? as i8 as i64, 1),
+            // 1111xxxx 直接保存 0 到 12
+            0xf1..=0xfd => ((encoding & 0x0f) as i64 - 1, 0),
+            _ => return Err(invalid(name)),
+        };
+        elements.push(Bytes::from(value.to_string()));
+        pos += size;
+    }
+    Ok(elements)
+}
+
+/// 读取 intset：编码宽度（4 字节）、元素个数（4 字节）和按小端序排列的整数
+pub fn read_intset(data: &[u8]) -> Result<Vec<i64>, String> {
+    let name = "intset";
+    let width = u32::from_le_bytes(slice(data, 0, 4, name)?.try_into().unwrap()) as usize;
+    let len = u32::from_le_bytes(slice(data, 4, 4, name)?.try_into().unwrap()) as usize;
+    let contents = data.get(8..).ok_or_else(|| invalid(name))?;
+    if !matches!(width, 2 | 4 | 8) || contents.len() < width * len {
+        return Err(invalid(name));
+    }
+    let ints = contents[..width * len]
+        .chunks_exact(width)
+        .map(|chunk| match width {
+            2 => i16::from_le_bytes(chunk.try_into().unwrap()) as i64,
+            4 => i32::from_le_bytes(chunk.try_into().unwrap()) as i64,
+            _ => i64::from_le_bytes(chunk.try_into().unwrap()),
+        })
+        .collect();
+    Ok(ints)
+}
+
+/// 读取 zipmap 中的所有字段和值。长度小于 254 时用 1 个字节保存，否则为 254 加上 4 字节的长度；
+/// 值之后还有若干空闲字节
+pub fn read_zipmap(data: &[u8]) -> Result<Vec<(Bytes, Bytes)>, String> {
+    let name = "zipmap";
+    let read_len = |pos: &mut usize| -> Result<Option<usize>, String> {
+        let len = *data.get(*pos).ok_or_else(|| invalid(name))?;
+        *pos += 1;
+        match len {
+            ZIP_END => Ok(None),
+            ZIP_BIG_PREVLEN => {
+                let len = slice(data, *pos, 4, name)?;
+                *pos += 4;
+                Ok(Some(u32::from_le_bytes(len.try_into().unwrap()) as usize))
+            }
+            len => Ok(Some(len as usize)),
+        }
+    };
+    let mut pairs = Vec::new();
+    // 第一个字节是元素个数，不可靠，以结束标记为准
+    let mut pos = 1;
+    while let Some(len) = read_len(&mut pos)? {
+        let field = Bytes::copy_from_slice(slice(data, pos, len, name)?);
+        pos += len;
+        let len = read_len(&mut pos)?.ok_or_else(|| invalid(name))?;
+        let free = *data.get(pos).ok_or_else(|| invalid(name))? as usize;
+        pos += 1;
+        let value = Bytes::copy_from_slice(slice(data, pos, len, name)?);
+        pos += len + free;
+        pairs.push((field, value));
+    }
+    Ok(pairs)
+}
@@ -1,16 +1,29 @@
+use bytes::{Buf, Bytes, BytesMut};
 use num_bigint::BigInt;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::io::{Read, Write};
+use std::mem;
 use std::net::{TcpListener, TcpStream};
 use std::str;
 
+/// 单个 bulk string 的最大长度，与 Redis 的 proto-max-bulk-len 默认值一致
+const MAX_BULK_LEN: isize = 512 * 1024 * 1024;
+/// 请求中数组的最大元素个数，与 Redis 一致
+const MAX_MULTIBULK_LEN: isize = i32::MAX as isize;
+/// 长度行的最大长度，与 Redis 的 PROTO_INLINE_MAX_SIZE 一致
+const MAX_LINE_LEN: usize = 64 * 1024;
+/// 未处理的请求数据的上限，与 Redis 的 client-query-buffer-limit 默认值一致
+const QUERY_BUFFER_LIMIT: usize = 1024 * 1024 * 1024;
+/// 按请求中的数组长度预先分配参数时的上限，避免客户端给出很大的长度后迟迟不发送参数
+const MAX_PREALLOCATED_ARGS: usize = 1024;
+
 /// 定义 RESP 类型
 #[derive(Debug)]
 pub enum RespType {
     SimpleString(String),
     SimpleError(String),
     Integer(i64),
-    BulkString(Option<String>),
+    BulkString(Option<Bytes>),
     BulkError(String),
     Array(Option<Vec<RespType>>),
     Null,
@@ -26,7 +39,10 @@ impl RespType {
       RespType::SimpleError(e) => format!("-{}\r\n", e).into_bytes(),
       RespType::Integer(i) => format!(":{}\r\n", i).into_bytes(),
       RespType::BulkString(Some(s)) => {
-        format!("${}\r\n{}\r\n", s.len(), s).into_bytes()
+        let mut serialized = format!("${}\r\n", s.len()).into_bytes();
+        serialized.extend_from_slice(s);
+        serialized.extend_from_slice(b"\r\n");
+        serialized
       }
       RespType::BulkString(None) => "$-1\r\n".to_string().into_bytes(),
       RespType::BulkError(e) => format!("!{}\r\n", e).into_bytes(),
@@ -49,6 +65,27 @@ impl RespType {
 }
 
 
+/// RESP 解析错误
+#[derive(Debug)]
+pub enum RespError {
+    /// 数据不完整，需要等待更多字节
+    Incomplete,
+    /// 协议错误
+    Invalid(String),
+}
+
+impl From<String> for RespError {
+    fn from(e: String) -> Self {
+        RespError::Invalid(e)
+    }
+}
+
+impl From<&str> for RespError {
+    fn from(e: &str) -> Self {
+        RespError::Invalid(e.to_string())
+    }
+}
+
 /// RESP 解析器
 pub struct RespParser<'a> {
     input: &'a [u8],
@@ -61,10 +98,15 @@ impl<'a> RespParser<'a> {
         Self { input, pos: 0 }
     }
 
+    /// 已经消费的字节数
+    pub fn consumed(&self) -> usize {
+        self.pos
+    }
+
     /// 解析一条 RESP 消息
-    pub fn parse(&mut self) -> Result<RespType, String> {
+    pub fn parse(&mut self) -> Result<RespType, RespError> {
         if self.pos >= self.input.len() {
-            return Err("Input exhausted".to_string());
+            return Err(RespError::Incomplete);
         }
 
         let prefix = self.input[self.pos];
@@ -81,27 +123,27 @@ impl<'a> RespParser<'a> {
             b'#' => self.parse_boolean(),
             b',' => self.parse_double(),
             b'(' => self.parse_big_number(),
-            _ => Err("Invalid RESP type marker".to_string()),
+            _ => Err("Invalid RESP type marker".into()),
         }
     }
 
-    fn parse_simple_string(&mut self) -> Result<RespType, String> {
+    fn parse_simple_string(&mut self) -> Result<RespType, RespError> {
         let line = self.read_line()?;
         Ok(RespType::SimpleString(line))
     }
 
-    fn parse_simple_error(&mut self) -> Result<RespType, String> {
+    fn parse_simple_error(&mut self) -> Result<RespType, RespError> {
         let line = self.read_line()?;
         Ok(RespType::SimpleError(line))
     }
 
-    fn parse_integer(&mut self) -> Result<RespType, String> {
+    fn parse_integer(&mut self) -> Result<RespType, RespError> {
         let line = self.read_line()?;
         let number: i64 = line.parse().map_err(|_| "Invalid integer".to_string())?;
         Ok(RespType::Integer(number))
     }
 
-    fn parse_bulk_string(&mut self) -> Result<RespType, String> {
+    fn parse_bulk_string(&mut self) -> Result<RespType, RespError> {
         let length: isize = self
             .read_line()?
             .parse()
@@ -110,32 +152,41 @@ impl<'a> RespParser<'a> {
         if length == -1 {
             return Ok(RespType::BulkString(None));
         }
+        if !(0..=MAX_BULK_LEN).contains(&length) {
+            return Err("Invalid bulk string length".into());
+        }
 
         let start = self.pos;
         let end = self.pos + length as usize;
 
-        if end + 2 > self.input.len() || self.input[end..end + 2] != *b"\r\n" {
-            return Err("Invalid bulk string termination".to_string());
+        if end + 2 > self.input.len() {
+            return Err(RespError::Incomplete);
+        }
+        if self.input[end..end + 2] != *b"\r\n" {
+            return Err("Invalid bulk string termination".into());
         }
 
         self.pos = end + 2;
 
-        let string = str::from_utf8(&self.input[start..end])
-            .map_err(|_| "Invalid UTF-8 in bulk string".to_string())?
-            .to_string();
-
-        Ok(RespType::BulkString(Some(string)))
+        let bytes = Bytes::copy_from_slice(&self.input[start..end]);
+        Ok(RespType::BulkString(Some(bytes)))
     }
 
-    fn parse_bulk_error(&mut self) -> Result<RespType, String> {
+    fn parse_bulk_error(&mut self) -> Result<RespType, RespError> {
         let length: isize = self
             .read_line()?
             .parse()
             .map_err(|_| "Invalid bulk error length".to_string())?;
+        if !(0..=MAX_BULK_LEN).contains(&length) {
+            return Err("Invalid bulk error length".into());
+        }
         let start = self.pos;
         let end = self.pos + length as usize;
-        if end + 2 > self.input.len() || self.input[end..end + 2] != *b"\r\n" {
-            return Err("Invalid bulk error termination".to_string());
+        if end + 2 > self.input.len() {
+            return Err(RespError::Incomplete);
+        }
+        if self.input[end..end + 2] != *b"\r\n" {
+            return Err("Invalid bulk error termination".into());
         }
         let error = str::from_utf8(&self.input[start..end])
             .map_err(|_| "Invalid UTF-8 in bulk error")?
@@ -144,7 +195,7 @@ impl<'a> RespParser<'a> {
         Ok(RespType::BulkError(error))
     }
 
-    fn parse_array(&mut self) -> Result<RespType, String> {
+    fn parse_array(&mut self) -> Result<RespType, RespError> {
         let length: isize = self
             .read_line()?
             .parse()
@@ -153,6 +204,9 @@ impl<'a> RespParser<'a> {
         if length == -1 {
             return Ok(RespType::Array(None));
         }
+        if length < 0 {
+            return Err("Invalid array length".into());
+        }
 
         let mut elements = Vec::new();
         for _ in 0..length {
@@ -162,25 +216,25 @@ impl<'a> RespParser<'a> {
         Ok(RespType::Array(Some(elements)))
     }
 
-    fn parse_null(&mut self) -> Result<RespType, String> {
+    fn parse_null(&mut self) -> Result<RespType, RespError> {
         let line = self.read_line()?;
-        if line != "" {
-            return Err("Invalid null value".to_string());
+        if !line.is_empty() {
+            return Err("Invalid null value".into());
         }
         Ok(RespType::Null)
     }
 
-    fn parse_boolean(&mut self) -> Result<RespType, String> {
+    fn parse_boolean(&mut self) -> Result<RespType, RespError> {
         let value = self.read_line()?;
         let bool = match value.as_str() {
             "t" => true,
             "f" => false,
-            _ => return Err("Invalid boolean value".to_string()),
+            _ => return Err("Invalid boolean value".into()),
         };
-        return Ok(RespType::Boolean(bool));
+        Ok(RespType::Boolean(bool))
     }
 
-    fn parse_double(&mut self) -> Result<RespType, String> {
+    fn parse_double(&mut self) -> Result<RespType, RespError> {
         let value = self.read_line()?;
         let double: f64 = value
             .parse()
@@ -188,7 +242,7 @@ impl<'a> RespParser<'a> {
         Ok(RespType::Double(double))
     }
 
-    fn parse_big_number(&mut self) -> Result<RespType, String> {
+    fn parse_big_number(&mut self) -> Result<RespType, RespError> {
         let value = self.read_line()?;
         let big_num: BigInt = value
             .parse()
@@ -196,15 +250,265 @@ impl<'a> RespParser<'a> {
         Ok(RespType::BigNumber(big_num))
     }
 
-    fn read_line(&mut self) -> Result<String, String> {
-        if let Some(pos) = self.input[self.pos..].iter().position(|&b| b == b'\n') {
-            let line = &self.input[self.pos..self.pos + pos - 1]; // 去掉 \r
-            self.pos += pos + 1; // 跳过 \r\n
+    fn read_line(&mut self) -> Result<String, RespError> {
+        if let Some(pos) = self.input[self.pos..].windows(2).position(|w| w == b"\r\n") {
+            let line = &self.input[self.pos..self.pos + pos]; // 去掉 \r\n
+            self.pos += pos + 2; // 跳过 \r\n
             str::from_utf8(line)
                 .map(|s| s.to_string())
-                .map_err(|_| "Invalid UTF-8 in line".to_string())
+                .map_err(|_| "Invalid UTF-8 in line".into())
         } else {
-            Err("Line not terminated".to_string())
+            Err(RespError::Incomplete)
+        }
+    }
+}
+
+/// 解析客户端发送的请求。与 Redis 的 processMultibulkBuffer 一样在多次读取之间保存进度：
+/// 已经读到的参数直接从缓冲区中切出，不会重复解析或复制，查找行尾时也从上次停止的位置继续
+pub struct RequestParser {
+    /// 当前请求的参数个数，None 表示等待新的请求
+    multibulk_len: Option<usize>,
+    /// 当前参数的长度，None 表示还没有读到长度行
+    bulk_len: Option<usize>,
+    args: Vec<RespType>,
+    /// 缓冲区开头已经查找过行尾的字节数
+    searched: usize,
+    query_buffer_limit: usize,
+}
+
+impl Default for RequestParser {
+    fn default() -> Self {
+        Self {
+            multibulk_len: None,
+            bulk_len: None,
+            args: Vec::new(),
+            searched: 0,
+            query_buffer_limit: QUERY_BUFFER_LIMIT,
+        }
+    }
+}
+
+impl RequestParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 缓冲区中未处理的数据超过上限时需要关闭连接
+    pub fn query_buffer_exceeded(&self, buffer: &BytesMut) -> bool {
+        buffer.len() > self.query_buffer_limit
+    }
+
+    /// 从缓冲区中解析一条请求，数据不完整时返回 Incomplete，已经解析的部分会保留到下次调用
+    pub fn parse(&mut self, buffer: &mut BytesMut) -> Result<RespType, RespError> {
+        let total = match self.multibulk_len {
+            Some(total) => total,
+            None => match buffer.first() {
+                None => return Err(RespError::Incomplete),
+                Some(b'*') => {
+                    let len = self.read_len(buffer, "mbulk")?;
+                    if len == -1 {
+                        return Ok(RespType::Array(None));
+                    }
+                    if !(0..=MAX_MULTIBULK_LEN).contains(&len) {
+                        return Err("invalid multibulk length".into());
+                    }
+                    self.args = Vec::with_capacity((len as usize).min(MAX_PREALLOCATED_ARGS));
+                    self.multibulk_len = Some(len as usize);
+                    len as usize
+                }
+                // 不是数组的请求很少见，按完整的 RESP 消息解析
+                Some(_) => return self.parse_other(buffer),
+            },
+        };
+        while self.args.len() < total {
+            let len = match self.bulk_len {
+                Some(len) => len,
+                None => {
+                    match buffer.first() {
+                        None => return Err(RespError::Incomplete),
+                        Some(b'$') => {}
+                        Some(&marker) => return Err(format!("expected '$', got '{}'", marker as char).into()),
+                    }
+                    let len = self.read_len(buffer, "bulk")?;
+                    if !(0..=MAX_BULK_LEN).contains(&len) {
+                        return Err("invalid bulk length".into());
+                    }
+                    self.bulk_len = Some(len as usize);
+                    len as usize
+                }
+            };
+            if buffer.len() < len + 2 {
+                return Err(RespError::Incomplete);
+            }
+            if buffer[len..len + 2] != *b"\r\n" {
+                return Err("Invalid bulk string termination".into());
+            }
+            let arg = buffer.split_to(len).freeze();
+            buffer.advance(2);
+            self.args.push(RespType::BulkString(Some(arg)));
+            self.bulk_len = None;
+        }
+        self.multibulk_len = None;
+        Ok(RespType::Array(Some(mem::take(&mut self.args))))
+    }
+
+    /// 读取缓冲区开头 `*<len>` 或 `$<len>` 形式的长度行
+    fn read_len(&mut self, buffer: &mut BytesMut, kind: &str) -> Result<isize, RespError> {
+        // 上次查找停在 \r 之后时，需要从 \r 开始重新查找
+        let start = self.searched.saturating_sub(1);
+        let end = match buffer[start..].windows(2).position(|w| w == b"\r\n") {
+            Some(pos) => start + pos,
+            None => {
+                if buffer.len() > MAX_LINE_LEN {
+                    return Err(format!("too big {} count string", kind).into());
+                }
+                self.searched = buffer.len();
+                return Err(RespError::Incomplete);
+            }
+        };
+        self.searched = 0;
+        let line = buffer.split_to(end);
+        buffer.advance(2);
+        str::from_utf8(&line[1..])
+            .ok()
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(|| format!("invalid {} length", if kind == "mbulk" { "multibulk" } else { "bulk" }).into())
+    }
+
+    fn parse_other(&mut self, buffer: &mut BytesMut) -> Result<RespType, RespError> {
+        let mut parser = RespParser::new(buffer);
+        match parser.parse() {
+            Ok(resp) => {
+                let consumed = parser.consumed();
+                buffer.advance(consumed);
+                Ok(resp)
+            }
+            Err(RespError::Incomplete) if buffer.len() > MAX_LINE_LEN && !buffer.windows(2).any(|w| w == b"\r\n") => {
+                Err("too big inline request".into())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Bytes, BytesMut};
+
+    use super::{RequestParser, RespError, RespType, MAX_LINE_LEN};
+
+    const SET: &[u8] = b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$12\r\nhello\r\nworld\r\n";
+
+    fn args(resp: RespType) -> Vec<Bytes> {
+        match resp {
+            RespType::Array(Some(elements)) => elements
+                .into_iter()
+                .map(|element| match element {
+                    RespType::BulkString(Some(arg)) => arg,
+                    other => panic!("unexpected element {:?}", other),
+                })
+                .collect(),
+            other => panic!("unexpected request {:?}", other),
+        }
+    }
+
+    fn set_args() -> Vec<Bytes> {
+        vec![Bytes::from("SET"), Bytes::from("key"), Bytes::from("hello\r\nworld")]
+    }
+
+    fn error(result: Result<RespType, RespError>) -> String {
+        match result {
+            Err(RespError::Invalid(e)) => e,
+            other => panic!("expected a protocol error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn split_at_every_offset() {
+        for split in 0..=SET.len() {
+            let mut parser = RequestParser::new();
+            let mut buffer = BytesMut::from(&SET[..split]);
+            if split < SET.len() {
+                assert!(matches!(parser.parse(&mut buffer), Err(RespError::Incomplete)), "split at {}", split);
+                buffer.extend_from_slice(&SET[split..]);
+            }
+            assert_eq!(args(parser.parse(&mut buffer).unwrap()), set_args(), "split at {}", split);
+            assert!(buffer.is_empty());
         }
     }
+
+    #[test]
+    fn one_byte_at_a_time() {
+        let mut parser = RequestParser::new();
+        let mut buffer = BytesMut::new();
+        for (i, &byte) in SET.iter().enumerate() {
+            buffer.extend_from_slice(&[byte]);
+            match parser.parse(&mut buffer) {
+                Ok(resp) => {
+                    assert_eq!(i, SET.len() - 1);
+                    assert_eq!(args(resp), set_args());
+                }
+                Err(RespError::Incomplete) => assert!(i < SET.len() - 1),
+                Err(RespError::Invalid(e)) => panic!("{}", e),
+            }
+        }
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn pipelined_requests() {
+        let mut parser = RequestParser::new();
+        let mut buffer = BytesMut::from(SET);
+        buffer.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+        buffer.extend_from_slice(&SET[..10]);
+        assert_eq!(args(parser.parse(&mut buffer).unwrap()), set_args());
+        assert_eq!(args(parser.parse(&mut buffer).unwrap()), vec![Bytes::from("PING")]);
+        assert!(matches!(parser.parse(&mut buffer), Err(RespError::Incomplete)));
+        buffer.extend_from_slice(&SET[10..]);
+        assert_eq!(args(parser.parse(&mut buffer).unwrap()), set_args());
+        assert!(matches!(parser.parse(&mut buffer), Err(RespError::Incomplete)));
+    }
+
+    #[test]
+    fn length_limits() {
+        let mut buffer = BytesMut::from(&b"*1\r\n$536870913\r\n"[..]);
+        assert_eq!(error(RequestParser::new().parse(&mut buffer)), "invalid bulk length");
+        let mut buffer = BytesMut::from(&b"*1\r\n$536870912\r\n"[..]);
+        assert!(matches!(RequestParser::new().parse(&mut buffer), Err(RespError::Incomplete)));
+        let mut buffer = BytesMut::from(&b"*2147483648\r\n"[..]);
+        assert_eq!(error(RequestParser::new().parse(&mut buffer)), "invalid multibulk length");
+        let mut buffer = BytesMut::from(&b"*1\r\n$-1\r\n"[..]);
+        assert_eq!(error(RequestParser::new().parse(&mut buffer)), "invalid bulk length");
+
+        let long_line = vec![b'1'; MAX_LINE_LEN + 1];
+        let mut buffer = BytesMut::from(&b"*"[..]);
+        buffer.extend_from_slice(&long_line);
+        assert_eq!(error(RequestParser::new().parse(&mut buffer)), "too big mbulk count string");
+        let mut buffer = BytesMut::from(&b"*1\r\n$"[..]);
+        buffer.extend_from_slice(&long_line);
+        assert_eq!(error(RequestParser::new().parse(&mut buffer)), "too big bulk count string");
+        let mut buffer = BytesMut::from(&b"+"[..]);
+        buffer.extend_from_slice(&long_line);
+        assert_eq!(error(RequestParser::new().parse(&mut buffer)), "too big inline request");
+
+        // 长度行刚好没有超过上限时继续等待
+        let mut buffer = BytesMut::from(&b"*1\r\n$"[..]);
+        buffer.extend_from_slice(&long_line[..MAX_LINE_LEN - 4]);
+        assert!(matches!(RequestParser::new().parse(&mut buffer), Err(RespError::Incomplete)));
+    }
+
+    #[test]
+    fn query_buffer_limit() {
+        let mut parser = RequestParser {
+            query_buffer_limit: 32,
+            ..Default::default()
+        };
+        let mut buffer = BytesMut::from(&b"*2\r\n$4\r\nECHO\r\n$100\r\n"[..]);
+        buffer.extend_from_slice(&[b'x'; 16]);
+        assert!(matches!(parser.parse(&mut buffer), Err(RespError::Incomplete)));
+        assert!(!parser.query_buffer_exceeded(&buffer));
+        buffer.extend_from_slice(&[b'x'; 32]);
+        assert!(matches!(parser.parse(&mut buffer), Err(RespError::Incomplete)));
+        assert!(parser.query_buffer_exceeded(&buffer));
+    }
 }
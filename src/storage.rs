@@ -1,48 +1,394 @@
-use std::{collections::HashMap, sync::LazyLock};
-use time::OffsetDateTime;
-use tokio::{sync::RwLock, time::Instant};
-use crate::rdb::RdbValue;
+use bytes::Bytes;
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    future::Future,
+    sync::{Mutex, OnceLock},
+};
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::random;
 
-static STORAGE: LazyLock<RwLock<HashMap<String, Item>>> =
-    LazyLock::new(|| RwLock::new(HashMap::new()));
+mod bitops;
+mod dict;
+mod expire;
+mod hash;
+mod hyperloglog;
+mod lazyfree;
+mod list;
+mod set;
+mod stream;
+mod stream_group;
+mod string;
+mod zset;
+
+pub use bitops::*;
+pub use dict::Dict;
+pub use expire::active_expire;
+pub use hash::*;
+pub use hyperloglog::*;
+pub use lazyfree::{free_async, free_items};
+pub use list::*;
+pub use set::*;
+pub use stream::*;
+pub use stream_group::*;
+pub use string::*;
+pub use zset::*;
+
+pub const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+/// 默认的数据库数量
+pub const DEFAULT_DATABASES: usize = 16;
+
+static STORAGE: OnceLock<Vec<RwLock<Db>>> = OnceLock::new();
+
+tokio::task_local! {
+    /// 当前连接选择的数据库
+    static SELECTED_DB: Cell<usize>;
+}
+
+/// 键对应的值
+#[derive(Clone, Debug)]
+pub enum Value {
+    String(StringValue),
+    List(VecDeque<Bytes>),
+    Hash(Hash),
+    Set(Set),
+    ZSet(SortedSet),
+    Stream(Stream),
+}
+
+impl Value {
+    /// TYPE 返回的类型名
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::ZSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    /// 集合类型为空时需要删除对应的键
+    pub fn is_empty_collection(&self) -> bool {
+        match self {
+            Value::String(_) => false,
+            Value::List(list) => list.is_empty(),
+            Value::Hash(hash) => hash.is_empty(),
+            Value::Set(set) => set.is_empty(),
+            Value::ZSet(zset) => zset.is_empty(),
+            // 空的流不会被自动删除
+            Value::Stream(_) => false,
+        }
+    }
+
+    /// OBJECT ENCODING 返回的编码名
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Value::String(value) => value.encoding(),
+            Value::List(_) => "quicklist",
+            Value::Hash(_) => "hashtable",
+            Value::Set(set) => set.encoding(),
+            Value::ZSet(_) => "skiplist",
+            Value::Stream(_) => "stream",
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
-struct Item {
-    value: String,
-    expires: Option<OffsetDateTime>,
-    created: Instant,
-}
-
-pub async fn set(key: &str, value: &str, expires: Option<OffsetDateTime>) {
-    let mut store = STORAGE.write().await;
-    let item = Item {
-        value: value.to_string(),
-        expires: expires,
-        created: Instant::now(),
-    };
-    store.insert(key.to_string(), item);
-}
-
-pub async fn get(key: &str) -> Option<String> {
-    let store = STORAGE.read().await;
-    let item = store.get(key).cloned();
-    match item {
-        Some(item) => {
-            if let Some(expires) = item.expires {
-                if expires < OffsetDateTime::now_utc(){
-                    // let mut store = STORAGE.write().await;
-                    // store.remove(key);
-                    // TODO: Remove the key from the storage
-                    return None;
-                }
+pub struct Item {
+    pub value: Value,
+    pub expires: Option<OffsetDateTime>,
+}
+
+impl Item {
+    pub fn new(value: Value) -> Self {
+        Self {
+            value,
+            expires: None,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| expires <= OffsetDateTime::now_utc())
+    }
+}
+
+/// 一个数据库（键空间）
+#[derive(Default, Debug)]
+pub struct Db {
+    items: Dict<Bytes, Item>,
+    volatile: expire::Volatile,
+    /// 只读访问时发现的过期键，在下一次获取写锁时删除
+    lazy_expired: Mutex<Vec<Bytes>>,
+    /// 因过期而被删除的键数
+    pub expired_keys: u64,
+    /// 设置了过期时间的键的平均剩余生存时间（毫秒），由主动过期的抽样估算
+    pub avg_ttl: u64,
+}
+
+impl Db {
+    /// 读取未过期的键
+    pub fn get(&self, key: &[u8]) -> Option<&Item> {
+        let item = self.items.get(key)?;
+        if item.is_expired() {
+            if let Ok(mut lazy_expired) = self.lazy_expired.lock() {
+                lazy_expired.push(Bytes::copy_from_slice(key));
+            }
+            return None;
+        }
+        Some(item)
+    }
+
+    /// 可写地读取未过期的键，已过期的键会被删除
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Item> {
+        self.expire_if_needed(key);
+        self.items.get_mut(key)
+    }
+
+    pub fn insert(&mut self, key: Bytes, item: Item) {
+        if item.expires.is_some() {
+            self.volatile.insert(key.clone());
+        } else {
+            self.volatile.remove(&key);
+        }
+        self.items.insert(key, item);
+    }
+
+    /// 设置或移除键的过期时间，键不存在时返回 false
+    pub fn set_expires(&mut self, key: &[u8], expires: Option<OffsetDateTime>) -> bool {
+        match self.get_mut(key) {
+            Some(item) => item.expires = expires,
+            None => return false,
+        }
+        if expires.is_some() {
+            self.volatile.insert(Bytes::copy_from_slice(key));
+        } else {
+            self.volatile.remove(key);
+        }
+        true
+    }
+
+    /// 删除键，返回键是否存在
+    pub fn delete(&mut self, key: &[u8]) -> bool {
+        self.take(key).is_some()
+    }
+
+    /// 删除未过期的键并返回其内容
+    pub fn take(&mut self, key: &[u8]) -> Option<Item> {
+        self.expire_if_needed(key);
+        self.volatile.remove(key);
+        self.items.remove(key)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> bool {
+        self.volatile.remove(key);
+        self.items.remove(key).is_some()
+    }
+
+    /// 清空数据库，返回原有的键，由调用方决定如何释放
+    pub fn clear(&mut self) -> Dict<Bytes, Item> {
+        self.volatile = Default::default();
+        self.avg_ttl = 0;
+        if let Ok(lazy_expired) = self.lazy_expired.get_mut() {
+            lazy_expired.clear();
+        }
+        std::mem::take(&mut self.items)
+    }
+
+    /// 随机返回一个未过期的键
+    pub fn random_key(&self) -> Option<Bytes> {
+        // 过期的键可能还未删除，最多重试若干次
+        for _ in 0..100 {
+            let (key, item) = self.items.random()?;
+            if !item.is_expired() {
+                return Some(key.clone());
             }
-            Some(item.value)
         }
-        None => None,
+        None
+    }
+
+    /// 键已过期时删除，返回是否删除
+    fn expire_if_needed(&mut self, key: &[u8]) -> bool {
+        if self.items.get(key).is_some_and(|item| item.is_expired()) {
+            self.remove(key);
+            self.expired_keys += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 删除只读访问时发现的过期键
+    fn delete_lazy_expired(&mut self) {
+        let keys = match self.lazy_expired.get_mut() {
+            Ok(keys) if !keys.is_empty() => std::mem::take(keys),
+            _ => return,
+        };
+        for key in keys {
+            self.expire_if_needed(&key);
+        }
+    }
+
+    /// 集合类型在最后一个元素被移除后删除键
+    pub fn remove_if_empty(&mut self, key: &[u8]) {
+        if self
+            .items
+            .get(key)
+            .is_some_and(|item| item.value.is_empty_collection())
+        {
+            self.remove(key);
+        }
+    }
+
+    /// 遍历未过期的键值对
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &Item)> {
+        self.items.iter().filter(|(_, item)| !item.is_expired())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Bytes> {
+        self.items
+            .iter()
+            .filter(|(_, item)| !item.is_expired())
+            .map(|(key, _)| key)
+    }
+
+    /// 按游标遍历键空间，返回下一个游标，结果中可能包含已过期的键
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&Bytes, &Item)>) {
+        self.items.scan_batch(cursor, count)
+    }
+
+    /// 键的总数，包括尚未被删除的过期键
+    pub fn size(&self) -> usize {
+        self.items.len()
+    }
+
+    /// 设置了过期时间的键数
+    pub fn volatile_len(&self) -> usize {
+        self.volatile.len()
+    }
+}
+
+/// 当前的毫秒时间戳
+pub fn now_ms() -> u64 {
+    (OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as u64
+}
+
+/// 毫秒时间戳转为时间，超出可表示的范围时取边界值
+pub fn from_unix_ms(ms: i64) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000).unwrap_or(if ms < 0 {
+        PrimitiveDateTime::MIN.assume_utc()
+    } else {
+        PrimitiveDateTime::MAX.assume_utc()
+    })
+}
+
+/// 时间转为毫秒时间戳
+pub fn unix_ms(time: OffsetDateTime) -> i64 {
+    (time.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+/// 严格解析整数：不允许前导 0、正号和空白，与 Redis 的 string2ll 一致
+pub fn parse_strict_int(value: &[u8]) -> Option<i64> {
+    let digits = value.strip_prefix(b"-").unwrap_or(value);
+    if digits.is_empty() || digits.len() > 20 || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    if digits[0] == b'0' && (digits.len() > 1 || value.len() > 1) {
+        return None;
+    }
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
+
+/// 创建指定数量的数据库，需要在访问数据前调用
+pub fn init(databases: usize) {
+    let _ = STORAGE.set((0..databases).map(|_| RwLock::new(Db::default())).collect());
+}
+
+fn databases() -> &'static [RwLock<Db>] {
+    STORAGE.get().expect("storage is not initialized")
+}
+
+/// 数据库的数量
+pub fn db_count() -> usize {
+    databases().len()
+}
+
+/// 为连接设置独立的数据库选择，初始为 0 号数据库
+pub async fn with_selected_db<F: Future>(f: F) -> F::Output {
+    SELECTED_DB.scope(Cell::new(0), f).await
+}
+
+/// 当前选择的数据库，不在连接中时为 0 号数据库
+pub fn selected_db() -> usize {
+    SELECTED_DB.try_with(Cell::get).unwrap_or(0)
+}
+
+/// 切换当前连接的数据库，编号超出范围时返回 false
+pub fn select_db(index: usize) -> bool {
+    if index >= db_count() {
+        return false;
+    }
+    SELECTED_DB.with(|selected| selected.set(index));
+    true
+}
+
+pub async fn read() -> RwLockReadGuard<'static, Db> {
+    read_db(selected_db()).await
+}
+
+pub async fn write() -> RwLockWriteGuard<'static, Db> {
+    write_db(selected_db()).await
+}
+
+pub async fn read_db(index: usize) -> RwLockReadGuard<'static, Db> {
+    databases()[index].read().await
+}
+
+pub async fn write_db(index: usize) -> RwLockWriteGuard<'static, Db> {
+    let mut db = databases()[index].write().await;
+    db.delete_lazy_expired();
+    db
+}
+
+/// 同时获取两个不同数据库的写锁，按编号顺序加锁以避免死锁
+pub async fn write_pair(a: usize, b: usize) -> (RwLockWriteGuard<'static, Db>, RwLockWriteGuard<'static, Db>) {
+    if a < b {
+        let first = write_db(a).await;
+        (first, write_db(b).await)
+    } else {
+        let second = write_db(b).await;
+        (write_db(a).await, second)
+    }
+}
+
+/// 按编号顺序获取所有数据库的读锁，用于生成一致的快照
+pub async fn read_all() -> Vec<RwLockReadGuard<'static, Db>> {
+    let mut guards = Vec::with_capacity(db_count());
+    for index in 0..db_count() {
+        guards.push(read_db(index).await);
     }
+    guards
+}
+
+/// 按编号顺序获取所有数据库的写锁
+pub async fn write_all() -> Vec<RwLockWriteGuard<'static, Db>> {
+    let mut guards = Vec::with_capacity(db_count());
+    for index in 0..db_count() {
+        guards.push(write_db(index).await);
+    }
+    guards
+}
+
+pub async fn get(key: &[u8]) -> Result<Option<Bytes>, String> {
+    let store = read().await;
+    store.string(key)
 }
 
-pub async fn keys() -> Vec<String> {
-    let store = STORAGE.read().await;
+pub async fn keys() -> Vec<Bytes> {
+    let store = read().await;
     store.keys().cloned().collect()
 }
@@ -0,0 +1,239 @@
+//! 位运算的实现，字符串按大端位序（每个字节的最高位是第 0 位）看作位数组
+
+/// 读取一位，超出字符串长度的位为 0
+pub fn get_bit(value: &[u8], offset: u64) -> u8 {
+    let byte = (offset >> 3) as usize;
+    let bit = 7 - (offset & 7) as u8;
+    value.get(byte).map_or(0, |byte| (byte >> bit) & 1)
+}
+
+/// 设置一位并返回原来的值，调用者需要保证字符串足够长
+pub fn set_bit(value: &mut [u8], offset: u64, on: u8) -> u8 {
+    let byte = (offset >> 3) as usize;
+    let bit = 7 - (offset & 7) as u8;
+    let old = (value[byte] >> bit) & 1;
+    value[byte] = (value[byte] & !(1 << bit)) | (on << bit);
+    old
+}
+
+/// BITCOUNT/BITPOS 的范围单位
+#[derive(Clone, Copy, PartialEq)]
+pub enum RangeUnit {
+    Byte,
+    Bit,
+}
+
+/// 按 Redis 的规则把 start/end 转换为字节范围，返回 (start, end, 首字节掩码, 尾字节掩码)。
+/// 掩码中置位的位不在范围内，start > end 表示范围为空
+pub fn byte_range(len: usize, start: i64, end: i64, unit: RangeUnit) -> (i64, i64, u8, u8) {
+    let total = match unit {
+        RangeUnit::Byte => len as i64,
+        RangeUnit::Bit => (len as i64) << 3,
+    };
+    let mut start = if start < 0 { start + total } else { start };
+    let mut end = if end < 0 { end + total } else { end };
+    start = start.max(0);
+    end = end.max(0).min(total - 1);
+    let (mut first_mask, mut last_mask) = (0, 0);
+    if unit == RangeUnit::Bit && start <= end {
+        first_mask = !((1u16 << (8 - (start & 7))) - 1) as u8;
+        last_mask = ((1u16 << (7 - (end & 7))) - 1) as u8;
+        start >>= 3;
+        end >>= 3;
+    }
+    (start, end, first_mask, last_mask)
+}
+
+/// 统计范围内置位的位数
+pub fn bit_count(value: &[u8], start: i64, end: i64, first_mask: u8, last_mask: u8) -> u64 {
+    if start > end {
+        return 0;
+    }
+    let bytes = &value[start as usize..=end as usize];
+    let mut count: u64 = bytes.iter().map(|byte| byte.count_ones() as u64).sum();
+    count -= (bytes[0] & first_mask).count_ones() as u64;
+    count -= (bytes[bytes.len() - 1] & last_mask).count_ones() as u64;
+    count
+}
+
+/// 查找第一个值为 bit 的位，找不到 1 时返回 -1，找不到 0 时返回范围之后的第一位，与 redisBitpos 一致
+fn find_bit(bytes: &[u8], bit: u8) -> i64 {
+    let skip = if bit == 1 { 0 } else { 0xff };
+    match bytes.iter().position(|&byte| byte != skip) {
+        Some(index) => {
+            let byte = if bit == 1 { bytes[index] } else { !bytes[index] };
+            (index as i64) * 8 + byte.leading_zeros() as i64
+        }
+        None if bit == 1 => -1,
+        None => (bytes.len() as i64) * 8,
+    }
+}
+
+/// 在 byte_range 计算出的范围内查找第一个值为 bit 的位，与 bitposCommand 一致。
+/// 指定了 end 时，范围内没有 0 返回 -1，否则认为字符串右侧用 0 填充
+pub fn bit_pos(value: &[u8], bit: u8, range: (i64, i64, u8, u8), end_given: bool) -> i64 {
+    let (mut start, end, first_mask, last_mask) = range;
+    if start > end {
+        return -1;
+    }
+    // 把不在范围内的位设置为与要查找的值相反
+    let mask = |byte: u8, mask: u8| if bit == 1 { byte & !mask } else { byte | mask };
+    let mut bytes = end - start + 1;
+    let pos = 'result: {
+        if first_mask != 0 {
+            let mut byte = mask(value[start as usize], first_mask);
+            // 范围只有一个字节
+            if last_mask != 0 && bytes == 1 {
+                byte = mask(byte, last_mask);
+            }
+            let pos = find_bit(&[byte], bit);
+            if bytes == 1 || (pos != -1 && pos != 8) {
+                break 'result pos;
+            }
+            start += 1;
+            bytes -= 1;
+        }
+        // 尾字节有不在范围内的位时单独处理
+        let whole = bytes - if last_mask != 0 { 1 } else { 0 };
+        if whole > 0 {
+            let pos = find_bit(&value[start as usize..(start + whole) as usize], bit);
+            if bytes == whole || (pos != -1 && pos != whole << 3) {
+                break 'result pos;
+            }
+            start += whole;
+            bytes -= whole;
+        }
+        find_bit(&[mask(value[end as usize], last_mask)], bit)
+    };
+    if end_given && bit == 0 && pos == bytes << 3 {
+        return -1;
+    }
+    if pos == -1 {
+        return -1;
+    }
+    pos + (start << 3)
+}
+
+/// BITOP 的运算
+#[derive(Clone, Copy, PartialEq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+/// 对多个字符串按位运算，较短的字符串用 0 填充
+pub fn bit_op(op: BitOp, sources: &[&[u8]]) -> Vec<u8> {
+    let len = sources.iter().map(|source| source.len()).max().unwrap_or(0);
+    (0..len)
+        .map(|i| {
+            let byte = |source: &[u8]| source.get(i).copied().unwrap_or(0);
+            let first = byte(sources[0]);
+            match op {
+                BitOp::Not => !first,
+                BitOp::And => sources[1..].iter().fold(first, |acc, source| acc & byte(source)),
+                BitOp::Or => sources[1..].iter().fold(first, |acc, source| acc | byte(source)),
+                BitOp::Xor => sources[1..].iter().fold(first, |acc, source| acc ^ byte(source)),
+            }
+        })
+        .collect()
+}
+
+/// BITFIELD 的溢出处理方式
+#[derive(Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+/// BITFIELD 的整数类型，有符号最多 64 位，无符号最多 63 位
+#[derive(Clone, Copy)]
+pub struct BitfieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl BitfieldType {
+    /// 读取 offset 开始的整数，超出字符串长度的位为 0
+    pub fn get(&self, value: &[u8], offset: u64) -> i64 {
+        let mut n: u64 = 0;
+        for i in 0..self.bits as u64 {
+            n = (n << 1) | get_bit(value, offset + i) as u64;
+        }
+        if self.signed && self.bits < 64 && n & (1 << (self.bits - 1)) != 0 {
+            n |= u64::MAX << self.bits;
+        }
+        n as i64
+    }
+
+    /// 把整数的低 bits 位写入 offset 开始的位置，调用者需要保证字符串足够长
+    pub fn set(&self, value: &mut [u8], offset: u64, n: i64) {
+        let n = n as u64;
+        for i in 0..self.bits as u64 {
+            let bit = ((n >> (self.bits as u64 - 1 - i)) & 1) as u8;
+            set_bit(value, offset + i, bit);
+        }
+    }
+
+    /// 计算 value + incr，溢出时按 overflow 处理，FAIL 时返回 None。
+    /// SET 以 incr 为 0 调用，检查写入的值本身是否溢出
+    pub fn add(&self, value: i64, incr: i64, overflow: Overflow) -> Option<i64> {
+        let (overflowed, limit) = if self.signed {
+            self.check_signed(value, incr)
+        } else {
+            self.check_unsigned(value as u64, incr)
+        };
+        match (overflowed, overflow) {
+            (false, _) => Some(value.wrapping_add(incr)),
+            (true, Overflow::Fail) => None,
+            (true, Overflow::Sat) => Some(limit),
+            (true, Overflow::Wrap) => Some(self.wrap(value, incr)),
+        }
+    }
+
+    /// 返回是否溢出以及 SAT 方式下的饱和值
+    fn check_signed(&self, value: i64, incr: i64) -> (bool, i64) {
+        let max = if self.bits == 64 { i64::MAX } else { (1i64 << (self.bits - 1)) - 1 };
+        let min = -max - 1;
+        // 与 Redis 一样，只有在 value 处于范围内时才会使用这两个可能溢出的值
+        let max_incr = (max as u64).wrapping_sub(value as u64) as i64;
+        let min_incr = min.wrapping_sub(value);
+        let small = self.bits != 64;
+        if value > max || (small && incr > max_incr) || (value >= 0 && incr > 0 && incr > max_incr) {
+            (true, max)
+        } else if value < min || (small && incr < min_incr) || (value < 0 && incr < 0 && incr < min_incr) {
+            (true, min)
+        } else {
+            (false, 0)
+        }
+    }
+
+    fn check_unsigned(&self, value: u64, incr: i64) -> (bool, i64) {
+        let max = (1u64 << self.bits) - 1;
+        let max_incr = max.wrapping_sub(value) as i64;
+        let min_incr = 0u64.wrapping_sub(value) as i64;
+        if value > max || (incr > 0 && incr > max_incr) {
+            (true, max as i64)
+        } else if incr < 0 && incr < min_incr {
+            (true, 0)
+        } else {
+            (false, 0)
+        }
+    }
+
+    /// 按二进制补码回绕到 bits 位
+    fn wrap(&self, value: i64, incr: i64) -> i64 {
+        let mut n = (value as u64).wrapping_add(incr as u64);
+        if self.bits < 64 {
+            let mask = u64::MAX << self.bits;
+            if self.signed && n & (1 << (self.bits - 1)) != 0 {
+                n |= mask;
+            } else {
+                n &= !mask;
+            }
+        }
+        n as i64
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug, Formatter},
+    hash::{BuildHasher, Hash},
+    mem,
+};
+
+use crate::random;
+
+/// 哈希表的初始桶数
+const DICT_HT_INITIAL_SIZE: usize = 4;
+/// 元素数少于桶数的 1/8 时缩容，与 Redis 的 HASHTABLE_MIN_FILL 一致
+const HASHTABLE_MIN_FILL: usize = 8;
+/// 每次迁移最多跳过的空桶数，与 Redis 的 dictRehash(d, 1) 一致
+const REHASH_EMPTY_VISITS: usize = 10;
+
+/// 与 Redis 的 dict 一样使用 2 的幂个桶并用链表解决冲突的哈希表。
+/// 扩容和缩容时同时保留新旧两张表，每次修改时迁移一个桶（渐进式 rehash），避免一次迁移所有元素。
+/// 桶的布局是公开的约定，SCAN 依赖它实现无状态的反向二进制游标
+#[derive(Clone)]
+pub struct Dict<K, V> {
+    /// rehash 期间元素从 tables[0] 迁移到 tables[1]，完成后 tables[1] 成为 tables[0]
+    tables: [Vec<Vec<(K, V)>>; 2],
+    /// 下一个要迁移的 tables[0] 的桶，None 表示没有在 rehash
+    rehash_index: Option<usize>,
+    len: usize,
+    hasher: RandomState,
+}
+
+impl<K, V> Default for Dict<K, V> {
+    fn default() -> Self {
+        Self {
+            tables: [Vec::new(), Vec::new()],
+            rehash_index: None,
+            len: 0,
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+fn new_table<K, V>(size: usize) -> Vec<Vec<(K, V)>> {
+    (0..size).map(|_| Vec::new()).collect()
+}
+
+impl<K, V> Dict<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.tables.iter().flatten().flatten().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// 随机返回一个元素：先随机选择非空的桶，再在桶内随机选择，与 dictGetRandomKey 一致。
+    /// rehash 期间 tables[0] 中已迁移的桶都是空的，从未迁移的桶和 tables[1] 中选择
+    pub fn random(&self) -> Option<(&K, &V)> {
+        if self.is_empty() {
+            return None;
+        }
+        let skipped = self.rehash_index.unwrap_or(0);
+        let (first, second) = (&self.tables[0], &self.tables[1]);
+        loop {
+            let index = skipped + random::below(first.len() + second.len() - skipped);
+            let bucket = match index.checked_sub(first.len()) {
+                Some(index) => &second[index],
+                None => &first[index],
+            };
+            if !bucket.is_empty() {
+                let (key, value) = &bucket[random::below(bucket.len())];
+                return Some((key, value));
+            }
+        }
+    }
+
+    /// 遍历游标对应的桶并返回下一个游标，返回 0 表示遍历结束。
+    /// 游标按反向二进制递增，因此两次调用之间表扩容或缩容时，
+    /// 遍历开始时就存在的元素仍然至少会被返回一次
+    pub fn scan<'a>(&'a self, cursor: u64, mut f: impl FnMut(&'a K, &'a V)) -> u64 {
+        let mut emit = |bucket: &'a Vec<(K, V)>| {
+            for (key, value) in bucket {
+                f(key, value);
+            }
+        };
+        if self.rehash_index.is_none() {
+            let table = &self.tables[0];
+            if table.is_empty() {
+                return 0;
+            }
+            let mask = (table.len() - 1) as u64;
+            emit(&table[(cursor & mask) as usize]);
+            // 高位加一：先把未使用的位置 1，翻转后加一再翻转回来
+            let cursor = (cursor | !mask).reverse_bits().wrapping_add(1);
+            return cursor.reverse_bits();
+        }
+        // rehash 期间先遍历小表中游标对应的桶，再遍历大表中由这个桶扩展出的所有桶，与 dictScan 一致
+        let (small, large) = if self.tables[0].len() <= self.tables[1].len() {
+            (&self.tables[0], &self.tables[1])
+        } else {
+            (&self.tables[1], &self.tables[0])
+        };
+        let (small_mask, large_mask) = ((small.len() - 1) as u64, (large.len() - 1) as u64);
+        emit(&small[(cursor & small_mask) as usize]);
+        let mut cursor = cursor;
+        loop {
+            emit(&large[(cursor & large_mask) as usize]);
+            // 只递增小表掩码没有覆盖的高位，进位后即为小表的下一个游标
+            cursor = (cursor | !large_mask).reverse_bits().wrapping_add(1).reverse_bits();
+            if cursor & (small_mask ^ large_mask) == 0 {
+                return cursor;
+            }
+        }
+    }
+
+    /// 按 COUNT 提示遍历，最多访问 count * 10 个桶，返回下一个游标和遍历到的元素
+    pub fn scan_batch(&self, mut cursor: u64, count: usize) -> (u64, Vec<(&K, &V)>) {
+        let mut found = Vec::new();
+        let mut max_iterations = count.saturating_mul(10);
+        loop {
+            cursor = self.scan(cursor, |key, value| found.push((key, value)));
+            max_iterations = max_iterations.saturating_sub(1);
+            if cursor == 0 || max_iterations == 0 || found.len() >= count {
+                break;
+            }
+        }
+        (cursor, found)
+    }
+}
+
+impl<K: Hash + Eq, V> Dict<K, V> {
+    /// 查找键所在的表和桶
+    fn find<Q>(&self, key: &Q) -> Option<(usize, usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.len == 0 {
+            return None;
+        }
+        let hash = self.hasher.hash_one(key) as usize;
+        let tables = if self.rehash_index.is_some() { 2 } else { 1 };
+        (0..tables).find_map(|table| {
+            let buckets = &self.tables[table];
+            let index = hash & (buckets.len() - 1);
+            let position = buckets[index].iter().position(|(k, _)| k.borrow() == key)?;
+            Some((table, index, position))
+        })
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (table, index, position) = self.find(key)?;
+        Some(&self.tables[table][index][position].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.rehash_step();
+        let (table, index, position) = self.find(key)?;
+        Some(&mut self.tables[table][index][position].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// 插入或替换，返回原来的值
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(current) = self.get_mut(&key) {
+            return Some(mem::replace(current, value));
+        }
+        if self.rehash_index.is_none() && self.len >= self.tables[0].len() {
+            self.resize(self.len + 1);
+        }
+        // rehash 期间新元素直接写入新表
+        let table = &mut self.tables[self.rehash_index.is_some() as usize];
+        let index = self.hasher.hash_one(&key) as usize & (table.len() - 1);
+        table[index].push((key, value));
+        self.len += 1;
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.rehash_step();
+        let (table, index, position) = self.find(key)?;
+        let (_, value) = self.tables[table][index].swap_remove(position);
+        self.len -= 1;
+        let size = self.tables[0].len();
+        if self.rehash_index.is_none() && size > DICT_HT_INITIAL_SIZE && self.len * HASHTABLE_MIN_FILL < size {
+            self.resize(self.len);
+        }
+        Some(value)
+    }
+
+    /// 开始调整为能容纳 size 个元素的最小的 2 的幂个桶，表为空时直接替换
+    fn resize(&mut self, size: usize) {
+        let size = size.max(DICT_HT_INITIAL_SIZE).next_power_of_two();
+        if self.len == 0 {
+            self.tables[0] = new_table(size);
+            return;
+        }
+        self.tables[1] = new_table(size);
+        self.rehash_index = Some(0);
+    }
+
+    /// 迁移 tables[0] 中的一个非空桶，最多跳过 REHASH_EMPTY_VISITS 个空桶，与 _dictRehashStep 一致
+    fn rehash_step(&mut self) {
+        let mut index = match self.rehash_index {
+            Some(index) => index,
+            None => return,
+        };
+        let mut empty_visits = REHASH_EMPTY_VISITS;
+        while index < self.tables[0].len() && self.tables[0][index].is_empty() {
+            empty_visits -= 1;
+            index += 1;
+            if empty_visits == 0 {
+                self.rehash_index = Some(index);
+                return;
+            }
+        }
+        let [old, new] = &mut self.tables;
+        if let Some(bucket) = old.get_mut(index) {
+            let mask = new.len() - 1;
+            for (key, value) in mem::take(bucket) {
+                let target = self.hasher.hash_one(&key) as usize & mask;
+                new[target].push((key, value));
+            }
+            index += 1;
+        }
+        if index >= self.tables[0].len() {
+            self.tables[0] = mem::take(&mut self.tables[1]);
+            self.rehash_index = None;
+        } else {
+            self.rehash_index = Some(index);
+        }
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for Dict<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for Dict<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut dict = Dict::new();
+        for (key, value) in iter {
+            dict.insert(key, value);
+        }
+        dict
+    }
+}
+
+impl<K, V> IntoIterator for Dict<K, V> {
+    type Item = (K, V);
+    type IntoIter = std::iter::Flatten<std::iter::Flatten<std::array::IntoIter<Vec<Vec<(K, V)>>, 2>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tables.into_iter().flatten().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::Dict;
+
+    fn filled(keys: impl Iterator<Item = u64>) -> Dict<u64, u64> {
+        keys.map(|key| (key, key * 2)).collect()
+    }
+
+    /// 推进 rehash 直到完成
+    fn finish_rehash(dict: &mut Dict<u64, u64>) {
+        while dict.rehash_index.is_some() {
+            dict.get_mut(&0);
+        }
+    }
+
+    #[test]
+    fn scan_while_growing() {
+        for _ in 0..20 {
+            let mut dict = filled(0..100);
+            finish_rehash(&mut dict);
+            let mut seen = HashSet::new();
+            let mut cursor = 0;
+            let mut next = 100;
+            let mut rehashed = false;
+            loop {
+                cursor = dict.scan(cursor, |key, _| {
+                    seen.insert(*key);
+                });
+                if cursor == 0 {
+                    break;
+                }
+                rehashed |= dict.rehash_index.is_some();
+                // 插入到 2000 个键为止，表在遍历期间扩容两次
+                for _ in 0..20 {
+                    if next < 2000 {
+                        dict.insert(next, next * 2);
+                        next += 1;
+                    }
+                }
+            }
+            assert!(rehashed && dict.tables[0].len() >= 2048, "table did not grow during the scan");
+            assert!((0..100).all(|key| seen.contains(&key)));
+        }
+    }
+
+    #[test]
+    fn scan_while_shrinking() {
+        for _ in 0..20 {
+            let mut dict = filled(0..1000);
+            finish_rehash(&mut dict);
+            let mut seen = HashSet::new();
+            let mut cursor = 0;
+            let mut next = 999;
+            let mut rehashed = false;
+            loop {
+                cursor = dict.scan(cursor, |key, _| {
+                    seen.insert(*key);
+                });
+                if cursor == 0 {
+                    break;
+                }
+                rehashed |= dict.rehash_index.is_some();
+                // 保留 0..50，其余的键在遍历期间删除
+                for _ in 0..20 {
+                    if next >= 50 {
+                        dict.remove(&next);
+                        next -= 1;
+                    }
+                }
+            }
+            assert!(rehashed && dict.len() == 50, "table did not shrink during the scan");
+            assert!((0..50).all(|key| seen.contains(&key)));
+        }
+    }
+
+    #[test]
+    fn remove_shrinks() {
+        let mut dict = filled(0..1000);
+        finish_rehash(&mut dict);
+        assert_eq!(dict.tables[0].len(), 1024);
+        for key in 10..1000 {
+            assert_eq!(dict.remove(&key), Some(key * 2));
+        }
+        finish_rehash(&mut dict);
+        dict.remove(&9);
+        finish_rehash(&mut dict);
+        assert_eq!(dict.len(), 9);
+        assert!(dict.tables[0].len() <= 16);
+        assert!((0..9).all(|key| dict.get(&key) == Some(&(key * 2))));
+        assert!(dict.get(&9).is_none());
+    }
+
+    #[test]
+    fn random_during_rehash() {
+        let mut dict = filled(0..1000);
+        finish_rehash(&mut dict);
+        for key in 100..1000 {
+            dict.remove(&key);
+        }
+        assert!(dict.rehash_index.is_some());
+        for _ in 0..10000 {
+            let (key, value) = dict.random().unwrap();
+            assert!(*key < 100);
+            assert_eq!(*value, key * 2);
+        }
+
+        let mut dict = filled(0..1024);
+        finish_rehash(&mut dict);
+        dict.insert(1024, 2048);
+        assert!(dict.rehash_index.is_some());
+        let mut seen = HashSet::new();
+        for _ in 0..100000 {
+            let (key, value) = dict.random().unwrap();
+            assert_eq!(*value, key * 2);
+            seen.insert(*key);
+        }
+        assert!(seen.iter().all(|key| *key <= 1024));
+        assert!(seen.contains(&1024));
+    }
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, time::Duration};
+
+use bytes::Bytes;
+use time::OffsetDateTime;
+use tokio::time::Instant;
+
+use super::{unix_ms, Db};
+use crate::random;
+
+/// 与 Redis 默认的 hz 一致，每秒执行 10 次主动过期
+const ACTIVE_EXPIRE_CYCLE_PERIOD: Duration = Duration::from_millis(100);
+/// 每轮抽样的键数
+const ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP: usize = 20;
+/// 抽样中过期键的比例不超过 10% 时结束本次循环
+const ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE: usize = 10;
+/// 每次循环最多占用周期的 25%
+const ACTIVE_EXPIRE_CYCLE_TIME_LIMIT: Duration = Duration::from_millis(25);
+
+/// 设置了过期时间的键，支持 O(1) 的插入、删除和随机抽样
+#[derive(Default, Debug)]
+pub(super) struct Volatile {
+    keys: Vec<Bytes>,
+    index: HashMap<Bytes, usize>,
+}
+
+impl Volatile {
+    pub(super) fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub(super) fn insert(&mut self, key: Bytes) {
+        if !self.index.contains_key(&key) {
+            self.index.insert(key.clone(), self.keys.len());
+            self.keys.push(key);
+        }
+    }
+
+    pub(super) fn remove(&mut self, key: &[u8]) {
+        if let Some(i) = self.index.remove(key) {
+            self.keys.swap_remove(i);
+            if let Some(moved) = self.keys.get(i) {
+                self.index.insert(moved.clone(), i);
+            }
+        }
+    }
+
+    fn random(&self) -> Option<Bytes> {
+        match self.keys.len() {
+            0 => None,
+            len => Some(self.keys[random::below(len)].clone()),
+        }
+    }
+}
+
+impl Db {
+    /// 随机检查最多 count 个设置了过期时间的键，删除其中已过期的，返回 (检查数, 删除数)。
+    /// 未过期的键的剩余时间用于更新 avg_ttl
+    fn expire_sample(&mut self, count: usize) -> (usize, usize) {
+        if self.volatile.len() == 0 {
+            self.avg_ttl = 0;
+            return (0, 0);
+        }
+        let now = unix_ms(OffsetDateTime::now_utc());
+        let mut sampled = 0;
+        let mut expired = 0;
+        let mut ttl_sum = 0;
+        let mut ttl_samples = 0;
+        for _ in 0..count {
+            let key = match self.volatile.random() {
+                Some(key) => key,
+                None => break,
+            };
+            sampled += 1;
+            match self.items.get(&key).and_then(|item| item.expires) {
+                Some(expires) => {
+                    if self.expire_if_needed(&key) {
+                        expired += 1;
+                    } else {
+                        ttl_sum += (unix_ms(expires) - now).max(0) as u64;
+                        ttl_samples += 1;
+                    }
+                }
+                // 过期时间已被移除的键
+                None => self.volatile.remove(&key),
+            }
+        }
+        // 与 Redis 一样平滑处理，每次抽样的平均值只占 2%
+        if let Some(avg_ttl) = ttl_sum.checked_div(ttl_samples) {
+            self.avg_ttl = match self.avg_ttl {
+                0 => avg_ttl,
+                old => old / 50 * 49 + avg_ttl / 50,
+            };
+        }
+        (sampled, expired)
+    }
+}
+
+/// 后台的主动过期任务，与 Redis 的 activeExpireCycle 一样自适应：
+/// 依次处理每个数据库，抽样中过期键较多时继续清理，直到比例足够低或用完时间片。
+/// current_db 在多次循环之间保留，用完时间片后下一次从下一个数据库继续，编号靠后的数据库也能被清理
+pub async fn active_expire() {
+    let mut interval = tokio::time::interval(ACTIVE_EXPIRE_CYCLE_PERIOD);
+    let mut current_db = 0;
+    loop {
+        interval.tick().await;
+        let start = Instant::now();
+        let db_count = super::db_count();
+        'databases: for _ in 0..db_count {
+            let index = current_db % db_count;
+            current_db = (index + 1) % db_count;
+            loop {
+                // 每轮重新获取锁，避免长时间阻塞其他连接
+                let (sampled, expired) = super::write_db(index)
+                    .await
+                    .expire_sample(ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP);
+                if start.elapsed() > ACTIVE_EXPIRE_CYCLE_TIME_LIMIT {
+                    break 'databases;
+                }
+                if sampled == 0 || expired * 100 <= sampled * ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+        }
+    }
+}
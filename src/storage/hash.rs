@@ -0,0 +1,32 @@
+use bytes::Bytes;
+
+use super::{Db, Dict, Item, Value, WRONGTYPE};
+
+pub type Hash = Dict<Bytes, Bytes>;
+
+impl Db {
+    /// 读取哈希，键不存在时返回 None
+    pub fn hash(&self, key: &[u8]) -> Result<Option<&Hash>, String> {
+        match self.get(key).map(|item| &item.value) {
+            Some(Value::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn hash_mut(&mut self, key: &[u8]) -> Result<Option<&mut Hash>, String> {
+        match self.get_mut(key).map(|item| &mut item.value) {
+            Some(Value::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// 读取哈希，键不存在时创建空哈希
+    pub fn hash_or_insert(&mut self, key: &Bytes) -> Result<&mut Hash, String> {
+        if self.get_mut(key).is_none() {
+            self.insert(key.clone(), Item::new(Value::Hash(Hash::new())));
+        }
+        self.hash_mut(key).map(|hash| hash.unwrap())
+    }
+}
@@ -0,0 +1,400 @@
+//! HyperLogLog，存储格式与 Redis 完全一致，可以通过 RDB 文件与 Redis 互相迁移。
+//! 16 字节的头部依次是 "HYLL"、编码、3 字节保留和 8 字节小端序的基数缓存（最高位置 1 表示缓存失效），
+//! 之后是寄存器：稀疏编码使用 ZERO/XZERO/VAL 操作码，密集编码每个寄存器占 6 位
+
+const HLL_P: u32 = 14;
+const HLL_Q: usize = 64 - HLL_P as usize;
+pub const HLL_REGISTERS: usize = 1 << HLL_P;
+const HLL_P_MASK: u64 = HLL_REGISTERS as u64 - 1;
+const HLL_BITS: usize = 6;
+const HLL_REGISTER_MAX: u16 = (1 << HLL_BITS) - 1;
+const HLL_HDR_SIZE: usize = 16;
+const HLL_DENSE_SIZE: usize = HLL_HDR_SIZE + (HLL_REGISTERS * HLL_BITS).div_ceil(8);
+const HLL_DENSE: u8 = 0;
+const HLL_SPARSE: u8 = 1;
+const HLL_SPARSE_VAL_MAX_VALUE: u8 = 32;
+const HLL_SPARSE_VAL_MAX_LEN: usize = 4;
+const HLL_SPARSE_ZERO_MAX_LEN: usize = 64;
+const HLL_SPARSE_XZERO_MAX_LEN: usize = 16384;
+/// 与 Redis 的 hll-sparse-max-bytes 默认值一致，稀疏编码超过该长度时转为密集编码
+const HLL_SPARSE_MAX_BYTES: usize = 3000;
+/// 0.5 / ln(2)
+const HLL_ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+/// 与 Redis 的 MurmurHash64A 使用相同的种子
+const HLL_HASH_SEED: u64 = 0xadc8_3b19;
+
+pub const INVALID_HLL: &str = "WRONGTYPE Key is not a valid HyperLogLog string value.";
+pub const CORRUPTED_HLL: &str = "INVALIDOBJ Corrupted HLL object detected";
+
+/// 稀疏编码的操作码
+enum Opcode {
+    /// ZERO 和 XZERO：连续 len 个值为 0 的寄存器
+    Zero(usize),
+    /// VAL：连续 len 个值为 value 的寄存器
+    Val(u8, usize),
+}
+
+/// 解析 p 处的操作码，返回操作码和它占用的字节数
+fn opcode(sparse: &[u8], p: usize) -> (Opcode, usize) {
+    let byte = sparse[p];
+    if byte & 0x80 != 0 {
+        (Opcode::Val(((byte >> 2) & 0x1f) + 1, (byte & 0x3) as usize + 1), 1)
+    } else if byte & 0xc0 == 0x40 {
+        // 与 C 字符串一样，越界时读到结尾的 0
+        let low = sparse.get(p + 1).copied().unwrap_or(0) as usize;
+        (Opcode::Zero((((byte & 0x3f) as usize) << 8 | low) + 1), 2)
+    } else {
+        (Opcode::Zero((byte & 0x3f) as usize + 1), 1)
+    }
+}
+
+fn val_opcode(value: u8, len: usize) -> u8 {
+    ((value - 1) << 2 | (len - 1) as u8) | 0x80
+}
+
+/// 追加表示 len 个 0 寄存器的操作码，超过 ZERO 的最大长度时使用 XZERO
+fn push_zero_opcode(seq: &mut Vec<u8>, len: usize) {
+    if len > HLL_SPARSE_ZERO_MAX_LEN {
+        let len = len - 1;
+        seq.push((len >> 8) as u8 | 0x40);
+        seq.push((len & 0xff) as u8);
+    } else {
+        seq.push((len - 1) as u8);
+    }
+}
+
+/// 与 Redis 相同的 MurmurHash64A，按小端序读取
+fn murmurhash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        for (i, &byte) in rest.iter().enumerate() {
+            h ^= (byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+/// 返回元素对应的寄存器和哈希值剩余部分中第一个 1 出现的位置（从 1 开始）
+fn pattern_len(element: &[u8]) -> (usize, u8) {
+    let mut hash = murmurhash64a(element, HLL_HASH_SEED);
+    let index = (hash & HLL_P_MASK) as usize;
+    hash >>= HLL_P;
+    // 保证结果不超过 Q + 1
+    hash |= 1 << HLL_Q;
+    (index, hash.trailing_zeros() as u8 + 1)
+}
+
+fn dense_get(registers: &[u8], index: usize) -> u8 {
+    let byte = index * HLL_BITS / 8;
+    let fb = (index * HLL_BITS) & 7;
+    let b0 = registers[byte] as u16;
+    let b1 = registers.get(byte + 1).copied().unwrap_or(0) as u16;
+    (((b0 >> fb) | (b1 << (8 - fb))) & HLL_REGISTER_MAX) as u8
+}
+
+fn dense_set(registers: &mut [u8], index: usize, value: u8) {
+    let byte = index * HLL_BITS / 8;
+    let fb = (index * HLL_BITS) & 7;
+    let value = value as u16;
+    registers[byte] &= !(HLL_REGISTER_MAX << fb) as u8;
+    registers[byte] |= (value << fb) as u8;
+    // 最后一个寄存器不会跨越到下一个字节
+    if let Some(next) = registers.get_mut(byte + 1) {
+        *next &= !(HLL_REGISTER_MAX >> (8 - fb)) as u8;
+        *next |= (value >> (8 - fb)) as u8;
+    }
+}
+
+/// 寄存器的值小于 count 时更新，返回是否有修改
+fn dense_set_if_greater(registers: &mut [u8], index: usize, count: u8) -> bool {
+    if count > dense_get(registers, index) {
+        dense_set(registers, index, count);
+        return true;
+    }
+    false
+}
+
+/// 新建一个空的 HyperLogLog，使用稀疏编码，所有寄存器用 XZERO 表示
+pub fn hll_new() -> Vec<u8> {
+    let mut hll = b"HYLL".to_vec();
+    hll.push(HLL_SPARSE);
+    hll.resize(HLL_HDR_SIZE, 0);
+    let mut remaining = HLL_REGISTERS;
+    while remaining > 0 {
+        let len = remaining.min(HLL_SPARSE_XZERO_MAX_LEN);
+        push_zero_opcode(&mut hll, len);
+        remaining -= len;
+    }
+    hll
+}
+
+/// 检查字符串是否是合法的 HyperLogLog，只检查头部和密集编码的长度
+pub fn hll_is_valid(value: &[u8]) -> bool {
+    value.len() >= HLL_HDR_SIZE
+        && value.starts_with(b"HYLL")
+        && value[4] <= HLL_SPARSE
+        && (value[4] != HLL_DENSE || value.len() == HLL_DENSE_SIZE)
+}
+
+pub fn hll_invalidate_cache(hll: &mut [u8]) {
+    hll[15] |= 1 << 7;
+}
+
+/// 把任意编码的 HyperLogLog 展开为每个寄存器一个字节
+fn hll_registers(hll: &[u8]) -> Result<Vec<u8>, String> {
+    if hll[4] == HLL_DENSE {
+        let registers = &hll[HLL_HDR_SIZE..];
+        return Ok((0..HLL_REGISTERS).map(|index| dense_get(registers, index)).collect());
+    }
+    let mut registers = vec![0; HLL_REGISTERS];
+    let mut index = 0;
+    let mut p = HLL_HDR_SIZE;
+    while p < hll.len() {
+        let (op, op_len) = opcode(hll, p);
+        let (value, len) = match op {
+            Opcode::Zero(len) => (0, len),
+            Opcode::Val(value, len) => (value, len),
+        };
+        if index + len > HLL_REGISTERS {
+            return Err(CORRUPTED_HLL.to_string());
+        }
+        registers[index..index + len].fill(value);
+        index += len;
+        p += op_len;
+    }
+    if index != HLL_REGISTERS {
+        return Err(CORRUPTED_HLL.to_string());
+    }
+    Ok(registers)
+}
+
+/// 转换为密集编码，保留头部的基数缓存
+fn sparse_to_dense(hll: &mut Vec<u8>) -> Result<(), String> {
+    if hll[4] == HLL_DENSE {
+        return Ok(());
+    }
+    let registers = hll_registers(hll)?;
+    let mut dense = hll[..HLL_HDR_SIZE].to_vec();
+    dense[4] = HLL_DENSE;
+    dense.resize(HLL_DENSE_SIZE, 0);
+    for (index, &value) in registers.iter().enumerate() {
+        if value != 0 {
+            dense_set(&mut dense[HLL_HDR_SIZE..], index, value);
+        }
+    }
+    *hll = dense;
+    Ok(())
+}
+
+/// 在稀疏编码上原地更新寄存器，与 hllSparseSet 生成完全相同的字节。
+/// 值超出 VAL 的表示范围或长度超出限制时转为密集编码
+fn sparse_set(hll: &mut Vec<u8>, index: usize, count: u8) -> Result<bool, String> {
+    if count > HLL_SPARSE_VAL_MAX_VALUE {
+        return promote(hll, index, count);
+    }
+
+    // 找到覆盖该寄存器的操作码，first 是它覆盖的第一个寄存器
+    let mut p = HLL_HDR_SIZE;
+    let mut first = 0;
+    let mut prev = None;
+    let mut found = None;
+    while p < hll.len() {
+        let (op, op_len) = opcode(hll, p);
+        let span = match op {
+            Opcode::Zero(len) | Opcode::Val(_, len) => len,
+        };
+        if index < first + span {
+            found = Some((op, op_len, span));
+            break;
+        }
+        prev = Some(p);
+        p += op_len;
+        first += span;
+    }
+    let (op, old_len, span) = found.ok_or_else(|| CORRUPTED_HLL.to_string())?;
+    if p + old_len > hll.len() {
+        return Err(CORRUPTED_HLL.to_string());
+    }
+
+    let last = first + span - 1;
+    let mut seq = Vec::with_capacity(5);
+    match op {
+        Opcode::Val(value, _) if value >= count => return Ok(false),
+        // 只覆盖这一个寄存器时直接修改
+        Opcode::Val(_, 1) => seq.push(val_opcode(count, 1)),
+        Opcode::Zero(1) if old_len == 1 => seq.push(val_opcode(count, 1)),
+        // 拆分为最多三个操作码：左侧的剩余部分、新的值、右侧的剩余部分
+        Opcode::Zero(_) => {
+            if index != first {
+                push_zero_opcode(&mut seq, index - first);
+            }
+            seq.push(val_opcode(count, 1));
+            if index != last {
+                push_zero_opcode(&mut seq, last - index);
+            }
+        }
+        Opcode::Val(value, _) => {
+            if index != first {
+                seq.push(val_opcode(value, index - first));
+            }
+            seq.push(val_opcode(count, 1));
+            if index != last {
+                seq.push(val_opcode(value, last - index));
+            }
+        }
+    }
+    if seq.len() > old_len && hll.len() + seq.len() - old_len > HLL_SPARSE_MAX_BYTES {
+        return promote(hll, index, count);
+    }
+    hll.splice(p..p + old_len, seq);
+
+    // 从前一个操作码开始最多检查 5 个操作码，合并相邻的值相同的 VAL
+    let mut p = prev.unwrap_or(HLL_HDR_SIZE);
+    let mut scan_len = 5;
+    while p < hll.len() && scan_len > 0 {
+        scan_len -= 1;
+        let (op, op_len) = opcode(hll, p);
+        let (value, len) = match op {
+            Opcode::Zero(_) => {
+                p += op_len;
+                continue;
+            }
+            Opcode::Val(value, len) => (value, len),
+        };
+        if p + 1 < hll.len() {
+            if let (Opcode::Val(next_value, next_len), _) = opcode(hll, p + 1) {
+                if value == next_value && len + next_len <= HLL_SPARSE_VAL_MAX_LEN {
+                    hll[p + 1] = val_opcode(value, len + next_len);
+                    hll.remove(p);
+                    // 继续尝试与右侧合并
+                    continue;
+                }
+            }
+        }
+        p += 1;
+    }
+    hll_invalidate_cache(hll);
+    Ok(true)
+}
+
+fn promote(hll: &mut Vec<u8>, index: usize, count: u8) -> Result<bool, String> {
+    sparse_to_dense(hll)?;
+    Ok(dense_set_if_greater(&mut hll[HLL_HDR_SIZE..], index, count))
+}
+
+fn set_register(hll: &mut Vec<u8>, index: usize, count: u8) -> Result<bool, String> {
+    match hll[4] {
+        HLL_DENSE => Ok(dense_set_if_greater(&mut hll[HLL_HDR_SIZE..], index, count)),
+        _ => sparse_set(hll, index, count),
+    }
+}
+
+/// 添加一个元素，返回是否有寄存器被修改
+pub fn hll_add(hll: &mut Vec<u8>, element: &[u8]) -> Result<bool, String> {
+    let (index, count) = pattern_len(element);
+    set_register(hll, index, count)
+}
+
+/// 与 hllTau 一致
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let z_prime = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if z_prime == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// 与 hllSigma 一致
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let z_prime = z;
+        z += x * y;
+        y += y;
+        if z_prime == z {
+            return z;
+        }
+    }
+}
+
+/// 使用 Otmar Ertl 的改进估计算法，根据寄存器的直方图估算基数
+pub fn hll_count_registers(registers: &[u8]) -> u64 {
+    let m = HLL_REGISTERS as f64;
+    let mut histogram = [0u32; 64];
+    for &register in registers {
+        histogram[register as usize] += 1;
+    }
+    let mut z = m * tau((m - histogram[HLL_Q + 1] as f64) / m);
+    for j in (1..=HLL_Q).rev() {
+        z += histogram[j] as f64;
+        z *= 0.5;
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+    (HLL_ALPHA_INF * m * m / z).round() as u64
+}
+
+/// 返回基数，缓存失效时重新计算并更新缓存
+pub fn hll_count(hll: &mut [u8]) -> Result<u64, String> {
+    if hll[15] & (1 << 7) == 0 {
+        return Ok(u64::from_le_bytes(hll[8..16].try_into().unwrap()));
+    }
+    let count = hll_count_registers(&hll_registers(hll)?);
+    hll[8..16].copy_from_slice(&count.to_le_bytes());
+    Ok(count)
+}
+
+/// 把 HyperLogLog 合并到 max 中，max 的每个寄存器取两者的较大值
+pub fn hll_merge(max: &mut [u8], hll: &[u8]) -> Result<(), String> {
+    for (max, value) in max.iter_mut().zip(hll_registers(hll)?) {
+        *max = (*max).max(value);
+    }
+    Ok(())
+}
+
+pub fn hll_is_dense(hll: &[u8]) -> bool {
+    hll[4] == HLL_DENSE
+}
+
+/// PFMERGE 写入合并结果，dense 为 true 时先转为密集编码
+pub fn hll_store_registers(hll: &mut Vec<u8>, max: &[u8], dense: bool) -> Result<(), String> {
+    if dense {
+        sparse_to_dense(hll)?;
+    }
+    for (index, &value) in max.iter().enumerate() {
+        if value != 0 {
+            set_register(hll, index, value)?;
+        }
+    }
+    hll_invalidate_cache(hll);
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use super::{Item, Value};
+
+/// 释放代价超过该值的对象在后台释放，与 Redis 的 LAZYFREE_THRESHOLD 一致
+const LAZYFREE_THRESHOLD: usize = 64;
+
+impl Value {
+    /// 释放对象的代价，大致等于需要释放的内存块数
+    fn free_effort(&self) -> usize {
+        match self {
+            Value::String(_) => 1,
+            Value::List(list) => list.len(),
+            Value::Hash(hash) => hash.len(),
+            Value::Set(set) => set.len(),
+            Value::ZSet(zset) => zset.len(),
+            Value::Stream(stream) => stream.len() + stream.groups.len(),
+        }
+    }
+}
+
+/// 在后台线程中释放数据，避免持有写锁时释放大对象
+pub fn free_async<T: Send + 'static>(value: T) {
+    tokio::task::spawn_blocking(move || drop(value));
+}
+
+/// 释放被删除的键，较大的对象交给后台释放
+pub fn free_items(items: Vec<Item>) {
+    let (large, small): (Vec<Item>, Vec<Item>) = items
+        .into_iter()
+        .partition(|item| item.value.free_effort() > LAZYFREE_THRESHOLD);
+    drop(small);
+    if !large.is_empty() {
+        free_async(large);
+    }
+}
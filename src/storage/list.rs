@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use super::{Db, Item, Value, WRONGTYPE};
+
+pub type List = VecDeque<Bytes>;
+
+impl Db {
+    /// 读取列表，键不存在时返回 None
+    pub fn list(&self, key: &[u8]) -> Result<Option<&List>, String> {
+        match self.get(key).map(|item| &item.value) {
+            Some(Value::List(list)) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn list_mut(&mut self, key: &[u8]) -> Result<Option<&mut List>, String> {
+        match self.get_mut(key).map(|item| &mut item.value) {
+            Some(Value::List(list)) => Ok(Some(list)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// 读取列表，键不存在时创建空列表
+    pub fn list_or_insert(&mut self, key: &Bytes) -> Result<&mut List, String> {
+        if self.get_mut(key).is_none() {
+            self.insert(key.clone(), Item::new(Value::List(List::new())));
+        }
+        self.list_mut(key).map(|list| list.unwrap())
+    }
+}
+
+/// 将 Redis 风格的下标（负数从尾部计数）转换为 [start, end] 闭区间，区间为空时返回 None
+pub fn normalize_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let stop = if stop < 0 { len + stop } else { stop.min(len - 1) };
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
+
+/// 将单个下标转换为正向下标
+pub fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    if index < 0 || index >= len as i64 {
+        return None;
+    }
+    Some(index as usize)
+}
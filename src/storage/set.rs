@@ -0,0 +1,163 @@
+use bytes::Bytes;
+
+use crate::random;
+
+use super::{parse_strict_int, Db, Dict, Item, Value, WRONGTYPE};
+
+/// 与 Redis 的 set-max-intset-entries 默认值一致
+pub const SET_MAX_INTSET_ENTRIES: usize = 512;
+
+/// 集合：全部是整数且元素较少时使用有序的整数数组（intset）存储
+#[derive(Clone, Debug)]
+pub enum Set {
+    IntSet(Vec<i64>),
+    HashTable(Dict<Bytes, ()>),
+}
+
+impl Default for Set {
+    fn default() -> Self {
+        Set::IntSet(Vec::new())
+    }
+}
+
+impl Set {
+    pub fn len(&self) -> usize {
+        match self {
+            Set::IntSet(ints) => ints.len(),
+            Set::HashTable(members) => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Set::IntSet(_) => "intset",
+            Set::HashTable(_) => "hashtable",
+        }
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match self {
+            Set::IntSet(ints) => {
+                parse_strict_int(member).is_some_and(|n| ints.binary_search(&n).is_ok())
+            }
+            Set::HashTable(members) => members.contains_key(member),
+        }
+    }
+
+    /// 添加成员，返回是否为新成员
+    pub fn insert(&mut self, member: Bytes) -> bool {
+        if let Set::IntSet(ints) = self {
+            if let Some(n) = parse_strict_int(&member) {
+                return match ints.binary_search(&n) {
+                    Ok(_) => false,
+                    Err(pos) => {
+                        ints.insert(pos, n);
+                        if ints.len() > SET_MAX_INTSET_ENTRIES {
+                            self.convert();
+                        }
+                        true
+                    }
+                };
+            }
+            self.convert();
+        }
+        match self {
+            Set::HashTable(members) => members.insert(member, ()).is_none(),
+            Set::IntSet(_) => unreachable!(),
+        }
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self {
+            Set::IntSet(ints) => match parse_strict_int(member).map(|n| ints.binary_search(&n)) {
+                Some(Ok(pos)) => {
+                    ints.remove(pos);
+                    true
+                }
+                _ => false,
+            },
+            Set::HashTable(members) => members.remove(member).is_some(),
+        }
+    }
+
+    pub fn members(&self) -> Vec<Bytes> {
+        self.iter().collect()
+    }
+
+    /// 逐个返回成员，intset 编码的成员在遍历到时才转为字符串
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Bytes> + '_> {
+        match self {
+            Set::IntSet(ints) => Box::new(ints.iter().map(|n| Bytes::from(n.to_string()))),
+            Set::HashTable(members) => Box::new(members.keys().cloned()),
+        }
+    }
+
+    /// 按游标遍历成员，intset 编码一次返回全部成员
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<Bytes>) {
+        match self {
+            Set::IntSet(_) => (0, self.members()),
+            Set::HashTable(members) => {
+                let (cursor, found) = members.scan_batch(cursor, count);
+                (cursor, found.into_iter().map(|(member, _)| member.clone()).collect())
+            }
+        }
+    }
+
+    /// 随机返回一个成员
+    pub fn random(&self) -> Option<Bytes> {
+        match self {
+            Set::IntSet(ints) if ints.is_empty() => None,
+            Set::IntSet(ints) => Some(Bytes::from(ints[random::below(ints.len())].to_string())),
+            Set::HashTable(members) => members.random().map(|(member, _)| member.clone()),
+        }
+    }
+
+    /// intset 转换为哈希表
+    fn convert(&mut self) {
+        if let Set::IntSet(ints) = self {
+            let members = ints.iter().map(|n| (Bytes::from(n.to_string()), ())).collect();
+            *self = Set::HashTable(members);
+        }
+    }
+}
+
+impl FromIterator<Bytes> for Set {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(iter: I) -> Self {
+        let mut set = Set::default();
+        for member in iter {
+            set.insert(member);
+        }
+        set
+    }
+}
+
+impl Db {
+    /// 读取集合，键不存在时返回 None
+    pub fn set(&self, key: &[u8]) -> Result<Option<&Set>, String> {
+        match self.get(key).map(|item| &item.value) {
+            Some(Value::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_mut(&mut self, key: &[u8]) -> Result<Option<&mut Set>, String> {
+        match self.get_mut(key).map(|item| &mut item.value) {
+            Some(Value::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// 读取集合，键不存在时创建空集合
+    pub fn set_or_insert(&mut self, key: &Bytes) -> Result<&mut Set, String> {
+        if self.get_mut(key).is_none() {
+            self.insert(key.clone(), Item::new(Value::Set(Set::default())));
+        }
+        self.set_mut(key).map(|set| set.unwrap())
+    }
+}
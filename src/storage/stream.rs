@@ -0,0 +1,258 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    ops::Bound,
+    sync::LazyLock,
+};
+
+use bytes::Bytes;
+use tokio::sync::Notify;
+
+use super::{now_ms, ConsumerGroup, Db, Item, Value, WRONGTYPE};
+
+/// 与 Redis 的 stream-node-max-entries 默认值一致，近似裁剪以此为单位
+pub const STREAM_NODE_MAX_ENTRIES: usize = 100;
+
+/// 有新条目写入任意流时通知阻塞的 XREAD
+pub static STREAM_ADDED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        Self { ms, seq }
+    }
+
+    /// 解析 `ms-seq` 或 `ms`，省略序号时使用 default_seq
+    pub fn parse(value: &[u8], default_seq: u64) -> Option<Self> {
+        let value = std::str::from_utf8(value).ok()?;
+        match value.split_once('-') {
+            Some((ms, seq)) => Some(Self::new(ms.parse().ok()?, seq.parse().ok()?)),
+            None => Some(Self::new(value.parse().ok()?, default_seq)),
+        }
+    }
+
+    pub fn next(self) -> Option<Self> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(Self::new(self.ms, seq)),
+            None => self.ms.checked_add(1).map(|ms| Self::new(ms, 0)),
+        }
+    }
+
+    pub fn prev(self) -> Option<Self> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(Self::new(self.ms, seq)),
+            None => self.ms.checked_sub(1).map(|ms| Self::new(ms, u64::MAX)),
+        }
+    }
+
+    pub fn to_bytes(self) -> Bytes {
+        Bytes::from(self.to_string())
+    }
+}
+
+impl Display for StreamId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// XADD 中指定的 ID
+#[derive(Clone, Copy, Debug)]
+pub enum IdSpec {
+    /// `*`
+    Auto,
+    /// `ms-*`
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+/// 裁剪策略
+#[derive(Clone, Copy, Debug)]
+pub enum TrimStrategy {
+    MaxLen(usize),
+    MinId(StreamId),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TrimSpec {
+    pub strategy: TrimStrategy,
+    pub approx: bool,
+    /// 近似裁剪时最多删除的条目数，0 表示不限制
+    pub limit: usize,
+}
+
+pub type StreamEntry = (StreamId, Vec<Bytes>);
+
+#[derive(Clone, Debug, Default)]
+pub struct Stream {
+    pub(super) entries: BTreeMap<StreamId, Vec<Bytes>>,
+    pub last_id: StreamId,
+    pub max_deleted_id: StreamId,
+    pub entries_added: u64,
+    pub groups: BTreeMap<Bytes, ConsumerGroup>,
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn last_entry(&self) -> Option<StreamEntry> {
+        self.entries.iter().next_back().map(|(id, fields)| (*id, fields.clone()))
+    }
+
+    /// 根据 ID 规则生成新条目的 ID
+    pub fn next_id(&self, spec: IdSpec) -> Result<StreamId, String> {
+        let too_small = || {
+            "ERR The ID specified in XADD is equal or smaller than the target stream top item"
+                .to_string()
+        };
+        match spec {
+            IdSpec::Auto => {
+                let now = now_ms();
+                if now > self.last_id.ms {
+                    Ok(StreamId::new(now, 0))
+                } else {
+                    self.last_id.next().ok_or_else(too_small)
+                }
+            }
+            IdSpec::AutoSeq(ms) => {
+                if ms > self.last_id.ms {
+                    Ok(StreamId::new(ms, 0))
+                } else if ms == self.last_id.ms {
+                    match self.last_id.seq.checked_add(1) {
+                        Some(seq) => Ok(StreamId::new(ms, seq)),
+                        None => Err(too_small()),
+                    }
+                } else {
+                    Err(too_small())
+                }
+            }
+            IdSpec::Explicit(id) => {
+                if id == StreamId::MIN {
+                    Err("ERR The ID specified in XADD must be greater than 0-0".to_string())
+                } else if id <= self.last_id {
+                    Err(too_small())
+                } else {
+                    Ok(id)
+                }
+            }
+        }
+    }
+
+    /// 按 ID 从小到大遍历所有条目
+    pub fn iter(&self) -> impl Iterator<Item = (&StreamId, &Vec<Bytes>)> {
+        self.entries.iter()
+    }
+
+    pub fn add(&mut self, id: StreamId, fields: Vec<Bytes>) {
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    pub fn delete(&mut self, id: &StreamId) -> bool {
+        if self.entries.remove(id).is_some() {
+            self.max_deleted_id = self.max_deleted_id.max(*id);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 按 ID 区间读取条目，rev 为 true 时从新到旧
+    pub fn range(
+        &self,
+        start: Bound<StreamId>,
+        end: Bound<StreamId>,
+        rev: bool,
+        count: Option<usize>,
+    ) -> Vec<StreamEntry> {
+        let empty = match (start, end) {
+            (Bound::Included(s), Bound::Included(e)) => s > e,
+            (Bound::Included(s) | Bound::Excluded(s), Bound::Excluded(e))
+            | (Bound::Excluded(s), Bound::Included(e)) => s >= e,
+            _ => false,
+        };
+        if empty {
+            return vec![];
+        }
+        let range = self.entries.range((start, end));
+        let count = count.unwrap_or(usize::MAX);
+        let map = |(id, fields): (&StreamId, &Vec<Bytes>)| (*id, fields.clone());
+        if rev {
+            range.rev().take(count).map(map).collect()
+        } else {
+            range.take(count).map(map).collect()
+        }
+    }
+
+    /// 裁剪流，返回删除的条目数
+    pub fn trim(&mut self, spec: TrimSpec) -> usize {
+        let mut removed = 0;
+        // 总是从头部删除，超出的条目数只需计算一次，之后每批减去删除的数量
+        let mut excess = match spec.strategy {
+            TrimStrategy::MaxLen(max) => self.len().saturating_sub(max),
+            TrimStrategy::MinId(min) => self.entries.range(..min).count(),
+        };
+        loop {
+            // 近似裁剪只删除完整的节点
+            let batch = if spec.approx {
+                if excess < STREAM_NODE_MAX_ENTRIES {
+                    break;
+                }
+                STREAM_NODE_MAX_ENTRIES
+            } else {
+                excess
+            };
+            if batch == 0 || (spec.approx && spec.limit > 0 && removed + batch > spec.limit) {
+                break;
+            }
+            for _ in 0..batch {
+                if let Some((id, _)) = self.entries.pop_first() {
+                    self.max_deleted_id = self.max_deleted_id.max(id);
+                    removed += 1;
+                }
+            }
+            excess -= batch;
+            if !spec.approx {
+                break;
+            }
+        }
+        removed
+    }
+}
+
+impl Db {
+    /// 读取流，键不存在时返回 None
+    pub fn stream(&self, key: &[u8]) -> Result<Option<&Stream>, String> {
+        match self.get(key).map(|item| &item.value) {
+            Some(Value::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn stream_mut(&mut self, key: &[u8]) -> Result<Option<&mut Stream>, String> {
+        match self.get_mut(key).map(|item| &mut item.value) {
+            Some(Value::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// 读取流，键不存在时创建空流
+    pub fn stream_or_insert(&mut self, key: &Bytes) -> Result<&mut Stream, String> {
+        if self.get_mut(key).is_none() {
+            self.insert(key.clone(), Item::new(Value::Stream(Stream::default())));
+        }
+        self.stream_mut(key).map(|stream| stream.unwrap())
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound,
+};
+
+use bytes::Bytes;
+
+use super::{Stream, StreamEntry, StreamId};
+
+/// 已投递但尚未确认的条目
+#[derive(Clone, Debug)]
+pub struct PendingEntry {
+    pub consumer: Bytes,
+    /// 最近一次投递的时间（毫秒时间戳）
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Consumer {
+    pub seen_time: u64,
+    /// 最近一次成功读取或认领的时间
+    pub active_time: Option<u64>,
+    pub pending: BTreeSet<StreamId>,
+}
+
+impl Consumer {
+    fn new(now: u64) -> Self {
+        Self {
+            seen_time: now,
+            active_time: None,
+            pending: BTreeSet::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ConsumerGroup {
+    pub last_id: StreamId,
+    /// 已读取的逻辑条目数，None 表示无法确定
+    pub entries_read: Option<u64>,
+    pub pel: BTreeMap<StreamId, PendingEntry>,
+    pub consumers: BTreeMap<Bytes, Consumer>,
+}
+
+impl ConsumerGroup {
+    pub fn new(last_id: StreamId, entries_read: Option<u64>) -> Self {
+        Self {
+            last_id,
+            entries_read,
+            ..Default::default()
+        }
+    }
+
+    /// 查找消费者，不存在时创建，并刷新最近访问时间
+    pub fn consumer_mut(&mut self, name: &Bytes, now: u64) -> &mut Consumer {
+        let consumer = self
+            .consumers
+            .entry(name.clone())
+            .or_insert_with(|| Consumer::new(now));
+        consumer.seen_time = now;
+        consumer
+    }
+
+    pub fn create_consumer(&mut self, name: &Bytes, now: u64) -> bool {
+        if self.consumers.contains_key(name) {
+            return false;
+        }
+        self.consumers.insert(name.clone(), Consumer::new(now));
+        true
+    }
+
+    /// 删除消费者及其待确认条目，返回删除的待确认条目数
+    pub fn delete_consumer(&mut self, name: &[u8]) -> Option<usize> {
+        let consumer = self.consumers.remove(name)?;
+        for id in &consumer.pending {
+            self.pel.remove(id);
+        }
+        Some(consumer.pending.len())
+    }
+
+    pub fn ack(&mut self, id: &StreamId) -> bool {
+        match self.pel.remove(id) {
+            Some(entry) => {
+                if let Some(consumer) = self.consumers.get_mut(&entry.consumer) {
+                    consumer.pending.remove(id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 将待确认条目转移给另一个消费者
+    fn transfer(&mut self, id: StreamId, consumer: &Bytes) {
+        let entry = match self.pel.get_mut(&id) {
+            Some(entry) => entry,
+            None => return,
+        };
+        if entry.consumer != consumer {
+            if let Some(old) = self.consumers.get_mut(&entry.consumer) {
+                old.pending.remove(&id);
+            }
+            entry.consumer = consumer.clone();
+        }
+        if let Some(new) = self.consumers.get_mut(consumer) {
+            new.pending.insert(id);
+        }
+    }
+}
+
+/// XCLAIM 的可选参数
+#[derive(Clone, Debug, Default)]
+pub struct ClaimOptions {
+    pub min_idle: u64,
+    /// IDLE/TIME 指定的投递时间
+    pub delivery_time: Option<u64>,
+    pub retry_count: Option<u64>,
+    pub force: bool,
+    pub justid: bool,
+    pub last_id: Option<StreamId>,
+}
+
+/// XAUTOCLAIM 的结果：下一次扫描的游标、认领的条目、已从流中删除的条目
+pub type AutoClaimResult = (StreamId, Vec<StreamEntry>, Vec<StreamId>);
+
+impl Stream {
+    pub fn first_id(&self) -> StreamId {
+        self.entries.keys().next().copied().unwrap_or_default()
+    }
+
+    pub fn first_entry(&self) -> Option<StreamEntry> {
+        self.entries.iter().next().map(|(id, fields)| (*id, fields.clone()))
+    }
+
+    /// 区间 [start, last_id] 中是否有被删除的条目
+    fn has_tombstones(&self, start: StreamId) -> bool {
+        if self.len() == 0 || self.max_deleted_id == StreamId::MIN {
+            return false;
+        }
+        start <= self.max_deleted_id
+    }
+
+    /// 估算从第一个条目到 id 的逻辑读取数，与 Redis 的 streamEstimateDistanceFromFirstEverEntry 一致
+    fn estimate_entries_read(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        if self.len() == 0 && id <= self.last_id {
+            return Some(self.entries_added);
+        }
+        if id == self.last_id {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+        let first = self.first_id();
+        if self.max_deleted_id == StreamId::MIN || self.max_deleted_id < first {
+            if id < first {
+                return Some(self.entries_added - self.len() as u64);
+            }
+            if id == first {
+                return Some(self.entries_added - self.len() as u64 + 1);
+            }
+        }
+        None
+    }
+
+    /// 消费者组落后的条目数，无法计算时返回 None
+    pub fn group_lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        match group.entries_read {
+            Some(read) if !self.has_tombstones(group.last_id) => {
+                Some(self.entries_added.saturating_sub(read))
+            }
+            _ => self
+                .estimate_entries_read(group.last_id)
+                .map(|read| self.entries_added.saturating_sub(read)),
+        }
+    }
+
+    pub fn create_group(&mut self, name: Bytes, last_id: StreamId, entries_read: Option<u64>) -> bool {
+        if self.groups.contains_key(&name) {
+            return false;
+        }
+        self.groups.insert(name, ConsumerGroup::new(last_id, entries_read));
+        true
+    }
+
+    pub fn set_group_id(&mut self, name: &[u8], last_id: StreamId, entries_read: Option<u64>) -> bool {
+        match self.groups.get_mut(name) {
+            Some(group) => {
+                group.last_id = last_id;
+                group.entries_read = entries_read;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// XREADGROUP 使用 `>` 读取从未投递过的条目，组不存在时返回 None
+    pub fn read_new(
+        &mut self,
+        group: &[u8],
+        consumer: &Bytes,
+        count: Option<usize>,
+        noack: bool,
+        now: u64,
+    ) -> Option<Vec<StreamEntry>> {
+        let last_id = self.groups.get(group)?.last_id;
+        let entries = self.range(Bound::Excluded(last_id), Bound::Unbounded, false, count);
+        let updates: Vec<(bool, Option<u64>)> = entries
+            .iter()
+            .map(|(id, _)| (self.has_tombstones(*id), self.estimate_entries_read(*id)))
+            .collect();
+        let entries_added = self.entries_added;
+        let group = self.groups.get_mut(group)?;
+        group.consumer_mut(consumer, now);
+        for ((id, _), (tombstones, estimate)) in entries.iter().zip(updates) {
+            if *id > group.last_id {
+                group.entries_read = match group.entries_read {
+                    Some(read) if !tombstones => Some(read + 1),
+                    _ if entries_added > 0 => estimate,
+                    read => read,
+                };
+                group.last_id = *id;
+            }
+            if !noack {
+                // 重复投递同一条目时覆盖原有的待确认记录
+                if let Some(old) = group.pel.get(id) {
+                    let owner = old.consumer.clone();
+                    if let Some(old) = group.consumers.get_mut(&owner) {
+                        old.pending.remove(id);
+                    }
+                }
+                group.pel.insert(
+                    *id,
+                    PendingEntry {
+                        consumer: consumer.clone(),
+                        delivery_time: now,
+                        delivery_count: 1,
+                    },
+                );
+                group.consumer_mut(consumer, now).pending.insert(*id);
+            }
+        }
+        if !entries.is_empty() {
+            group.consumer_mut(consumer, now).active_time = Some(now);
+        }
+        Some(entries)
+    }
+
+    /// XREADGROUP 使用具体 ID 读取该消费者的历史待确认条目，已删除的条目内容为 None
+    pub fn read_pending(
+        &mut self,
+        group: &[u8],
+        consumer: &Bytes,
+        after: StreamId,
+        count: Option<usize>,
+        now: u64,
+    ) -> Option<Vec<(StreamId, Option<Vec<Bytes>>)>> {
+        let entries = &self.entries;
+        let consumer = self.groups.get_mut(group)?.consumer_mut(consumer, now);
+        let pending = consumer
+            .pending
+            .range((Bound::Excluded(after), Bound::Unbounded))
+            .take(count.unwrap_or(usize::MAX))
+            .map(|id| (*id, entries.get(id).cloned()))
+            .collect();
+        Some(pending)
+    }
+
+    /// XCLAIM，返回认领的条目和从待确认列表中移除的已删除条目数，组不存在时返回 None
+    pub fn claim(
+        &mut self,
+        group: &[u8],
+        consumer: &Bytes,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+        now: u64,
+    ) -> Option<(Vec<StreamEntry>, usize)> {
+        let entries = &self.entries;
+        let group = self.groups.get_mut(group)?;
+        if let Some(last_id) = options.last_id {
+            if last_id > group.last_id {
+                group.last_id = last_id;
+            }
+        }
+        group.consumer_mut(consumer, now);
+        let mut claimed = Vec::new();
+        let mut deleted = 0;
+        for id in ids {
+            let exists = entries.contains_key(id);
+            // FORCE 新建的待确认条目不做空闲时间检查，与 Redis 一致
+            let forced = options.force && exists && !group.pel.contains_key(id);
+            if forced {
+                group.pel.insert(
+                    *id,
+                    PendingEntry {
+                        consumer: consumer.clone(),
+                        delivery_time: now,
+                        delivery_count: 0,
+                    },
+                );
+            }
+            let delivery_time = match group.pel.get(id) {
+                Some(pending) => pending.delivery_time,
+                None => continue,
+            };
+            if !exists {
+                // 条目已被删除，直接从待确认列表中移除
+                group.ack(id);
+                deleted += 1;
+                continue;
+            }
+            if !forced && options.min_idle > 0 && now.saturating_sub(delivery_time) < options.min_idle {
+                continue;
+            }
+            group.transfer(*id, consumer);
+            let pending = group.pel.get_mut(id)?;
+            pending.delivery_time = options.delivery_time.unwrap_or(now);
+            match options.retry_count {
+                Some(count) => pending.delivery_count = count,
+                None if !options.justid => pending.delivery_count += 1,
+                None => {}
+            }
+            group.consumer_mut(consumer, now).active_time = Some(now);
+            claimed.push((*id, entries.get(id).cloned().unwrap_or_default()));
+        }
+        Some((claimed, deleted))
+    }
+
+    /// XAUTOCLAIM，只使用 options 中的 min_idle 和 justid，组不存在时返回 None
+    pub fn auto_claim(
+        &mut self,
+        group: &[u8],
+        consumer: &Bytes,
+        start: StreamId,
+        count: usize,
+        options: &ClaimOptions,
+        now: u64,
+    ) -> Option<AutoClaimResult> {
+        let entries = &self.entries;
+        let group = self.groups.get_mut(group)?;
+        group.consumer_mut(consumer, now);
+        let mut attempts = count.saturating_mul(10);
+        let mut claimed = Vec::new();
+        let mut deleted = Vec::new();
+        // 逐个向后查找，循环中会修改待确认列表，不能直接持有迭代器
+        let mut next = group.pel.range(start..).next().map(|(id, _)| *id);
+        while let Some(id) = next {
+            if attempts == 0 || claimed.len() >= count {
+                break;
+            }
+            attempts -= 1;
+            next = group
+                .pel
+                .range((Bound::Excluded(id), Bound::Unbounded))
+                .next()
+                .map(|(id, _)| *id);
+            if !entries.contains_key(&id) {
+                group.ack(&id);
+                deleted.push(id);
+                continue;
+            }
+            let idle = now.saturating_sub(group.pel[&id].delivery_time);
+            if idle < options.min_idle {
+                continue;
+            }
+            group.transfer(id, consumer);
+            let pending = group.pel.get_mut(&id)?;
+            pending.delivery_time = now;
+            if !options.justid {
+                pending.delivery_count += 1;
+            }
+            group.consumer_mut(consumer, now).active_time = Some(now);
+            claimed.push((id, entries[&id].clone()));
+        }
+        // 下一次从剩余的待确认条目开始，扫描完毕时返回 0-0
+        Some((next.unwrap_or(StreamId::MIN), claimed, deleted))
+    }
+}
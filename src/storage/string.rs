@@ -0,0 +1,102 @@
+use std::mem;
+
+use bytes::Bytes;
+
+use super::{parse_strict_int, Db, Item, Value, WRONGTYPE};
+
+/// 与 Redis 的 proto-max-bulk-len 默认值一致，字符串的最大长度
+pub const STRING_MAX_LEN: usize = 512 * 1024 * 1024;
+/// 不超过该长度的字符串使用 embstr 编码
+const EMBSTR_SIZE_LIMIT: usize = 44;
+
+/// 字符串：能按 Redis 的规则解析为 i64 的值使用整数编码，计数器自增时无需重新解析
+#[derive(Clone, Debug)]
+pub enum StringValue {
+    Int(i64),
+    Raw(Bytes),
+}
+
+impl From<Bytes> for StringValue {
+    /// 与 tryObjectEncoding 一致，尽量使用整数编码
+    fn from(value: Bytes) -> Self {
+        match parse_strict_int(&value) {
+            Some(n) => StringValue::Int(n),
+            None => StringValue::Raw(value),
+        }
+    }
+}
+
+impl StringValue {
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            StringValue::Int(n) => Bytes::from(n.to_string()),
+            StringValue::Raw(value) => value.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            StringValue::Int(n) => n.to_string().len(),
+            StringValue::Raw(value) => value.len(),
+        }
+    }
+
+    /// 按整数读取，非整数编码的值也会尝试严格解析
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            StringValue::Int(n) => Some(*n),
+            StringValue::Raw(value) => parse_strict_int(value),
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            StringValue::Int(_) => "int",
+            StringValue::Raw(value) if value.len() <= EMBSTR_SIZE_LIMIT => "embstr",
+            StringValue::Raw(_) => "raw",
+        }
+    }
+}
+
+impl Db {
+    /// 读取字符串，键不存在时返回 None
+    pub fn string(&self, key: &[u8]) -> Result<Option<Bytes>, String> {
+        Ok(self.string_value(key)?.map(StringValue::to_bytes))
+    }
+
+    /// 读取字符串的原始表示，不转换编码
+    pub fn string_value(&self, key: &[u8]) -> Result<Option<&StringValue>, String> {
+        match self.get(key).map(|item| &item.value) {
+            Some(Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// 在原字符串上修改并保留过期时间，键不存在时创建空字符串，长度不足 min_len 时用 0 填充
+    pub fn modify_string<T>(&mut self, key: &Bytes, min_len: usize, f: impl FnOnce(&mut Vec<u8>) -> T) -> Result<T, String> {
+        let mut value = match self.get_mut(key).map(|item| &mut item.value) {
+            Some(Value::String(value)) => match mem::replace(value, StringValue::Raw(Bytes::new())) {
+                StringValue::Int(n) => n.to_string().into_bytes(),
+                // 没有其他引用时不会复制
+                StringValue::Raw(value) => Vec::from(value),
+            },
+            Some(_) => return Err(WRONGTYPE.to_string()),
+            None => Vec::new(),
+        };
+        if value.len() < min_len {
+            value.resize(min_len, 0);
+        }
+        let result = f(&mut value);
+        self.update_string(key, StringValue::Raw(Bytes::from(value)));
+        Ok(result)
+    }
+
+    /// 写入字符串并保留原有的过期时间，键不存在时创建
+    pub fn update_string(&mut self, key: &Bytes, value: StringValue) {
+        match self.get_mut(key) {
+            Some(item) => item.value = Value::String(value),
+            None => self.insert(key.clone(), Item::new(Value::String(value))),
+        }
+    }
+}
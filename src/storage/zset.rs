@@ -0,0 +1,608 @@
+
+use bytes::Bytes;
+
+use crate::random;
+
+use super::{Db, Dict, Item, Value, WRONGTYPE};
+
+const MAX_LEVEL: usize = 32;
+const HEAD: usize = 0;
+
+/// 分数区间，minex/maxex 表示开区间
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreRange {
+    pub min: f64,
+    pub max: f64,
+    pub minex: bool,
+    pub maxex: bool,
+}
+
+impl ScoreRange {
+    fn gte_min(&self, score: f64) -> bool {
+        if self.minex {
+            score > self.min
+        } else {
+            score >= self.min
+        }
+    }
+
+    fn lte_max(&self, score: f64) -> bool {
+        if self.maxex {
+            score < self.max
+        } else {
+            score <= self.max
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min > self.max || (self.min == self.max && (self.minex || self.maxex))
+    }
+}
+
+/// 字典序区间的端点
+#[derive(Clone, Debug)]
+pub enum LexBound {
+    NegInf,
+    PosInf,
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+}
+
+#[derive(Clone, Debug)]
+pub struct LexRange {
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+impl LexRange {
+    fn gte_min(&self, member: &[u8]) -> bool {
+        match &self.min {
+            LexBound::NegInf => true,
+            LexBound::PosInf => false,
+            LexBound::Inclusive(min) => member >= &min[..],
+            LexBound::Exclusive(min) => member > &min[..],
+        }
+    }
+
+    fn lte_max(&self, member: &[u8]) -> bool {
+        match &self.max {
+            LexBound::NegInf => false,
+            LexBound::PosInf => true,
+            LexBound::Inclusive(max) => member <= &max[..],
+            LexBound::Exclusive(max) => member < &max[..],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match (&self.min, &self.max) {
+            (LexBound::PosInf, _) | (_, LexBound::NegInf) => true,
+            (LexBound::NegInf, _) | (_, LexBound::PosInf) => false,
+            (LexBound::Inclusive(min), LexBound::Inclusive(max)) => min > max,
+            (LexBound::Inclusive(min), LexBound::Exclusive(max))
+            | (LexBound::Exclusive(min), LexBound::Inclusive(max))
+            | (LexBound::Exclusive(min), LexBound::Exclusive(max)) => min >= max,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Level {
+    forward: Option<usize>,
+    span: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    member: Bytes,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Level>,
+}
+
+impl Node {
+    /// 节点是否排在 (score, member) 之前
+    fn less(&self, score: f64, member: &[u8]) -> bool {
+        self.score < score || (self.score == score && &self.member[..] < member)
+    }
+}
+
+/// 带跨度的跳表，与 Redis 的 zskiplist 相同，节点存放在数组中，用下标代替指针
+#[derive(Clone, Debug)]
+struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    tail: Option<usize>,
+    length: usize,
+    level: usize,
+}
+
+impl SkipList {
+    fn new() -> Self {
+        let head = Node {
+            member: Bytes::new(),
+            score: 0.0,
+            backward: None,
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0,
+                };
+                MAX_LEVEL
+            ],
+        };
+        Self {
+            nodes: vec![head],
+            free: Vec::new(),
+            tail: None,
+            length: 0,
+            level: 1,
+        }
+    }
+
+    fn random_level() -> usize {
+        let mut level = 1;
+        // 每层晋升概率为 1/4
+        while level < MAX_LEVEL && random::next_u64() & 3 == 0 {
+            level += 1;
+        }
+        level
+    }
+
+    fn forward(&self, x: usize, i: usize) -> Option<usize> {
+        self.nodes[x].levels[i].forward
+    }
+
+    fn insert(&mut self, score: f64, member: Bytes) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0usize; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(f) = self.forward(x, i) {
+                if !self.nodes[f].less(score, &member) {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = f;
+            }
+            update[i] = x;
+        }
+        let level = Self::random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].levels[i].span = self.length;
+            }
+            self.level = level;
+        }
+        let node = Node {
+            member,
+            score,
+            backward: if update[0] == HEAD { None } else { Some(update[0]) },
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0,
+                };
+                level
+            ],
+        };
+        let new = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        for i in 0..level {
+            let prev = update[i];
+            self.nodes[new].levels[i].forward = self.nodes[prev].levels[i].forward;
+            self.nodes[prev].levels[i].forward = Some(new);
+            self.nodes[new].levels[i].span = self.nodes[prev].levels[i].span - (rank[0] - rank[i]);
+            self.nodes[prev].levels[i].span = (rank[0] - rank[i]) + 1;
+        }
+        for (i, prev) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[*prev].levels[i].span += 1;
+        }
+        match self.forward(new, 0) {
+            Some(next) => self.nodes[next].backward = Some(new),
+            None => self.tail = Some(new),
+        }
+        self.length += 1;
+    }
+
+    fn delete(&mut self, score: f64, member: &[u8]) -> bool {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(f) = self.forward(x, i) {
+                if !self.nodes[f].less(score, member) {
+                    break;
+                }
+                x = f;
+            }
+            update[i] = x;
+        }
+        match self.forward(x, 0) {
+            Some(x) if self.nodes[x].score == score && self.nodes[x].member == member => {
+                self.delete_node(x, &update);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn delete_node(&mut self, x: usize, update: &[usize; MAX_LEVEL]) {
+        for (i, prev) in update.iter().enumerate().take(self.level) {
+            if self.nodes[*prev].levels[i].forward == Some(x) {
+                self.nodes[*prev].levels[i].span += self.nodes[x].levels[i].span;
+                self.nodes[*prev].levels[i].span -= 1;
+                self.nodes[*prev].levels[i].forward = self.nodes[x].levels[i].forward;
+            } else {
+                self.nodes[*prev].levels[i].span -= 1;
+            }
+        }
+        match self.forward(x, 0) {
+            Some(next) => self.nodes[next].backward = self.nodes[x].backward,
+            None => self.tail = self.nodes[x].backward,
+        }
+        while self.level > 1 && self.nodes[HEAD].levels[self.level - 1].forward.is_none() {
+            self.level -= 1;
+        }
+        self.length -= 1;
+        self.nodes[x].member = Bytes::new();
+        self.nodes[x].levels = Vec::new();
+        self.free.push(x);
+    }
+
+    /// 从 1 开始的排名
+    fn rank(&self, score: f64, member: &[u8]) -> Option<usize> {
+        let mut x = HEAD;
+        let mut rank = 0;
+        for i in (0..self.level).rev() {
+            while let Some(f) = self.forward(x, i) {
+                let node = &self.nodes[f];
+                if !(node.score < score || (node.score == score && &node.member[..] <= member)) {
+                    break;
+                }
+                rank += self.nodes[x].levels[i].span;
+                x = f;
+            }
+            if x != HEAD && self.nodes[x].member == member {
+                return Some(rank);
+            }
+        }
+        None
+    }
+
+    /// 按从 1 开始的排名查找节点
+    fn by_rank(&self, rank: usize) -> Option<usize> {
+        let mut x = HEAD;
+        let mut traversed = 0;
+        for i in (0..self.level).rev() {
+            while let Some(f) = self.forward(x, i) {
+                if traversed + self.nodes[x].levels[i].span > rank {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = f;
+            }
+            if traversed == rank {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    fn first_in_score_range(&self, range: &ScoreRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(f) = self.forward(x, i) {
+                if range.gte_min(self.nodes[f].score) {
+                    break;
+                }
+                x = f;
+            }
+        }
+        self.forward(x, 0)
+            .filter(|&x| range.lte_max(self.nodes[x].score))
+    }
+
+    fn last_in_score_range(&self, range: &ScoreRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(f) = self.forward(x, i) {
+                if !range.lte_max(self.nodes[f].score) {
+                    break;
+                }
+                x = f;
+            }
+        }
+        Some(x).filter(|&x| x != HEAD && range.gte_min(self.nodes[x].score))
+    }
+
+    fn first_in_lex_range(&self, range: &LexRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(f) = self.forward(x, i) {
+                if range.gte_min(&self.nodes[f].member) {
+                    break;
+                }
+                x = f;
+            }
+        }
+        self.forward(x, 0)
+            .filter(|&x| range.lte_max(&self.nodes[x].member))
+    }
+
+    fn last_in_lex_range(&self, range: &LexRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(f) = self.forward(x, i) {
+                if !range.lte_max(&self.nodes[f].member) {
+                    break;
+                }
+                x = f;
+            }
+        }
+        Some(x).filter(|&x| x != HEAD && range.gte_min(&self.nodes[x].member))
+    }
+
+    fn entry(&self, x: usize) -> (Bytes, f64) {
+        (self.nodes[x].member.clone(), self.nodes[x].score)
+    }
+
+    fn next(&self, x: usize, rev: bool) -> Option<usize> {
+        if rev {
+            self.nodes[x].backward
+        } else {
+            self.forward(x, 0)
+        }
+    }
+}
+
+/// 有序集合：成员到分数的映射加上按 (分数, 成员) 排序的跳表
+#[derive(Clone, Debug)]
+pub struct SortedSet {
+    dict: Dict<Bytes, f64>,
+    list: SkipList,
+}
+
+impl Default for SortedSet {
+    fn default() -> Self {
+        Self {
+            dict: Dict::new(),
+            list: SkipList::new(),
+        }
+    }
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        self.dict.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dict.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.dict.get(member).copied()
+    }
+
+    /// 添加或更新成员，返回是否为新成员
+    pub fn insert(&mut self, member: Bytes, score: f64) -> bool {
+        match self.dict.get(&member).copied() {
+            Some(current) => {
+                if current != score {
+                    self.list.delete(current, &member);
+                    self.list.insert(score, member.clone());
+                    self.dict.insert(member, score);
+                }
+                false
+            }
+            None => {
+                self.list.insert(score, member.clone());
+                self.dict.insert(member, score);
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.dict.remove(member) {
+            Some(score) => {
+                self.list.delete(score, member);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 按游标遍历成员和分数
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(Bytes, f64)>) {
+        let (cursor, found) = self.dict.scan_batch(cursor, count);
+        (cursor, found.into_iter().map(|(member, score)| (member.clone(), *score)).collect())
+    }
+
+    /// 从 0 开始的排名，rev 为 true 时按分数从高到低
+    pub fn rank(&self, member: &[u8], rev: bool) -> Option<usize> {
+        let score = self.score(member)?;
+        let rank = self.list.rank(score, member)?;
+        Some(if rev { self.len() - rank } else { rank - 1 })
+    }
+
+    /// 按排名闭区间 [start, end] 读取成员
+    pub fn range_by_rank(&self, start: usize, end: usize, rev: bool) -> Vec<(Bytes, f64)> {
+        let first = if rev {
+            self.list.by_rank(self.len() - start)
+        } else {
+            self.list.by_rank(start + 1)
+        };
+        self.collect(first, rev, 0, Some(end - start + 1), |_| true)
+    }
+
+    /// 按分数区间读取成员，offset/count 对应 LIMIT 参数
+    pub fn range_by_score(
+        &self,
+        range: &ScoreRange,
+        rev: bool,
+        offset: usize,
+        count: Option<usize>,
+    ) -> Vec<(Bytes, f64)> {
+        let first = if rev {
+            self.list.last_in_score_range(range)
+        } else {
+            self.list.first_in_score_range(range)
+        };
+        self.collect(first, rev, offset, count, |(_, score)| {
+            if rev {
+                range.gte_min(score)
+            } else {
+                range.lte_max(score)
+            }
+        })
+    }
+
+    pub fn range_by_lex(
+        &self,
+        range: &LexRange,
+        rev: bool,
+        offset: usize,
+        count: Option<usize>,
+    ) -> Vec<(Bytes, f64)> {
+        let first = if rev {
+            self.list.last_in_lex_range(range)
+        } else {
+            self.list.first_in_lex_range(range)
+        };
+        self.collect(first, rev, offset, count, |(member, _)| {
+            if rev {
+                range.gte_min(member)
+            } else {
+                range.lte_max(member)
+            }
+        })
+    }
+
+    pub fn count_in_score_range(&self, range: &ScoreRange) -> usize {
+        self.count_between(
+            self.list.first_in_score_range(range),
+            self.list.last_in_score_range(range),
+        )
+    }
+
+    pub fn count_in_lex_range(&self, range: &LexRange) -> usize {
+        self.count_between(
+            self.list.first_in_lex_range(range),
+            self.list.last_in_lex_range(range),
+        )
+    }
+
+    /// 弹出分数最小（或最大）的成员
+    pub fn pop(&mut self, max: bool) -> Option<(Bytes, f64)> {
+        let x = if max {
+            self.list.tail?
+        } else {
+            self.list.forward(HEAD, 0)?
+        };
+        let (member, score) = self.list.entry(x);
+        self.remove(&member);
+        Some((member, score))
+    }
+
+    /// 按分数从低到高遍历
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, f64)> + '_ {
+        let mut x = self.list.forward(HEAD, 0);
+        std::iter::from_fn(move || {
+            let node = &self.list.nodes[x?];
+            x = node.levels[0].forward;
+            Some((&node.member, node.score))
+        })
+    }
+
+    fn count_between(&self, first: Option<usize>, last: Option<usize>) -> usize {
+        match (first, last) {
+            (Some(first), Some(last)) => {
+                let (first_member, first_score) = self.list.entry(first);
+                let (last_member, last_score) = self.list.entry(last);
+                let first_rank = self.list.rank(first_score, &first_member).unwrap_or(0);
+                let last_rank = self.list.rank(last_score, &last_member).unwrap_or(0);
+                (last_rank + 1).saturating_sub(first_rank)
+            }
+            _ => 0,
+        }
+    }
+
+    fn collect(
+        &self,
+        first: Option<usize>,
+        rev: bool,
+        offset: usize,
+        count: Option<usize>,
+        in_range: impl Fn((&[u8], f64)) -> bool,
+    ) -> Vec<(Bytes, f64)> {
+        let mut result = Vec::new();
+        let mut x = first;
+        let mut skipped = 0;
+        while let Some(node) = x {
+            if count.is_some_and(|count| result.len() >= count) {
+                break;
+            }
+            let (member, score) = (&self.list.nodes[node].member, self.list.nodes[node].score);
+            if !in_range((member, score)) {
+                break;
+            }
+            if skipped < offset {
+                skipped += 1;
+            } else {
+                result.push((member.clone(), score));
+            }
+            x = self.list.next(node, rev);
+        }
+        result
+    }
+}
+
+impl Db {
+    /// 读取有序集合，键不存在时返回 None
+    pub fn zset(&self, key: &[u8]) -> Result<Option<&SortedSet>, String> {
+        match self.get(key).map(|item| &item.value) {
+            Some(Value::ZSet(zset)) => Ok(Some(zset)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn zset_mut(&mut self, key: &[u8]) -> Result<Option<&mut SortedSet>, String> {
+        match self.get_mut(key).map(|item| &mut item.value) {
+            Some(Value::ZSet(zset)) => Ok(Some(zset)),
+            Some(_) => Err(WRONGTYPE.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// 读取有序集合，键不存在时创建空的有序集合
+    pub fn zset_or_insert(&mut self, key: &Bytes) -> Result<&mut SortedSet, String> {
+        if self.get_mut(key).is_none() {
+            self.insert(key.clone(), Item::new(Value::ZSet(SortedSet::default())));
+        }
+        self.zset_mut(key).map(|zset| zset.unwrap())
+    }
+}